@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use rustier_chess::{
+    board::Board, moves::move_mask_gen::MoveGenMasks, utils::zobrist::ZobristHasher,
+};
+
+// the same perft walked with the three ways of taking a move back
+
+fn perft_make_unmake(
+    board: &mut Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+    depth: u8,
+) -> usize {
+    let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
+    if depth == 1 {
+        return legal_moves.len();
+    }
+
+    let mut nodes = 0;
+    for legal_move in legal_moves {
+        let unmake_move_helper = board.make_move(&legal_move, hasher);
+        nodes += perft_make_unmake(board, move_gen_masks, hasher, depth - 1);
+        board.unmake_move(unmake_move_helper, hasher);
+    }
+    nodes
+}
+
+fn perft_play_undo(
+    board: &mut Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+    depth: u8,
+) -> usize {
+    let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
+    if depth == 1 {
+        return legal_moves.len();
+    }
+
+    let mut nodes = 0;
+    for legal_move in legal_moves {
+        board.play(&legal_move, hasher);
+        nodes += perft_play_undo(board, move_gen_masks, hasher, depth - 1);
+        board.undo(hasher);
+    }
+    nodes
+}
+
+fn perft_copy_make(
+    board: &Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+    depth: u8,
+) -> usize {
+    let legal_moves = board.clone().get_legal_moves(move_gen_masks, hasher);
+    if depth == 1 {
+        return legal_moves.len();
+    }
+
+    legal_moves
+        .iter()
+        .map(|legal_move| {
+            let child = board.after_move(legal_move, hasher);
+            perft_copy_make(&child, move_gen_masks, hasher, depth - 1)
+        })
+        .sum()
+}
+
+fn main() {
+    let hasher = ZobristHasher::load();
+    let move_gen_masks = MoveGenMasks::load();
+    let positions = [
+        (
+            "startpos",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            5,
+        ),
+        (
+            "kiwipete",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            4,
+        ),
+    ];
+
+    for (name, fen, depth) in positions {
+        let board = Board::from_fen(fen, &hasher).unwrap();
+
+        let now = Instant::now();
+        let nodes = perft_make_unmake(&mut board.clone(), &move_gen_masks, &hasher, depth);
+        println!(
+            "{} make/unmake: {} nodes in {:.2?}",
+            name,
+            nodes,
+            now.elapsed()
+        );
+
+        let now = Instant::now();
+        let nodes = perft_play_undo(&mut board.clone(), &move_gen_masks, &hasher, depth);
+        println!(
+            "{} play/undo:   {} nodes in {:.2?}",
+            name,
+            nodes,
+            now.elapsed()
+        );
+
+        let now = Instant::now();
+        let nodes = perft_copy_make(&board, &move_gen_masks, &hasher, depth);
+        println!(
+            "{} copy-make:   {} nodes in {:.2?}",
+            name,
+            nodes,
+            now.elapsed()
+        );
+    }
+}
@@ -2,14 +2,17 @@ use std::error::Error;
 use std::fmt;
 use std::ops::{Add, Sub};
 use std::str::FromStr;
+use std::sync::Arc;
 
+use crate::bots::nnue::{Network, NnueState};
+use crate::bots::pesto::PestoScore;
+use crate::moves::geometry::pawn_attacks;
 use crate::moves::move_mask_gen::MoveGenMasks;
-use crate::moves::moves_calculation::{get_all_moves, is_square_in_check};
-use crate::moves::moves_utils::{Move, UnmakeMoveHelper};
-use crate::types::state::{
-    BLACK_LONG_ROOK_STARTING_MASK, BLACK_SHORT_ROOK_STARTING_MASK, WHITE_LONG_ROOK_STARTING_MASK,
-    WHITE_SHORT_ROOK_STARTING_MASK,
+use crate::moves::moves_calculation::{
+    castling_obstacle, get_all_captures, get_all_moves, get_castling_candidate, is_square_in_check,
 };
+use crate::moves::moves_utils::{Move, MoveKind, NullMoveHelper, UnmakeMoveHelper};
+use crate::types::state::{LONG, SHORT};
 use crate::types::{
     bitboard::BitBoard,
     piece::{Color, Piece, Pieces},
@@ -18,6 +21,205 @@ use crate::types::{
 };
 use crate::utils::zobrist::{ZobristHash, ZobristHasher};
 
+const BACK_RANKS_MASK: BitBoard = BitBoard(0xff000000000000ff);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    WrongNumberOfFields(usize),
+    BadRankCount(usize),
+    /// rank number 1..=8 that does not describe exactly eight squares
+    BadRankLength(u8),
+    BadPieceChar(char),
+    WrongSide(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    EnPassantWrongRank(Square),
+    InvalidHalfMove(String),
+    InvalidFullMove(String),
+    MissingKing(usize),
+    TooManyKings(usize),
+    TooManyPawns(usize),
+    PawnsOnBackRank,
+    SideNotToMoveInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color_name = |color: &usize| {
+            if *color == Color::WHITE {
+                "white"
+            } else {
+                "black"
+            }
+        };
+        match self {
+            FenError::WrongNumberOfFields(n) => write!(f, "Fen has {} fields", n),
+            FenError::BadRankCount(n) => write!(f, "Fen has {} ranks instead of 8", n),
+            FenError::BadRankLength(rank) => {
+                write!(f, "Rank {} does not have exactly 8 squares", rank)
+            }
+            FenError::BadPieceChar(c) => write!(f, "Invalid piece char {}", c),
+            FenError::WrongSide(side) => write!(f, "Invalid side to move {}", side),
+            FenError::InvalidCastling(castling) => write!(f, "Invalid castling {}", castling),
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "Invalid en passant square {}", square)
+            }
+            FenError::EnPassantWrongRank(square) => {
+                write!(f, "En passant square {} is on the wrong rank", square)
+            }
+            FenError::InvalidHalfMove(half_moves) => {
+                write!(f, "Invalid half move clock {}", half_moves)
+            }
+            FenError::InvalidFullMove(full_moves) => {
+                write!(f, "Invalid full move number {}", full_moves)
+            }
+            FenError::MissingKing(color) => write!(f, "Missing {} king", color_name(color)),
+            FenError::TooManyKings(color) => write!(f, "Too many {} kings", color_name(color)),
+            FenError::TooManyPawns(color) => write!(f, "Too many {} pawns", color_name(color)),
+            FenError::PawnsOnBackRank => write!(f, "Pawns on the first or last rank"),
+            FenError::SideNotToMoveInCheck => write!(f, "Side not to move is in check"),
+        }
+    }
+}
+
+impl Error for FenError {}
+
+/// Broken invariant found by `Board::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    AllPiecesMismatch,
+    OverlappingColors,
+    /// colour whose piece boards overlap
+    OverlappingPieces(usize),
+    /// colour whose piece boards do not add up to its colour board
+    ColorMismatch(usize),
+    MailboxMismatch,
+    /// colour without exactly one king
+    KingCount(usize),
+    PawnsOnBackRank,
+    TurnMismatch,
+    /// (colour, side) castling right without its king and rook
+    InvalidCastling(usize, usize),
+    InvalidEnPassant(Square),
+    HashMismatch {
+        expected: ZobristHash,
+        found: ZobristHash,
+    },
+    PawnKeyMismatch,
+    MaterialKeyMismatch,
+    /// colour whose non-pawn key is out of sync
+    NonPawnKeyMismatch(usize),
+    PestoMismatch,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::AllPiecesMismatch => write!(f, "all_pieces is not the union of colors"),
+            BoardError::OverlappingColors => write!(f, "White and black pieces overlap"),
+            BoardError::OverlappingPieces(color) => {
+                write!(f, "Piece boards of color {} overlap", color)
+            }
+            BoardError::ColorMismatch(color) => {
+                write!(
+                    f,
+                    "Piece boards of color {} do not match its color board",
+                    color
+                )
+            }
+            BoardError::MailboxMismatch => write!(f, "Mailbox does not match the bitboards"),
+            BoardError::KingCount(color) => {
+                write!(f, "Color {} does not have exactly one king", color)
+            }
+            BoardError::PawnsOnBackRank => write!(f, "Pawns on the first or last rank"),
+            BoardError::TurnMismatch => write!(f, "Turn and opponent are the same color"),
+            BoardError::InvalidCastling(color, side) => write!(
+                f,
+                "Castling right {} of color {} without king and rook in place",
+                side, color
+            ),
+            BoardError::InvalidEnPassant(square) => {
+                write!(f, "Invalid en passant square {}", square)
+            }
+            BoardError::HashMismatch { expected, found } => write!(
+                f,
+                "Zobrist hash {:x} does not match recomputed {:x}",
+                found.0, expected.0
+            ),
+            BoardError::PawnKeyMismatch => write!(f, "Pawn key is out of sync"),
+            BoardError::MaterialKeyMismatch => write!(f, "Material key is out of sync"),
+            BoardError::NonPawnKeyMismatch(color) => {
+                write!(f, "Non-pawn key of color {} is out of sync", color)
+            }
+            BoardError::PestoMismatch => write!(f, "Incremental PeSTO score is out of sync"),
+        }
+    }
+}
+
+impl Error for BoardError {}
+
+/// Why `Board::check_and_make_move` refused a move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IllegalMoveError {
+    NoPieceOnOrigin(Square),
+    /// origin square holding a piece of the side not to move
+    WrongColor(Square),
+    OwnPieceOnDestination(Square),
+    /// (origin, destination) the piece cannot reach even on an empty board
+    UnreachableDestination(Square, Square),
+    BlockedPath,
+    LeavesKingInCheck,
+    MissingPromotion,
+    CastlingThroughCheck,
+}
+
+impl fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalMoveError::NoPieceOnOrigin(square) => write!(f, "No piece on {}", square),
+            IllegalMoveError::WrongColor(square) => {
+                write!(f, "Piece on {} belongs to the side not to move", square)
+            }
+            IllegalMoveError::OwnPieceOnDestination(square) => {
+                write!(
+                    f,
+                    "Destination {} holds a piece of the side to move",
+                    square
+                )
+            }
+            IllegalMoveError::UnreachableDestination(origin, destination) => {
+                write!(f, "Piece on {} cannot move to {}", origin, destination)
+            }
+            IllegalMoveError::BlockedPath => write!(f, "Path is blocked"),
+            IllegalMoveError::LeavesKingInCheck => write!(f, "Move leaves the king in check"),
+            IllegalMoveError::MissingPromotion => write!(f, "Promotion piece is missing"),
+            IllegalMoveError::CastlingThroughCheck => {
+                write!(f, "King castles out of or through check")
+            }
+        }
+    }
+}
+
+impl Error for IllegalMoveError {}
+
+/// Leniency switches for `Board::from_fen_with_options`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FenOptions {
+    /// accepts four or five field fens, half moves default to 0 and full moves to 1
+    pub allow_missing_counters: bool,
+    /// skips kings, pawns, castling, en passant and check sanity checks
+    pub skip_position_checks: bool,
+}
+
+impl FenOptions {
+    pub fn lenient() -> Self {
+        Self {
+            allow_missing_counters: true,
+            skip_position_checks: false,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Board {
     pub colors: [BitBoard; 2],
@@ -25,7 +227,22 @@ pub struct Board {
     pub all_pieces: BitBoard,
     pub state: State,
     pub zobrist: ZobristHash,
+    /// pawns only, keys pawn structure caches
+    pub pawn_key: ZobristHash,
+    /// piece counts only, keys material tables and endgame recognition
+    pub material_key: ZobristHash,
+    /// every piece but pawns, per colour
+    pub non_pawn_keys: [ZobristHash; 2],
     pub position_history: Vec<ZobristHash>,
+    /// index in `position_history` of the position after the last null move,
+    /// repetitions are only counted from there
+    pub null_move_start: usize,
+    /// irreversible state of every move made with `play`, popped by `undo`
+    pub state_stack: Vec<UnmakeMoveHelper>,
+    pub pesto: PestoScore,
+    pub nnue: Option<NnueState>,
+    /// piece on every square, kept in sync with the bitboards
+    pub mailbox: [Option<Piece>; 64],
 }
 
 impl Board {
@@ -42,7 +259,10 @@ impl Board {
         if self.state.half_moves < 6 {
             return false;
         }
-        self.position_history[(pos_history_len - self.state.half_moves as usize)..pos_history_len]
+        // a FEN half-move clock counts plies that were never recorded in the history
+        let window = (self.state.half_moves as usize)
+            .min(pos_history_len.saturating_sub(self.null_move_start));
+        self.position_history[(pos_history_len - window)..pos_history_len]
             .iter()
             .filter(|history| history == &&self.zobrist)
             .collect::<Vec<&ZobristHash>>()
@@ -53,6 +273,13 @@ impl Board {
     pub fn clear_piece(&mut self, square: &Square, piece: usize, color: usize) {
         self.colors[color].set_zero(square);
         self.pieces[color][piece].set_zero(square);
+        self.mailbox[square.as_usize()] = None;
+    }
+
+    pub fn put_piece(&mut self, square: &Square, piece: usize, color: usize) {
+        self.colors[color].set_one(square);
+        self.pieces[color][piece].set_one(square);
+        self.mailbox[square.as_usize()] = Some(Piece::new(piece, color));
     }
 
     pub fn move_piece(
@@ -62,9 +289,8 @@ impl Board {
         piece: usize,
         color: usize,
     ) {
-        self.colors[color].set_one(destination);
-        self.pieces[color][piece].set_one(destination);
         self.clear_piece(origin, piece, color);
+        self.put_piece(destination, piece, color);
     }
 
     pub fn sync_all_pieces(&mut self) {
@@ -78,9 +304,7 @@ impl Board {
     ) -> Vec<Move> {
         self.get_legal_moves(move_gen_masks, hasher)
             .into_iter()
-            .filter(|legal_move| {
-                self.colors[self.state.turn].read_square(&legal_move.get_destination())
-            })
+            .filter(|legal_move| legal_move.is_capture())
             .collect()
     }
 
@@ -89,34 +313,98 @@ impl Board {
         is_square_in_check(&king_square, self, move_gen_masks)
     }
 
+    /// Pieces of both colours attacking `square` when only `occupancy` blocks the sliders
+    pub fn attackers_to(
+        &self,
+        square: &Square,
+        occupancy: BitBoard,
+        move_gen_masks: &MoveGenMasks,
+    ) -> BitBoard {
+        let pieces =
+            |piece: usize| self.pieces[Color::WHITE][piece] | self.pieces[Color::BLACK][piece];
+        let square_bb = BitBoard::zeros_with_one_bit(square).0;
+        let white_pawns = BitBoard(pawn_attacks(square_bb, Color::BLACK))
+            & self.pieces[Color::WHITE][Pieces::PAWN];
+        let black_pawns = BitBoard(pawn_attacks(square_bb, Color::WHITE))
+            & self.pieces[Color::BLACK][Pieces::PAWN];
+        let queens = pieces(Pieces::QUEEN);
+
+        white_pawns
+            | black_pawns
+            | (move_gen_masks.knight_moves[square.as_usize()] & pieces(Pieces::KNIGHT))
+            | (move_gen_masks.king_moves[square.as_usize()] & pieces(Pieces::KING))
+            | (move_gen_masks.rook_moves(square, occupancy) & (pieces(Pieces::ROOK) | queens))
+            | (move_gen_masks.bishop_moves(square, occupancy) & (pieces(Pieces::BISHOP) | queens))
+    }
+
+    /// Opponent pieces giving check to the side to move
+    pub fn checkers(&self, move_gen_masks: &MoveGenMasks) -> BitBoard {
+        let king_square = self.pieces[self.state.turn][Pieces::KING].get_one();
+        self.attackers_to(&king_square, self.all_pieces, move_gen_masks)
+            & self.colors[self.state.opponent]
+    }
+
+    /// Pieces of `color` that are the only blocker between their king and an enemy slider
+    pub fn pinned(&self, color: usize, move_gen_masks: &MoveGenMasks) -> BitBoard {
+        let king_square = self.pieces[color][Pieces::KING].get_one();
+        let enemies = &self.pieces[1 - color];
+        let snipers = (move_gen_masks.rook_moves(&king_square, BitBoard::zeros())
+            & (enemies[Pieces::ROOK] | enemies[Pieces::QUEEN]))
+            | (move_gen_masks.bishop_moves(&king_square, BitBoard::zeros())
+                & (enemies[Pieces::BISHOP] | enemies[Pieces::QUEEN]));
+
+        let mut pinned = BitBoard::zeros();
+        for sniper in snipers.get_ones() {
+            let blockers = move_gen_masks.geometry.between(&king_square, &sniper) & self.all_pieces;
+            if blockers.0.count_ones() == 1 {
+                pinned |= blockers;
+            }
+        }
+        pinned & self.colors[color]
+    }
+
     pub fn get_legal_moves(
         &mut self,
         move_gen_masks: &MoveGenMasks,
         hasher: &ZobristHasher,
     ) -> Vec<Move> {
-        let all_moves: Vec<Move> = get_all_moves(self, move_gen_masks)
+        let all_moves = get_all_moves(self, move_gen_masks);
+        self.keep_legal(all_moves, move_gen_masks, hasher)
+    }
+
+    /// `get_legal_moves` without the quiet moves
+    pub fn get_legal_captures(
+        &mut self,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Vec<Move> {
+        let captures = get_all_captures(self, move_gen_masks);
+        self.keep_legal(captures, move_gen_masks, hasher)
+    }
+
+    fn keep_legal(
+        &mut self,
+        moves: Vec<Move>,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Vec<Move> {
+        moves
             .into_iter()
-            .filter_map(|the_move| {
-                let unmake_move_helper = self.make_move(&the_move, hasher);
+            .filter(|the_move| {
+                let unmake_move_helper = self.make_move(the_move, hasher);
 
                 // this turn changing is dumb. Can I make it better?
                 self.state.change_turn();
-                if !is_square_in_check(
+                let is_legal = !is_square_in_check(
                     &self.pieces[self.state.turn][Pieces::KING].get_one(),
                     self,
                     move_gen_masks,
-                ) {
-                    self.state.change_turn();
-                    self.unmake_move(unmake_move_helper);
-                    return Some(the_move);
-                }
+                );
                 self.state.change_turn();
-                self.unmake_move(unmake_move_helper);
-                None
+                self.unmake_move(unmake_move_helper, hasher);
+                is_legal
             })
-            .collect();
-
-        all_moves
+            .collect()
     }
 
     pub fn make_move(&mut self, the_move: &Move, hasher: &ZobristHasher) -> UnmakeMoveHelper {
@@ -126,12 +414,18 @@ impl Board {
         let prev_en_passant = self.state.en_passant;
         let prev_halfmove = self.state.half_moves;
         let prev_hash = self.zobrist;
+        let prev_pawn_key = self.pawn_key;
+        let prev_material_key = self.material_key;
+        let prev_non_pawn_keys = self.non_pawn_keys;
         let prev_castling: Castling = self.state.castling;
+        let prev_pesto = self.pesto;
 
         let move_bb =
             BitBoard::zeros_with_one_bit(&origin) ^ BitBoard::zeros_with_one_bit(&destination);
 
-        let is_capture = self.colors[self.state.opponent].read_square(&destination);
+        let captured_piece = self.mailbox[destination.as_usize()]
+            .filter(|piece| piece.color() == self.state.opponent)
+            .map(|piece| piece.piece());
 
         self.state.increment_half_move();
 
@@ -141,67 +435,62 @@ impl Board {
             self.zobrist ^= hasher.hash_en_passant(self, self.state.turn);
         }
 
-        self.colors[self.state.turn] ^= move_bb;
-        for (piece_type, piece_bitboard) in self.pieces[self.state.turn].iter_mut().enumerate() {
-            if piece_bitboard.read_square(&origin) {
-                self.zobrist ^= hasher.hash_piece_at_square(&piece_type, &self.state.turn, &origin);
-                self.zobrist ^=
-                    hasher.hash_piece_at_square(&piece_type, &self.state.turn, &destination);
+        if the_move.is_castling() {
+            moving_piece_type = Pieces::KING;
+            self.make_castling_move(the_move, hasher);
+        } else {
+            if let Some(piece_type) = captured_piece {
+                self.clear_piece(&destination, piece_type, self.state.opponent);
+                capture = Some(piece_type);
+                self.pesto
+                    .remove_piece(piece_type, self.state.opponent, &destination);
+                self.hash_piece(hasher, piece_type, self.state.opponent, &destination);
+                self.update_material_key(hasher, piece_type, self.state.opponent, false);
+                self.state.reset_half_move();
+            }
+
+            self.colors[self.state.turn] ^= move_bb;
+            if let Some(piece_type) = self.mailbox[origin.as_usize()]
+                .filter(|piece| piece.color() == self.state.turn)
+                .map(|piece| piece.piece())
+            {
+                self.hash_piece(hasher, piece_type, self.state.turn, &origin);
+                self.hash_piece(hasher, piece_type, self.state.turn, &destination);
                 moving_piece_type = piece_type;
-                *piece_bitboard ^= move_bb;
+                self.pieces[self.state.turn][piece_type] ^= move_bb;
+                self.mailbox[destination.as_usize()] = self.mailbox[origin.as_usize()].take();
+                self.pesto
+                    .move_piece(piece_type, self.state.turn, &origin, &destination);
                 if piece_type == Pieces::PAWN {
                     self.state.reset_half_move();
                 }
-                break;
             }
         }
 
-        if is_capture {
-            self.colors[self.state.opponent].set_zero(&destination);
-            for (piece_type, piece_bitboard) in
-                self.pieces[self.state.opponent].iter_mut().enumerate()
-            {
-                if piece_bitboard.read_square(&destination) {
-                    capture = Some(piece_type);
-                    *piece_bitboard &= !move_bb;
-                    self.zobrist ^= hasher.hash_piece_at_square(
-                        &piece_type,
-                        &self.state.opponent,
-                        &destination,
-                    );
-                    break;
-                }
-            }
-            self.state.reset_half_move();
-        } else if let Some(en_passant_square) = self.state.en_passant {
-            // is en_passant capture
-            if destination == en_passant_square && moving_piece_type == Pieces::PAWN {
+        self.state.en_passant = None;
+
+        match the_move.kind() {
+            MoveKind::EnPassant => {
                 let capture_square = Square::new(origin.get_rank() * 8 + destination.get_file());
                 self.clear_piece(&capture_square, Pieces::PAWN, self.state.opponent);
-                self.zobrist ^= hasher.hash_piece_at_square(
-                    &Pieces::PAWN,
-                    &self.state.opponent,
-                    &capture_square,
-                );
+                self.pesto
+                    .remove_piece(Pieces::PAWN, self.state.opponent, &capture_square);
+                self.hash_piece(hasher, Pieces::PAWN, self.state.opponent, &capture_square);
+                self.update_material_key(hasher, Pieces::PAWN, self.state.opponent, false);
             }
-        }
-
-        self.state.en_passant = None;
-
-        match the_move.special_move() {
-            0 => (),
-            1 => {
-                // 1 promotion
-                self.pieces[self.state.turn][Pieces::PAWN] &= !move_bb;
-                self.zobrist ^=
-                    hasher.hash_piece_at_square(&Pieces::PAWN, &self.state.turn, &destination);
-                let promotion_piece = the_move.get_promotion_piece();
-                self.pieces[self.state.turn][promotion_piece].set_one(&destination);
-                self.zobrist ^=
-                    hasher.hash_piece_at_square(&promotion_piece, &self.state.turn, &destination);
-            }
-            2 => {
-                // 2 en passant
+            MoveKind::Promotion(promotion_piece) | MoveKind::PromotionCapture(promotion_piece) => {
+                self.clear_piece(&destination, Pieces::PAWN, self.state.turn);
+                self.put_piece(&destination, promotion_piece, self.state.turn);
+                self.pesto
+                    .remove_piece(Pieces::PAWN, self.state.turn, &destination);
+                self.pesto
+                    .add_piece(promotion_piece, self.state.turn, &destination);
+                self.hash_piece(hasher, Pieces::PAWN, self.state.turn, &destination);
+                self.hash_piece(hasher, promotion_piece, self.state.turn, &destination);
+                self.update_material_key(hasher, Pieces::PAWN, self.state.turn, false);
+                self.update_material_key(hasher, promotion_piece, self.state.turn, true);
+            }
+            MoveKind::DoublePush => {
                 let en_passant_rank = if self.state.turn == Color::WHITE {
                     origin.get_rank().add(1)
                 } else {
@@ -211,70 +500,35 @@ impl Board {
                 self.state.en_passant = Some(en_passant_square);
                 self.zobrist ^= hasher.hash_en_passant(self, self.state.opponent);
             }
-            3 => {
-                // 3 castling
-                let (rook_origin_file, rook_destination_file) = if destination.get_file() == 2 {
-                    // long
-                    (0, 3)
-                } else {
-                    // short
-                    (7, 5)
-                };
-                let rank = origin.get_rank() * 8;
-                let rook_origin = Square::new(rank + rook_origin_file);
-                let rook_destination = Square::new(rank + rook_destination_file);
-                self.move_piece(
-                    &rook_origin,
-                    &rook_destination,
-                    Pieces::ROOK,
-                    self.state.turn,
-                );
-                self.zobrist ^=
-                    hasher.hash_piece_at_square(&Pieces::ROOK, &self.state.turn, &rook_origin);
-                self.zobrist ^=
-                    hasher.hash_piece_at_square(&Pieces::ROOK, &self.state.turn, &rook_destination);
+            MoveKind::ShortCastle | MoveKind::LongCastle => {
+                // pieces were moved by make_castling_move
                 self.state.castling.remove_color_castling(self.state.turn);
                 self.state.reset_half_move();
             }
-            _ => panic!("Boom, invalid special move"),
+            MoveKind::Quiet | MoveKind::Capture => (),
         }
 
         if self.state.castling.can_someone_castle() {
-            if self.state.castling.white_long()
-                && (self.pieces[Color::WHITE][Pieces::ROOK] & WHITE_LONG_ROOK_STARTING_MASK)
-                    .is_empty()
-            {
-                self.state.castling.remove_white_long();
-                self.zobrist ^= hasher.hash_castling_white_long();
-            }
-            if self.state.castling.white_short()
-                && (self.pieces[Color::WHITE][Pieces::ROOK] & WHITE_SHORT_ROOK_STARTING_MASK)
-                    .is_empty()
-            {
-                self.state.castling.remove_white_short();
-                self.zobrist ^= hasher.hash_castling_white_short();
-            }
-            if self.state.castling.black_long()
-                && (self.pieces[Color::BLACK][Pieces::ROOK] & BLACK_LONG_ROOK_STARTING_MASK)
-                    .is_empty()
-            {
-                self.state.castling.remove_black_long();
-                self.zobrist ^= hasher.hash_castling_black_long();
-            }
-            if self.state.castling.black_short()
-                && (self.pieces[Color::BLACK][Pieces::ROOK] & BLACK_SHORT_ROOK_STARTING_MASK)
-                    .is_empty()
-            {
-                self.state.castling.remove_black_short();
-                self.zobrist ^= hasher.hash_castling_black_short();
+            for color in [Color::WHITE, Color::BLACK] {
+                for side in [SHORT, LONG] {
+                    if self.state.castling.has_right(color, side)
+                        && !self.pieces[color][Pieces::ROOK]
+                            .read_square(&self.state.castling.rook_square(color, side))
+                    {
+                        self.state.castling.remove_right(color, side);
+                    }
+                }
             }
             if self.state.castling.can_color_castle(self.state.turn)
                 && moving_piece_type == Pieces::KING
             {
                 self.state.castling.remove_color_castling(self.state.turn);
-                self.zobrist ^= hasher.hash_castling_color(self.state.turn);
             }
         }
+        if self.state.castling != prev_castling {
+            self.zobrist ^= hasher.hash_castling_rights(&prev_castling)
+                ^ hasher.hash_castling_rights(&self.state.castling);
+        }
 
         self.zobrist ^= hasher.turn_hash();
         self.sync_all_pieces();
@@ -283,6 +537,9 @@ impl Board {
         }
         self.state.change_turn();
         self.position_history.push(self.zobrist);
+        self.push_nnue(the_move, moving_piece_type, capture);
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.self_check(hasher, "make", the_move);
 
         UnmakeMoveHelper {
             origin,
@@ -292,59 +549,111 @@ impl Board {
             capture,
             prev_en_passant,
             prev_hash,
+            prev_pawn_key,
+            prev_material_key,
+            prev_non_pawn_keys,
             prev_halfmove,
             prev_castling,
-            special_move: the_move.special_move() as u8,
+            prev_pesto,
+            the_move: *the_move,
         }
     }
 
-    pub fn unmake_move(&mut self, helper: UnmakeMoveHelper) {
-        self.state.change_turn();
-        self.colors[self.state.turn] ^= helper.move_bb;
-        self.pieces[self.state.turn][helper.piece] ^= helper.move_bb;
+    /// Passes the turn, `None` when the side to move is in check. `check_repeat_draw`
+    /// never counts positions across the null move, the half-move clock is kept
+    pub fn make_null_move(
+        &mut self,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Option<NullMoveHelper> {
+        if self.is_check(move_gen_masks) {
+            return None;
+        }
 
-        if let Some(captured_piece) = helper.capture {
-            self.colors[self.state.opponent].set_one(&helper.destination);
-            self.pieces[self.state.opponent][captured_piece].set_one(&helper.destination);
+        let helper = NullMoveHelper {
+            prev_en_passant: self.state.en_passant,
+            prev_hash: self.zobrist,
+            prev_null_move_start: self.null_move_start,
+        };
+        if self.state.en_passant.is_some() {
+            self.zobrist ^= hasher.hash_en_passant(self, self.state.turn);
+            self.state.en_passant = None;
         }
+        self.zobrist ^= hasher.turn_hash();
+        if self.state.turn == Color::BLACK {
+            self.state.increment_full_move();
+        }
+        self.state.change_turn();
+        self.null_move_start = self.position_history.len();
+        self.position_history.push(self.zobrist);
 
-        if let Some(en_passant) = helper.prev_en_passant {
-            if helper.destination == en_passant && helper.piece == Pieces::PAWN {
-                let capture_square =
-                    Square::new(helper.origin.get_rank() * 8 + helper.destination.get_file());
-                self.colors[self.state.opponent].set_one(&capture_square);
-                self.pieces[self.state.opponent][Pieces::PAWN].set_one(&capture_square);
-            }
+        Some(helper)
+    }
+
+    pub fn unmake_null_move(&mut self, helper: NullMoveHelper) {
+        self.state.change_turn();
+        if self.state.turn == Color::BLACK {
+            self.state.restore_full_move();
         }
+        self.state.en_passant = helper.prev_en_passant;
+        self.null_move_start = helper.prev_null_move_start;
+        self.zobrist = helper.prev_hash;
+        self.position_history.pop();
+    }
 
-        match helper.special_move {
-            // promotion
-            1 => {
-                for piece_bb in self.pieces[self.state.turn].iter_mut() {
-                    piece_bb.set_zero(&helper.destination)
+    /// `make_move` keeping the unmake information on the board for `undo`
+    pub fn play(&mut self, the_move: &Move, hasher: &ZobristHasher) {
+        let helper = self.make_move(the_move, hasher);
+        self.state_stack.push(helper);
+    }
+
+    /// Takes back the last move made with `play`, moves made with `make_move` must
+    /// be unmade first
+    pub fn undo(&mut self, hasher: &ZobristHasher) -> Option<Move> {
+        let helper = self.state_stack.pop()?;
+        let the_move = helper.the_move;
+        self.unmake_move(helper, hasher);
+        Some(the_move)
+    }
+
+    /// Copy-make, the position after `the_move` with `self` left untouched
+    pub fn after_move(&self, the_move: &Move, hasher: &ZobristHasher) -> Board {
+        let mut board = self.clone();
+        board.make_move(the_move, hasher);
+        board
+    }
+
+    #[cfg_attr(
+        not(any(debug_assertions, feature = "paranoid")),
+        allow(unused_variables)
+    )]
+    pub fn unmake_move(&mut self, helper: UnmakeMoveHelper, hasher: &ZobristHasher) {
+        let the_move = helper.the_move;
+        self.state.change_turn();
+        if the_move.is_castling() {
+            self.unmake_castling_move(&the_move);
+        } else {
+            // promotion, the pawn is put back on the destination before moving it back
+            if the_move.is_promotion() {
+                if let Some(promoted) = self.mailbox[helper.destination.as_usize()] {
+                    self.clear_piece(&helper.destination, promoted.piece(), self.state.turn);
+                    self.put_piece(&helper.destination, Pieces::PAWN, self.state.turn);
                 }
             }
-            // castling
-            3 => {
-                let rank = helper.destination.get_rank();
-                let (origin_file, destination_file) = if helper.destination.get_file() == 2 {
-                    // long
-                    (0, 3)
-                } else {
-                    // short
-                    (7, 5)
-                };
-                let rook_origin = Square::new(rank * 8 + origin_file);
-                let rook_destination = Square::new(rank * 8 + destination_file);
-
-                self.move_piece(
-                    &rook_destination,
-                    &rook_origin,
-                    Pieces::ROOK,
-                    self.state.turn,
-                );
-            }
-            _ => (),
+            self.colors[self.state.turn] ^= helper.move_bb;
+            self.pieces[self.state.turn][helper.piece] ^= helper.move_bb;
+            self.mailbox[helper.origin.as_usize()] =
+                self.mailbox[helper.destination.as_usize()].take();
+        }
+
+        if let Some(captured_piece) = helper.capture {
+            self.put_piece(&helper.destination, captured_piece, self.state.opponent);
+        }
+
+        if the_move.is_en_passant() {
+            let capture_square =
+                Square::new(helper.origin.get_rank() * 8 + helper.destination.get_file());
+            self.put_piece(&capture_square, Pieces::PAWN, self.state.opponent);
         }
 
         if self.state.turn == Color::BLACK {
@@ -354,9 +663,128 @@ impl Board {
         self.state.castling = helper.prev_castling;
         self.state.half_moves = helper.prev_halfmove;
         self.zobrist = helper.prev_hash;
+        self.pawn_key = helper.prev_pawn_key;
+        self.material_key = helper.prev_material_key;
+        self.non_pawn_keys = helper.prev_non_pawn_keys;
         self.state.castling = helper.prev_castling;
+        self.pesto = helper.prev_pesto;
+        if let Some(nnue) = &mut self.nnue {
+            nnue.pop();
+        }
         self.position_history.pop();
         self.sync_all_pieces();
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.self_check(hasher, "unmake", &the_move);
+    }
+
+    /// Validates after every make / unmake move in debug builds and with the paranoid feature
+    #[cfg(any(debug_assertions, feature = "paranoid"))]
+    fn self_check(&self, hasher: &ZobristHasher, action: &str, the_move: &Move) {
+        if let Err(e) = self.validate(hasher) {
+            panic!("{} {}: {}\n{}", action, the_move, e, self.get_fen());
+        }
+    }
+
+    /// Toggles a piece on a square in the zobrist hash and in its pawn or non-pawn key
+    fn hash_piece(&mut self, hasher: &ZobristHasher, piece: usize, color: usize, square: &Square) {
+        let key = hasher.hash_piece_at_square(&piece, &color, square);
+        self.zobrist ^= key;
+        if piece == Pieces::PAWN {
+            self.pawn_key ^= key;
+        } else {
+            self.non_pawn_keys[color] ^= key;
+        }
+    }
+
+    /// Call after the bitboards gained (`added`) or lost a piece
+    fn update_material_key(
+        &mut self,
+        hasher: &ZobristHasher,
+        piece: usize,
+        color: usize,
+        added: bool,
+    ) {
+        let count = self.pieces[color][piece].0.count_ones() as u8;
+        let index = if added { count - 1 } else { count };
+        self.material_key ^= hasher.hash_material_count(piece, color, index);
+    }
+
+    /// Updates the accumulators with the pieces `the_move` moved, after the turn changed
+    fn push_nnue(&mut self, the_move: &Move, piece: usize, capture: Option<usize>) {
+        let Some(nnue) = &mut self.nnue else {
+            return;
+        };
+        let (us, them) = (self.state.opponent, self.state.turn);
+        let origin = the_move.get_origin();
+        let destination = the_move.get_destination();
+
+        match the_move.kind() {
+            MoveKind::ShortCastle | MoveKind::LongCastle => {
+                let (king_destination, rook_destination) = the_move.castling_destinations();
+                nnue.push(
+                    &[(us, Pieces::KING, origin), (us, Pieces::ROOK, destination)],
+                    &[
+                        (us, Pieces::KING, king_destination),
+                        (us, Pieces::ROOK, rook_destination),
+                    ],
+                );
+            }
+            MoveKind::EnPassant => {
+                let capture_square = Square::new(origin.get_rank() * 8 + destination.get_file());
+                nnue.push(
+                    &[
+                        (us, Pieces::PAWN, origin),
+                        (them, Pieces::PAWN, capture_square),
+                    ],
+                    &[(us, Pieces::PAWN, destination)],
+                );
+            }
+            kind => {
+                let placed = match kind {
+                    MoveKind::Promotion(promotion_piece)
+                    | MoveKind::PromotionCapture(promotion_piece) => promotion_piece,
+                    _ => piece,
+                };
+                let added = [(us, placed, destination)];
+                match capture {
+                    Some(captured) => nnue.push(
+                        &[(us, piece, origin), (them, captured, destination)],
+                        &added,
+                    ),
+                    None => nnue.push(&[(us, piece, origin)], &added),
+                }
+            }
+        }
+    }
+
+    /// King and rook are lifted before being placed, their squares can overlap in Chess960
+    fn make_castling_move(&mut self, the_move: &Move, hasher: &ZobristHasher) {
+        let turn = self.state.turn;
+        let king_origin = the_move.get_origin();
+        let rook_origin = the_move.get_destination();
+        let (king_destination, rook_destination) = the_move.castling_destinations();
+
+        self.clear_piece(&king_origin, Pieces::KING, turn);
+        self.clear_piece(&rook_origin, Pieces::ROOK, turn);
+        for (piece, origin, destination) in [
+            (Pieces::KING, &king_origin, &king_destination),
+            (Pieces::ROOK, &rook_origin, &rook_destination),
+        ] {
+            self.put_piece(destination, piece, turn);
+            self.pesto.move_piece(piece, turn, origin, destination);
+            self.hash_piece(hasher, piece, turn, origin);
+            self.hash_piece(hasher, piece, turn, destination);
+        }
+    }
+
+    fn unmake_castling_move(&mut self, castling_move: &Move) {
+        let turn = self.state.turn;
+        let (king_destination, rook_destination) = castling_move.castling_destinations();
+
+        self.clear_piece(&king_destination, Pieces::KING, turn);
+        self.clear_piece(&rook_destination, Pieces::ROOK, turn);
+        self.put_piece(&castling_move.get_origin(), Pieces::KING, turn);
+        self.put_piece(&castling_move.get_destination(), Pieces::ROOK, turn);
     }
 
     pub fn check_and_make_move(
@@ -364,12 +792,90 @@ impl Board {
         the_move: &Move,
         move_gen_masks: &MoveGenMasks,
         hasher: &ZobristHasher,
-    ) {
-        for possible_move in self.get_legal_moves(move_gen_masks, hasher) {
-            if &possible_move == the_move {
-                self.make_move(&possible_move, hasher);
-                return;
+    ) -> Result<UnmakeMoveHelper, IllegalMoveError> {
+        let origin = the_move.get_origin();
+        let piece = self
+            .get_piece_on_square(&origin)
+            .ok_or(IllegalMoveError::NoPieceOnOrigin(origin))?;
+        if piece.color() != self.state.turn {
+            return Err(IllegalMoveError::WrongColor(origin));
+        }
+        // moves compare without their flags, e7e8 would silently match e7e8q
+        if piece.piece() == Pieces::PAWN
+            && BACK_RANKS_MASK.read_square(&the_move.get_destination())
+            && !the_move.is_promotion()
+        {
+            return Err(IllegalMoveError::MissingPromotion);
+        }
+
+        let legal_moves = self.get_legal_moves(move_gen_masks, hasher);
+        // standard notation castling (e1g1) only matches by its string
+        match legal_moves
+            .iter()
+            .find(|possible_move| *possible_move == the_move)
+            .or_else(|| {
+                legal_moves.iter().find(|possible_move| {
+                    possible_move.to_long_string() == the_move.to_long_string()
+                })
+            }) {
+            Some(legal_move) => Ok(self.make_move(legal_move, hasher)),
+            None => Err(self.illegal_move_reason(the_move, piece.piece(), move_gen_masks)),
+        }
+    }
+
+    /// Called once `the_move` is known to be illegal, with `piece` of the side to move
+    /// on its origin
+    fn illegal_move_reason(
+        &self,
+        the_move: &Move,
+        piece: usize,
+        move_gen_masks: &MoveGenMasks,
+    ) -> IllegalMoveError {
+        let origin = the_move.get_origin();
+        let destination = the_move.get_destination();
+
+        if piece == Pieces::KING {
+            for side in [SHORT, LONG] {
+                let Some(castling_move) = get_castling_candidate(&origin, self, side) else {
+                    continue;
+                };
+                if castling_move == *the_move
+                    || castling_move.to_long_string() == the_move.to_long_string()
+                {
+                    return castling_obstacle(&castling_move, self, move_gen_masks)
+                        .unwrap_or(IllegalMoveError::LeavesKingInCheck);
+                }
+            }
+        }
+
+        if self.colors[self.state.turn].read_square(&destination) {
+            return IllegalMoveError::OwnPieceOnDestination(destination);
+        }
+        if get_all_moves(self, move_gen_masks).contains(the_move) {
+            return IllegalMoveError::LeavesKingInCheck;
+        }
+
+        let is_blocked = match piece {
+            Pieces::PAWN => {
+                let (direction, base_rank) = if self.state.turn == Color::WHITE {
+                    (1, 1)
+                } else {
+                    (-1, 6)
+                };
+                let rank_distance =
+                    (destination.get_rank() as i8 - origin.get_rank() as i8) * direction;
+                destination.get_file() == origin.get_file()
+                    && (rank_distance == 1
+                        || (rank_distance == 2 && origin.get_rank() == base_rank))
             }
+            _ => move_gen_masks
+                .piece_attacks(piece, &origin, BitBoard::zeros())
+                .read_square(&destination),
+        };
+        if is_blocked {
+            IllegalMoveError::BlockedPath
+        } else {
+            IllegalMoveError::UnreachableDestination(origin, destination)
         }
     }
 
@@ -397,19 +903,45 @@ impl Board {
             all_pieces: BitBoard::zeros(),
             state: State::default(),
             zobrist: ZobristHash::zero(),
+            pawn_key: ZobristHash::zero(),
+            material_key: ZobristHash::zero(),
+            non_pawn_keys: [ZobristHash::zero(); 2],
             position_history: Vec::with_capacity(50),
+            null_move_start: 0,
+            state_stack: Vec::new(),
+            pesto: PestoScore::default(),
+            nnue: None,
+            mailbox: [None; 64],
         }
     }
 
+    /// Evaluates with `network` from now on, the accumulators follow make / unmake move
+    pub fn enable_nnue(&mut self, network: Arc<Network>) {
+        self.nnue = Some(NnueState::new(network, &self.pieces));
+    }
+
+    pub fn disable_nnue(&mut self) {
+        self.nnue = None;
+    }
+
     pub fn check_en_passant(&self, square: &Square) -> bool {
         self.state.en_passant.is_some_and(|x| &x == square)
     }
 
-    pub fn from_fen(fen: &str, hasher: &ZobristHasher) -> Result<Self, Box<dyn Error>> {
-        let fen_parts: Vec<&str> = fen.trim().split(" ").collect();
+    pub fn from_fen(fen: &str, hasher: &ZobristHasher) -> Result<Self, FenError> {
+        Self::from_fen_with_options(fen, hasher, &FenOptions::default())
+    }
+
+    pub fn from_fen_with_options(
+        fen: &str,
+        hasher: &ZobristHasher,
+        options: &FenOptions,
+    ) -> Result<Self, FenError> {
+        let fen_parts: Vec<&str> = fen.split_whitespace().collect();
 
-        if fen_parts.len() != 6 {
-            return Err("Incorrect fen string")?;
+        let min_fields = if options.allow_missing_counters { 4 } else { 6 };
+        if !(min_fields..=6).contains(&fen_parts.len()) {
+            return Err(FenError::WrongNumberOfFields(fen_parts.len()));
         }
 
         let board_string = fen_parts[0];
@@ -420,142 +952,342 @@ impl Board {
             .rev()
             .collect();
         if board_string_parts.len() != 8 {
-            return Err("Invorrect fen string")?;
-        }
-
-        let mut colors = [BitBoard::zeros(), BitBoard::zeros()];
-        let mut pieces = [
-            [
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-            ],
-            [
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-                BitBoard::zeros(),
-            ],
-        ];
+            return Err(FenError::BadRankCount(board_string_parts.len()));
+        }
+
+        let mut board = Self::empty();
 
         for (rank, rank_str) in board_string_parts.iter().enumerate() {
             let mut file = 0_usize;
             for fen_char in rank_str.chars() {
-                if (rank > 7) | (file > 7) {
-                    panic!("Invalid fen");
+                if file > 7 {
+                    return Err(FenError::BadRankLength(rank as u8 + 1));
                 }
 
-                let fen_char_digit = fen_char as usize;
+                if let Some(n_empty_spaces) = fen_char.to_digit(10) {
+                    if !(1..=8).contains(&n_empty_spaces) {
+                        return Err(FenError::BadPieceChar(fen_char));
+                    }
+                    file += n_empty_spaces as usize;
+                    continue;
+                }
 
-                if (48..=56).contains(&fen_char_digit) {
-                    let n_empty_spaces = fen_char_digit - '0' as usize;
-                    file += n_empty_spaces
+                let piece_color = if fen_char.is_lowercase() {
+                    Color::BLACK
                 } else {
-                    let piece_color = if fen_char.is_lowercase() {
-                        Color::BLACK
-                    } else {
-                        Color::WHITE
-                    };
-
-                    let piece_kind = match fen_char.to_ascii_lowercase() {
-                        'p' => Pieces::PAWN,
-                        'r' => Pieces::ROOK,
-                        'n' => Pieces::KNIGHT,
-                        'b' => Pieces::BISHOP,
-                        'k' => Pieces::KING,
-                        'q' => Pieces::QUEEN,
-                        _ => panic!("Invalid fen char"),
-                    };
-                    let piece_square = Square::new((rank * 8 + file) as u8);
-                    pieces[piece_color][piece_kind].set_one(&piece_square);
-                    colors[piece_color].set_one(&piece_square);
-                    file += 1;
-                }
+                    Color::WHITE
+                };
+
+                let piece_kind = match fen_char.to_ascii_lowercase() {
+                    'p' => Pieces::PAWN,
+                    'r' => Pieces::ROOK,
+                    'n' => Pieces::KNIGHT,
+                    'b' => Pieces::BISHOP,
+                    'k' => Pieces::KING,
+                    'q' => Pieces::QUEEN,
+                    _ => return Err(FenError::BadPieceChar(fen_char)),
+                };
+                let piece_square = Square::new((rank * 8 + file) as u8);
+                board.put_piece(&piece_square, piece_kind, piece_color);
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::BadRankLength(rank as u8 + 1));
             }
         }
+        board.sync_all_pieces();
 
-        let all_pieces = colors[0] | colors[1];
-        let turn = if fen_parts[1].eq_ignore_ascii_case("w") {
-            Color::WHITE
-        } else {
-            Color::BLACK
+        let turn = match fen_parts[1] {
+            "w" | "W" => Color::WHITE,
+            "b" | "B" => Color::BLACK,
+            side => return Err(FenError::WrongSide(side.to_owned())),
         };
-        let opponent = if turn == 1 { 0 } else { 1 };
 
-        let mut castling = Castling::new();
-        for castling_char in fen_parts[2].chars() {
-            match castling_char {
-                'Q' => castling.set_white_long(),
-                'K' => castling.set_white_short(),
-                'q' => castling.set_black_long(),
-                'k' => castling.set_black_short(),
-                '-' => break,
-                _ => return Err("Invalid castling char")?,
-            }
-        }
+        let castling = Self::parse_fen_castling(&board, fen_parts[2])?;
 
-        let en_passant = if fen_parts[3] == "-" {
-            None
-        } else {
-            match Square::from_str(fen_parts[3]) {
-                Ok(square) => Some(square),
-                Err(_) => return Err("Error parsing en passant square")?,
+        let en_passant = match fen_parts[3] {
+            "-" => None,
+            square_str => {
+                let mut chars = square_str.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some('a'..='h'), Some('1'..='8'), None) => {
+                        Some(Square::from_str(square_str).unwrap())
+                    }
+                    _ => return Err(FenError::InvalidEnPassant(square_str.to_owned())),
+                }
             }
         };
 
-        let half_moves = match fen_parts[4].parse::<u8>() {
-            Ok(x) => x,
-            Err(_) => return Err("Invalid half move string")?,
+        let half_moves = match fen_parts.get(4) {
+            Some(half_moves_str) => half_moves_str
+                .parse::<u8>()
+                .map_err(|_| FenError::InvalidHalfMove(half_moves_str.to_string()))?,
+            None => 0,
         };
-        let full_moves = match fen_parts[5].parse::<u16>() {
-            Ok(x) => x,
-            Err(_) => return Err("Invalid full move string")?,
+        let full_moves = match fen_parts.get(5) {
+            Some(full_moves_str) => full_moves_str
+                .parse::<u16>()
+                .map_err(|_| FenError::InvalidFullMove(full_moves_str.to_string()))?,
+            None => 1,
         };
 
-        let state = State {
+        board.state = State {
             castling,
             en_passant,
             half_moves,
             full_moves,
             turn,
-            opponent,
+            opponent: turn ^ 1,
         };
 
-        let mut board = Self {
-            colors,
-            pieces,
-            all_pieces,
-            state,
-            zobrist: ZobristHash::new(0), // default board hash with polyglot randoms
-            position_history: Vec::with_capacity(50),
-        };
-        board.zobrist = hasher.hash_everyting(&board);
+        if !options.skip_position_checks {
+            board.check_fen_position().map_err(|e| match e {
+                FenError::InvalidCastling(_) => FenError::InvalidCastling(fen_parts[2].to_owned()),
+                e => e,
+            })?;
+        }
+
+        board.pesto = PestoScore::from_board(&board);
+        board.zobrist = hasher.hash_everyting(&board); // default board hash with polyglot randoms
+        board.pawn_key = hasher.hash_pawns(&board);
+        board.material_key = hasher.hash_material(&board);
+        board.non_pawn_keys = [
+            hasher.hash_non_pawns(&board, Color::WHITE),
+            hasher.hash_non_pawns(&board, Color::BLACK),
+        ];
         board.position_history.push(board.zobrist);
 
         Ok(board)
     }
 
-    pub fn get_fen(&self) -> String {
-        let mut fen = String::new();
+    /// KQkq castle with the outermost rook (X-FEN), file letters name the rook (Shredder-FEN).
+    /// Rights without a matching rook are left for `check_fen_position` to reject
+    fn parse_fen_castling(board: &Board, castling_str: &str) -> Result<Castling, FenError> {
+        let mut castling = Castling::new();
+        if castling_str == "-" {
+            return Ok(castling);
+        }
 
-        for i in (0..8).rev() {
-            let mut empty_squares = 0;
-            for j in 0..8 {
-                let square = Square::new(i * 8 + j);
-                match self.get_piece_on_square(&square) {
-                    Some(piece) => {
-                        if empty_squares > 0 {
-                            fen.push_str(&empty_squares.to_string());
-                        }
-                        fen.push_str(&piece.to_string());
-                        empty_squares = 0;
-                    }
-                    None => empty_squares += 1,
+        for castling_char in castling_str.chars() {
+            let color = if castling_char.is_ascii_uppercase() {
+                Color::WHITE
+            } else {
+                Color::BLACK
+            };
+            let back_rank = if color == Color::WHITE { 0 } else { 7 };
+            let king_file = board.pieces[color][Pieces::KING]
+                .get_ones()
+                .into_iter()
+                .find(|square| square.get_rank() == back_rank)
+                .map_or(4, |square| square.get_file());
+            let has_rook = |file: u8| {
+                board.pieces[color][Pieces::ROOK].read_square(&Square::new(back_rank * 8 + file))
+            };
+
+            let (side, rook_file) = match castling_char.to_ascii_lowercase() {
+                'k' => (
+                    SHORT,
+                    (king_file + 1..8)
+                        .rev()
+                        .find(|file| has_rook(*file))
+                        .unwrap_or(7),
+                ),
+                'q' => (
+                    LONG,
+                    (0..king_file).find(|file| has_rook(*file)).unwrap_or(0),
+                ),
+                file_char @ 'a'..='h' => {
+                    let file = file_char as u8 - b'a';
+                    let side = if file > king_file { SHORT } else { LONG };
+                    (side, file)
+                }
+                _ => return Err(FenError::InvalidCastling(castling_str.to_owned())),
+            };
+            if castling.has_right(color, side) {
+                return Err(FenError::InvalidCastling(castling_str.to_owned()));
+            }
+            castling.set_right(color, side, rook_file);
+        }
+
+        Ok(castling)
+    }
+
+    /// Rejects positions that cannot arise in a legal game
+    fn check_fen_position(&self) -> Result<(), FenError> {
+        for color in [Color::WHITE, Color::BLACK] {
+            match self.pieces[color][Pieces::KING].0.count_ones() {
+                0 => return Err(FenError::MissingKing(color)),
+                1 => (),
+                _ => return Err(FenError::TooManyKings(color)),
+            }
+            if self.pieces[color][Pieces::PAWN].0.count_ones() > 8 {
+                return Err(FenError::TooManyPawns(color));
+            }
+        }
+
+        if !((self.pieces[Color::WHITE][Pieces::PAWN] | self.pieces[Color::BLACK][Pieces::PAWN])
+            & BACK_RANKS_MASK)
+            .is_empty()
+        {
+            return Err(FenError::PawnsOnBackRank);
+        }
+
+        let castling = self.state.castling;
+        for color in [Color::WHITE, Color::BLACK] {
+            let king_square = self.pieces[color][Pieces::KING].get_one();
+            for side in [SHORT, LONG] {
+                if !castling.has_right(color, side) {
+                    continue;
+                }
+                let rook_square = castling.rook_square(color, side);
+                let rook_on_side = if side == SHORT {
+                    rook_square.get_file() > king_square.get_file()
+                } else {
+                    rook_square.get_file() < king_square.get_file()
+                };
+                if king_square.get_rank() != rook_square.get_rank()
+                    || !rook_on_side
+                    || !self.pieces[color][Pieces::ROOK].read_square(&rook_square)
+                {
+                    return Err(FenError::InvalidCastling(self.get_fen_castling(false)));
+                }
+            }
+        }
+
+        if let Some(en_passant) = self.state.en_passant {
+            let expected_rank = if self.state.turn == Color::WHITE {
+                5
+            } else {
+                2
+            };
+            if en_passant.get_rank() != expected_rank {
+                return Err(FenError::EnPassantWrongRank(en_passant));
+            }
+            let (pawn_square, origin_square) = if self.state.turn == Color::WHITE {
+                (en_passant - 8_u8, en_passant + 8_u8)
+            } else {
+                (en_passant + 8_u8, en_passant - 8_u8)
+            };
+            if !self.pieces[self.state.opponent][Pieces::PAWN].read_square(&pawn_square)
+                || self.all_pieces.read_square(&en_passant)
+                || self.all_pieces.read_square(&origin_square)
+            {
+                return Err(FenError::InvalidEnPassant(en_passant.to_string()));
+            }
+        }
+
+        let opponent_king = self.pieces[self.state.opponent][Pieces::KING].get_one();
+        if self.is_attacked_by(&opponent_king, self.state.turn) {
+            return Err(FenError::SideNotToMoveInCheck);
+        }
+
+        Ok(())
+    }
+
+    /// Ray walking attack detection that does not need the precomputed move masks
+    fn is_attacked_by(&self, square: &Square, attacker: usize) -> bool {
+        let pieces = &self.pieces[attacker];
+        let rank = square.get_rank() as i8;
+        let file = square.get_file() as i8;
+        let square_at = |rank: i8, file: i8| {
+            ((0..8).contains(&rank) && (0..8).contains(&file))
+                .then(|| Square::new((rank * 8 + file) as u8))
+        };
+
+        let pawn_rank = if attacker == Color::WHITE { -1 } else { 1 };
+        let steppers = [
+            (Pieces::PAWN, vec![(pawn_rank, -1), (pawn_rank, 1)]),
+            (
+                Pieces::KNIGHT,
+                vec![
+                    (1, 2),
+                    (2, 1),
+                    (2, -1),
+                    (1, -2),
+                    (-1, -2),
+                    (-2, -1),
+                    (-2, 1),
+                    (-1, 2),
+                ],
+            ),
+            (
+                Pieces::KING,
+                vec![
+                    (1, 0),
+                    (1, 1),
+                    (0, 1),
+                    (-1, 1),
+                    (-1, 0),
+                    (-1, -1),
+                    (0, -1),
+                    (1, -1),
+                ],
+            ),
+        ];
+        for (piece, offsets) in steppers {
+            if offsets.into_iter().any(|(rank_offset, file_offset)| {
+                square_at(rank + rank_offset, file + file_offset)
+                    .is_some_and(|target| pieces[piece].read_square(&target))
+            }) {
+                return true;
+            }
+        }
+
+        let sliders = [
+            (
+                pieces[Pieces::ROOK] | pieces[Pieces::QUEEN],
+                [(1, 0), (0, 1), (-1, 0), (0, -1)],
+            ),
+            (
+                pieces[Pieces::BISHOP] | pieces[Pieces::QUEEN],
+                [(1, 1), (1, -1), (-1, 1), (-1, -1)],
+            ),
+        ];
+        for (slider_bb, directions) in sliders {
+            for (rank_step, file_step) in directions {
+                let (mut new_rank, mut new_file) = (rank + rank_step, file + file_step);
+                while let Some(target) = square_at(new_rank, new_file) {
+                    if slider_bb.read_square(&target) {
+                        return true;
+                    }
+                    if self.all_pieces.read_square(&target) {
+                        break;
+                    }
+                    new_rank += rank_step;
+                    new_file += file_step;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// X-FEN, identical to plain FEN for standard chess positions
+    pub fn get_fen(&self) -> String {
+        self.fen_with_castling(self.get_fen_castling(false))
+    }
+
+    /// Castling rights written as rook files, e.g. HAha
+    pub fn get_shredder_fen(&self) -> String {
+        self.fen_with_castling(self.get_fen_castling(true))
+    }
+
+    fn fen_with_castling(&self, castling: String) -> String {
+        let mut fen = String::new();
+
+        for i in (0..8).rev() {
+            let mut empty_squares = 0;
+            for j in 0..8 {
+                let square = Square::new(i * 8 + j);
+                match self.get_piece_on_square(&square) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            fen.push_str(&empty_squares.to_string());
+                        }
+                        fen.push_str(&piece.to_string());
+                        empty_squares = 0;
+                    }
+                    None => empty_squares += 1,
                 };
             }
 
@@ -577,21 +1309,7 @@ impl Board {
 
         fen.push(' ');
 
-        if self.state.castling.white_short() {
-            fen.push('K');
-        }
-        if self.state.castling.white_long() {
-            fen.push('Q');
-        }
-        if self.state.castling.black_short() {
-            fen.push('k');
-        }
-        if self.state.castling.black_long() {
-            fen.push('q');
-        }
-        if !self.state.castling.can_someone_castle() {
-            fen.push('-');
-        }
+        fen.push_str(&castling);
 
         fen.push(' ');
 
@@ -611,32 +1329,154 @@ impl Board {
         fen
     }
 
+    /// KQkq unless another rook stands between the castling rook and the edge of the board
+    fn get_fen_castling(&self, shredder: bool) -> String {
+        let mut castling = String::new();
+        for color in [Color::WHITE, Color::BLACK] {
+            for side in [SHORT, LONG] {
+                if !self.state.castling.has_right(color, side) {
+                    continue;
+                }
+                let rook_square = self.state.castling.rook_square(color, side);
+                let outer_files = if side == SHORT {
+                    rook_square.get_file() + 1..8
+                } else {
+                    0..rook_square.get_file()
+                };
+                let is_outermost = outer_files.into_iter().all(|file| {
+                    !self.pieces[color][Pieces::ROOK]
+                        .read_square(&Square::new(rook_square.get_rank() * 8 + file))
+                });
+
+                let castling_char = if shredder || !is_outermost {
+                    (b'a' + rook_square.get_file()) as char
+                } else if side == SHORT {
+                    'k'
+                } else {
+                    'q'
+                };
+                if color == Color::WHITE {
+                    castling.push(castling_char.to_ascii_uppercase());
+                } else {
+                    castling.push(castling_char);
+                }
+            }
+        }
+        if !self.state.castling.can_someone_castle() {
+            castling.push('-');
+        }
+        castling
+    }
+
     pub fn get_piece_on_square(&self, square: &Square) -> Option<Piece> {
-        let mut piece_color: usize = 3;
-        for (i, color) in self.colors.iter().enumerate() {
-            if color.read_square(square) {
-                piece_color = i;
-                break;
+        self.mailbox[square.as_usize()]
+    }
+
+    /// Checks every invariant make / unmake move has to keep
+    pub fn validate(&self, hasher: &ZobristHasher) -> Result<(), BoardError> {
+        if self.all_pieces != (self.colors[0] | self.colors[1]) {
+            return Err(BoardError::AllPiecesMismatch);
+        }
+        if !(self.colors[0] & self.colors[1]).is_empty() {
+            return Err(BoardError::OverlappingColors);
+        }
+        for color in [Color::WHITE, Color::BLACK] {
+            let mut union = BitBoard::zeros();
+            for piece_board in self.pieces[color] {
+                if !(union & piece_board).is_empty() {
+                    return Err(BoardError::OverlappingPieces(color));
+                }
+                union |= piece_board;
+            }
+            if union != self.colors[color] {
+                return Err(BoardError::ColorMismatch(color));
             }
+            if self.pieces[color][Pieces::KING].0.count_ones() != 1 {
+                return Err(BoardError::KingCount(color));
+            }
+        }
+        if !self.mailbox_is_consistent() {
+            return Err(BoardError::MailboxMismatch);
+        }
+        if !((self.pieces[Color::WHITE][Pieces::PAWN] | self.pieces[Color::BLACK][Pieces::PAWN])
+            & BACK_RANKS_MASK)
+            .is_empty()
+        {
+            return Err(BoardError::PawnsOnBackRank);
+        }
+        if self.state.opponent != self.state.turn ^ 1 {
+            return Err(BoardError::TurnMismatch);
         }
 
-        if piece_color == 3 {
-            return None;
+        for color in [Color::WHITE, Color::BLACK] {
+            let king_square = self.pieces[color][Pieces::KING].get_one();
+            let back_rank = if color == Color::WHITE { 0 } else { 7 };
+            for side in [SHORT, LONG] {
+                if self.state.castling.has_right(color, side)
+                    && (king_square.get_rank() != back_rank
+                        || !self.pieces[color][Pieces::ROOK]
+                            .read_square(&self.state.castling.rook_square(color, side)))
+                {
+                    return Err(BoardError::InvalidCastling(color, side));
+                }
+            }
         }
 
-        let mut piece_type = 9_usize;
+        if let Some(en_passant) = self.state.en_passant {
+            let (expected_rank, pawn_square, origin_square) = if self.state.turn == Color::WHITE {
+                (5, en_passant - 8_u8, en_passant + 8_u8)
+            } else {
+                (2, en_passant + 8_u8, en_passant - 8_u8)
+            };
+            if en_passant.get_rank() != expected_rank
+                || !self.pieces[self.state.opponent][Pieces::PAWN].read_square(&pawn_square)
+                || self.all_pieces.read_square(&en_passant)
+                || self.all_pieces.read_square(&origin_square)
+            {
+                return Err(BoardError::InvalidEnPassant(en_passant));
+            }
+        }
 
-        for (i, piece_bb) in self.pieces[piece_color].iter().enumerate() {
-            if piece_bb.read_square(square) {
-                piece_type = i;
-                break;
+        let expected = hasher.hash_everyting(self);
+        if self.zobrist != expected {
+            return Err(BoardError::HashMismatch {
+                expected,
+                found: self.zobrist,
+            });
+        }
+        if self.pawn_key != hasher.hash_pawns(self) {
+            return Err(BoardError::PawnKeyMismatch);
+        }
+        if self.material_key != hasher.hash_material(self) {
+            return Err(BoardError::MaterialKeyMismatch);
+        }
+        for color in [Color::WHITE, Color::BLACK] {
+            if self.non_pawn_keys[color] != hasher.hash_non_pawns(self, color) {
+                return Err(BoardError::NonPawnKeyMismatch(color));
             }
         }
-        if piece_type == 9 {
-            panic!("Found piece color but not piece type");
+        if self.pesto != PestoScore::from_board(self) {
+            return Err(BoardError::PestoMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Invariant checker, the mailbox, colour and piece bitboards must describe the same position
+    pub fn mailbox_is_consistent(&self) -> bool {
+        let mut colors = [BitBoard::zeros(); 2];
+        let mut pieces = [[BitBoard::zeros(); 6]; 2];
+        for index in 0..64 {
+            if let Some(piece) = self.mailbox[index] {
+                let square = Square::new(index as u8);
+                colors[piece.color()].set_one(&square);
+                pieces[piece.color()][piece.piece()].set_one(&square);
+            }
         }
 
-        Some(Piece::new(piece_type, piece_color))
+        colors == self.colors
+            && pieces == self.pieces
+            && self.all_pieces == (self.colors[0] | self.colors[1])
     }
 }
 
@@ -664,6 +1504,8 @@ impl fmt::Display for Board {
 
 #[cfg(test)]
 mod test_board {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
     use super::*;
 
     // #[test]
@@ -707,4 +1549,559 @@ mod test_board {
             assert_eq!(Board::from_fen(fen, &hasher).unwrap().get_fen(), fen);
         }
     }
+
+    #[test]
+    fn test_fen_errors() {
+        let hasher = ZobristHasher::load();
+        let cases = [
+            ("8/8/8/8/8/8/8/8 w - -", FenError::WrongNumberOfFields(4)),
+            ("8/8/8/8/8/8/8 w - - 0 1", FenError::BadRankCount(7)),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::BadRankLength(1)),
+            ("4k3/8/8/8/8/8/8/4K2 w - - 0 1", FenError::BadRankLength(1)),
+            (
+                "4k3/8/8/8/8/8/8/4K2X w - - 0 1",
+                FenError::BadPieceChar('X'),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3K03 w - - 0 1",
+                FenError::BadPieceChar('0'),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::WrongSide("x".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KA - 0 1",
+                FenError::InvalidCastling("KA".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KK - 0 1",
+                FenError::InvalidCastling("KK".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                FenError::InvalidCastling("K".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e9 0 1",
+                FenError::InvalidEnPassant("e9".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1",
+                FenError::EnPassantWrongRank(Square::from_str("e3").unwrap()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+                FenError::InvalidEnPassant("e6".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::InvalidHalfMove("x".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 -1",
+                FenError::InvalidFullMove("-1".to_owned()),
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::MissingKing(Color::BLACK),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4KK2 w - - 0 1",
+                FenError::TooManyKings(Color::WHITE),
+            ),
+            (
+                "4k3/8/8/8/8/p7/pppppppp/4K3 b - - 0 1",
+                FenError::TooManyPawns(Color::BLACK),
+            ),
+            ("P3k3/8/8/8/8/8/8/4K3 w - - 0 1", FenError::PawnsOnBackRank),
+            (
+                "4k2R/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::SideNotToMoveInCheck,
+            ),
+            (
+                "4k3/8/3N4/8/8/8/8/4K3 w - - 0 1",
+                FenError::SideNotToMoveInCheck,
+            ),
+            (
+                "4k3/8/8/1B6/8/8/8/4K3 w - - 0 1",
+                FenError::SideNotToMoveInCheck,
+            ),
+        ];
+
+        for (fen, expected_error) in cases {
+            assert_eq!(
+                Board::from_fen(fen, &hasher).err(),
+                Some(expected_error),
+                "{}",
+                fen
+            );
+        }
+
+        // fine when the checked side is to move or the line is blocked
+        assert!(Board::from_fen("4k2R/8/8/8/8/8/8/4K3 b - - 0 1", &hasher).is_ok());
+        assert!(Board::from_fen("4k3/8/2P5/1B6/8/8/8/4K3 w - - 0 1", &hasher).is_ok());
+    }
+
+    #[test]
+    fn test_fen_options() {
+        let hasher = ZobristHasher::load();
+        let lenient = FenOptions::lenient();
+
+        let board =
+            Board::from_fen_with_options("4k3/8/8/8/8/8/8/4K3 b - -", &hasher, &lenient).unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        let board =
+            Board::from_fen_with_options("4k3/8/8/8/8/8/8/4K3 b - - 7", &hasher, &lenient).unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 7 1");
+        assert_eq!(
+            Board::from_fen_with_options("4k3/8/8/8/8/8/8/4K3 b -", &hasher, &lenient).err(),
+            Some(FenError::WrongNumberOfFields(3))
+        );
+
+        let unchecked = FenOptions {
+            skip_position_checks: true,
+            ..Default::default()
+        };
+        assert!(
+            Board::from_fen_with_options("8/8/8/8/8/8/8/8 w - - 0 1", &hasher, &unchecked).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_fen_round_trip_random_games() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let start_fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            "1r2k1rr/pppppppp/8/8/8/8/PPPPPPPP/1R2K1RR w Gg - 0 1",
+        ];
+
+        for start_fen in start_fens {
+            for _ in 0..10 {
+                let mut board = Board::from_fen(start_fen, &hasher).unwrap();
+                for _ in 0..120 {
+                    let legal_moves = board.get_legal_moves(&move_gen_masks, &hasher);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    board.make_move(&the_move, &hasher);
+
+                    let fen = board.get_fen();
+                    let parsed = Board::from_fen(&fen, &hasher).unwrap();
+                    assert_eq!(parsed.get_fen(), fen);
+                    assert_eq!(parsed.zobrist, board.zobrist, "{}", fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mailbox_random_games() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let mut rng = StdRng::seed_from_u64(0xb0c5);
+        let start_fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        ];
+
+        for start_fen in start_fens {
+            for _ in 0..10 {
+                let mut board = Board::from_fen(start_fen, &hasher).unwrap();
+                assert!(board.mailbox_is_consistent());
+                for _ in 0..120 {
+                    let legal_moves = board.get_legal_moves(&move_gen_masks, &hasher);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    for legal_move in &legal_moves {
+                        let helper = board.make_move(legal_move, &hasher);
+                        assert!(board.mailbox_is_consistent(), "{}", legal_move);
+                        board.unmake_move(helper, &hasher);
+                        assert!(board.mailbox_is_consistent(), "{}", legal_move);
+                    }
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    board.make_move(&the_move, &hasher);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_random_make_unmake() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let mut rng = StdRng::seed_from_u64(0x7a1d);
+        let start_fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        ];
+
+        for start_fen in start_fens {
+            for _ in 0..5 {
+                let mut board = Board::from_fen(start_fen, &hasher).unwrap();
+                let mut helpers = Vec::new();
+                for _ in 0..80 {
+                    let legal_moves = board.get_legal_moves(&move_gen_masks, &hasher);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    helpers.push(board.make_move(&the_move, &hasher));
+                    assert_eq!(board.validate(&hasher), Ok(()), "{}", board.get_fen());
+                }
+                while let Some(helper) = helpers.pop() {
+                    board.unmake_move(helper, &hasher);
+                    assert_eq!(board.validate(&hasher), Ok(()), "{}", board.get_fen());
+                }
+                assert_eq!(
+                    board.get_shredder_fen(),
+                    Board::from_fen(start_fen, &hasher)
+                        .unwrap()
+                        .get_shredder_fen()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_detects_corruption() {
+        let hasher = ZobristHasher::load();
+        let board = Board::new(&hasher);
+        assert_eq!(board.validate(&hasher), Ok(()));
+
+        let mut corrupted = board.clone();
+        corrupted.all_pieces.set_zero(&Square::new(0));
+        assert_eq!(
+            corrupted.validate(&hasher),
+            Err(BoardError::AllPiecesMismatch)
+        );
+
+        let mut corrupted = board.clone();
+        corrupted.pieces[Color::WHITE][Pieces::QUEEN].set_one(&Square::new(0));
+        assert_eq!(
+            corrupted.validate(&hasher),
+            Err(BoardError::OverlappingPieces(Color::WHITE))
+        );
+
+        let mut corrupted = board.clone();
+        corrupted.mailbox[20] = Some(Piece::new(Pieces::PAWN, Color::WHITE));
+        assert_eq!(
+            corrupted.validate(&hasher),
+            Err(BoardError::MailboxMismatch)
+        );
+
+        let mut corrupted = board.clone();
+        corrupted.state.castling.remove_right(Color::BLACK, SHORT);
+        assert!(matches!(
+            corrupted.validate(&hasher),
+            Err(BoardError::HashMismatch { .. })
+        ));
+
+        let mut corrupted = board.clone();
+        corrupted.clear_piece(&Square::new(7), Pieces::ROOK, Color::WHITE);
+        corrupted.sync_all_pieces();
+        assert_eq!(
+            corrupted.validate(&hasher),
+            Err(BoardError::InvalidCastling(Color::WHITE, SHORT))
+        );
+
+        let mut corrupted = board.clone();
+        corrupted.state.en_passant = Some(Square::from_str("e6").unwrap());
+        assert_eq!(
+            corrupted.validate(&hasher),
+            Err(BoardError::InvalidEnPassant(
+                Square::from_str("e6").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_chess960_fen() {
+        let hasher = ZobristHasher::load();
+
+        let board = Board::from_fen(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &hasher,
+        )
+        .unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w KQkq - 1 9"
+        );
+        assert_eq!(
+            board.get_shredder_fen(),
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9"
+        );
+        assert_eq!(board.state.castling.rook_file(Color::WHITE, LONG), 4);
+
+        // the g rook is not the outermost one, so X-FEN has to name its file
+        let board = Board::from_fen("1r2k1rr/8/8/8/8/8/8/1R2K1RR w GBgb - 0 1", &hasher).unwrap();
+        assert_eq!(board.get_fen(), "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GQgq - 0 1");
+
+        assert_eq!(
+            Board::from_fen("1r2k1rr/8/8/8/8/8/8/1R2K1RR w F - 0 1", &hasher).err(),
+            Some(FenError::InvalidCastling("F".to_owned()))
+        );
+        assert_eq!(
+            Board::from_fen("1r2k1rr/8/8/8/8/8/8/1R2K1RR w GH - 0 1", &hasher).err(),
+            Some(FenError::InvalidCastling("GH".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_chess960_castling_make_unmake() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        // king f1 and rook g1 swap places when castling short
+        let fen = "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKR1 w GE - 1 9";
+        let mut board = Board::from_fen(fen, &hasher).unwrap();
+
+        let castling_move = board
+            .get_legal_moves(&move_gen_masks, &hasher)
+            .into_iter()
+            .find(|legal_move| legal_move.is_castling())
+            .unwrap();
+        assert_eq!(castling_move.to_uci_string(true), "f1g1");
+        assert_eq!(castling_move.to_uci_string(false), "f1g1");
+
+        let helper = board.make_move(&castling_move, &hasher);
+        assert_eq!(
+            board.get_fen(),
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRRK1 b - - 0 9"
+        );
+        assert_eq!(board.zobrist, hasher.hash_everyting(&board));
+
+        board.unmake_move(helper, &hasher);
+        assert_eq!(board.get_shredder_fen(), fen);
+        assert_eq!(board.zobrist, hasher.hash_everyting(&board));
+    }
+
+    #[test]
+    fn test_check_and_make_move_errors() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let square = |name: &str| Square::from_str(name).unwrap();
+        let try_move = |fen: &str, move_str: &str| {
+            let mut board = Board::from_fen(fen, &hasher).unwrap();
+            board
+                .check_and_make_move(&Move::from_long_str(move_str), &move_gen_masks, &hasher)
+                .map(|_| board.get_fen())
+        };
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        assert_eq!(
+            try_move(start, "e2e4"),
+            Ok("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_owned())
+        );
+        assert_eq!(
+            try_move(start, "e3e4"),
+            Err(IllegalMoveError::NoPieceOnOrigin(square("e3")))
+        );
+        assert_eq!(
+            try_move(start, "e7e5"),
+            Err(IllegalMoveError::WrongColor(square("e7")))
+        );
+        assert_eq!(
+            try_move(start, "e1e2"),
+            Err(IllegalMoveError::OwnPieceOnDestination(square("e2")))
+        );
+        assert_eq!(try_move(start, "f1c4"), Err(IllegalMoveError::BlockedPath));
+        assert_eq!(try_move(start, "e1g1"), Err(IllegalMoveError::BlockedPath));
+        assert_eq!(
+            try_move(start, "e2e5"),
+            Err(IllegalMoveError::UnreachableDestination(
+                square("e2"),
+                square("e5")
+            ))
+        );
+
+        let pinned = "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1";
+        assert_eq!(
+            try_move(pinned, "e2d3"),
+            Err(IllegalMoveError::LeavesKingInCheck)
+        );
+
+        let promotion = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(
+            try_move(promotion, "a7a8"),
+            Err(IllegalMoveError::MissingPromotion)
+        );
+        assert_eq!(
+            try_move(promotion, "a7a8n"),
+            Ok("N3k3/8/8/8/8/8/8/4K3 b - - 0 1".to_owned())
+        );
+
+        let castling = "4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1";
+        assert_eq!(
+            try_move(castling, "e1g1"),
+            Err(IllegalMoveError::CastlingThroughCheck)
+        );
+        assert_eq!(
+            try_move(castling, "e1h1"),
+            Err(IllegalMoveError::CastlingThroughCheck)
+        );
+        assert_eq!(
+            try_move(castling, "e1c1"),
+            Ok("4k3/8/8/8/8/8/5r2/2KR3R b - - 0 1".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_attackers_pins_and_checkers() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let square = |name: &str| Square::from_str(name).unwrap();
+        let squares = |names: &[&str]| {
+            let mut bitboard = BitBoard::zeros();
+            for name in names {
+                bitboard.set_one(&square(name));
+            }
+            bitboard
+        };
+
+        let board = Board::new(&hasher);
+        let attackers = |board: &Board, name: &str, occupancy: BitBoard| {
+            board.attackers_to(&square(name), occupancy, &move_gen_masks)
+        };
+        assert!(attackers(&board, "f3", board.all_pieces) == squares(&["e2", "g2", "g1"]));
+        assert!(attackers(&board, "d6", board.all_pieces) == squares(&["c7", "e7"]));
+        assert!(attackers(&board, "d4", board.all_pieces).is_empty());
+        assert!(attackers(&board, "d4", board.all_pieces ^ squares(&["d2"])) == squares(&["d1"]));
+        assert!(board.checkers(&move_gen_masks).is_empty());
+        assert!(board.pinned(Color::WHITE, &move_gen_masks).is_empty());
+
+        // a1 rook has two blockers, the others pin one piece each
+        let board = Board::from_fen("4k3/4r3/8/8/1b6/8/3NB3/rNB1K1Rr w - - 0 1", &hasher).unwrap();
+        assert!(board.pinned(Color::WHITE, &move_gen_masks) == squares(&["d2", "e2", "g1"]));
+        assert!(board.pinned(Color::BLACK, &move_gen_masks).is_empty());
+        assert!(board.checkers(&move_gen_masks).is_empty());
+
+        let board = Board::from_fen("4k3/8/8/8/1b6/3n4/8/4K3 w - - 0 1", &hasher).unwrap();
+        assert!(board.checkers(&move_gen_masks) == squares(&["b4", "d3"]));
+    }
+
+    #[test]
+    fn test_play_undo_and_copy_make() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let mut rng = StdRng::seed_from_u64(0x5ac4);
+        let mut board = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &hasher,
+        )
+        .unwrap();
+        assert!(board.undo(&hasher).is_none());
+
+        let mut played = Vec::new();
+        for _ in 0..60 {
+            let legal_moves = board.get_legal_moves(&move_gen_masks, &hasher);
+            if legal_moves.is_empty() {
+                break;
+            }
+            let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+
+            let copy = board.after_move(&the_move, &hasher);
+            played.push((board.get_shredder_fen(), board.zobrist, the_move));
+            board.play(&the_move, &hasher);
+            assert_eq!(copy.get_shredder_fen(), board.get_shredder_fen());
+            assert_eq!(copy.zobrist, board.zobrist);
+        }
+
+        while let Some((fen, zobrist, the_move)) = played.pop() {
+            assert_eq!(board.undo(&hasher), Some(the_move));
+            assert_eq!(board.get_shredder_fen(), fen);
+            assert_eq!(board.zobrist, zobrist);
+        }
+        assert!(board.undo(&hasher).is_none());
+        assert_eq!(board.position_history.len(), 1);
+    }
+
+    #[test]
+    fn test_null_move() {
+        let hasher = ZobristHasher::load();
+        let move_gen_masks = MoveGenMasks::load();
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 4 3";
+        let mut board = Board::from_fen(fen, &hasher).unwrap();
+
+        let helper = board.make_null_move(&move_gen_masks, &hasher).unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 4 3"
+        );
+        assert_eq!(board.zobrist, hasher.hash_everyting(&board));
+        assert_eq!(board.position_history.len(), 2);
+        board.unmake_null_move(helper);
+        assert_eq!(board.get_fen(), fen);
+        assert_eq!(board.zobrist, hasher.hash_everyting(&board));
+        assert_eq!(board.position_history.len(), 1);
+
+        let mut in_check = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1", &hasher).unwrap();
+        assert!(in_check.make_null_move(&move_gen_masks, &hasher).is_none());
+
+        // Nf3, pass, Ng1, pass reaches the start position again, but only through null moves
+        let mut board = Board::new(&hasher);
+        let knight_move = |board: &mut Board, move_str: &str| {
+            board
+                .check_and_make_move(&Move::from_long_str(move_str), &move_gen_masks, &hasher)
+                .unwrap();
+        };
+        for _ in 0..3 {
+            knight_move(&mut board, "g1f3");
+            board.make_null_move(&move_gen_masks, &hasher).unwrap();
+            knight_move(&mut board, "f3g1");
+            board.make_null_move(&move_gen_masks, &hasher).unwrap();
+            assert_eq!(board.zobrist, hasher.hash_everyting(&Board::new(&hasher)));
+            assert!(!board.check_repeat_draw());
+        }
+        assert_eq!(board.state.half_moves, 6);
+    }
+
+    #[test]
+    fn test_fen_mutations_do_not_panic() {
+        let hasher = ZobristHasher::load();
+        let mut rng = StdRng::seed_from_u64(0xfe11);
+        let alphabet: Vec<char> = "pnbrqkPNBRQK0123456789/ -wbKQkqabcdefgh".chars().collect();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+
+        for fen in fens {
+            for _ in 0..2000 {
+                let mut chars: Vec<char> = fen.chars().collect();
+                for _ in 0..rng.random_range(1..4) {
+                    let i = rng.random_range(0..chars.len());
+                    let new_char = alphabet[rng.random_range(0..alphabet.len())];
+                    match rng.random_range(0..3) {
+                        0 => chars[i] = new_char,
+                        1 => chars.insert(i, new_char),
+                        _ => {
+                            chars.remove(i);
+                        }
+                    }
+                }
+                let mutated: String = chars.into_iter().collect();
+
+                if let Ok(board) = Board::from_fen(&mutated, &hasher) {
+                    let round_trip = board.get_fen();
+                    assert_eq!(
+                        Board::from_fen(&round_trip, &hasher).unwrap().get_fen(),
+                        round_trip
+                    );
+                }
+            }
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
-    sync::atomic::Ordering,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
@@ -16,17 +19,60 @@ use crate::{
 
 use crate::game::UCI_STOP;
 
-use super::{pesto::PeSTO, time_control::TimeControl};
+use super::{
+    endgame::probe_endgame,
+    king_safety::evaluate_king_safety,
+    mobility::evaluate_mobility,
+    pawns::{evaluate_pawns, PawnHashTable},
+    pesto::PeSTO,
+    time_control::TimeControl,
+};
 
 const MIN_VALUE: i32 = -1_000_000;
 const MAX_VALUE: i32 = 1_000_000;
-const CHECKMATE_SCORE: i32 = 990_000;
+pub const CHECKMATE_SCORE: i32 = 990_000;
+const MAX_MATE_PLY: i32 = 1000;
+
+/// Outcome of an iterative deepening search
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    /// Number of moves to mate, negative when the side to move is getting mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if !is_mate_score(self.score) {
+            return None;
+        }
+        let plies = CHECKMATE_SCORE - self.score.abs();
+        let moves = (plies + 1) / 2;
+        if self.score > 0 {
+            Some(moves)
+        } else {
+            Some(-moves)
+        }
+    }
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= CHECKMATE_SCORE - MAX_MATE_PLY
+}
 
 pub struct Bot {
     evaluation_cache: HashMap<ZobristHash, i32>,
     piece_values: [i32; 6],
     max_depth: u8,
+    max_nodes: Option<u64>,
+    nodes_searched: u64,
+    print_info: bool,
+    /// set when this bot runs out of time or nodes, UCI_STOP stops every search
+    stop: Arc<AtomicBool>,
     pesto: PeSTO,
+    pawn_table: PawnHashTable,
     time_control: TimeControl,
 }
 
@@ -40,7 +86,12 @@ impl Bot {
             evaluation_cache: HashMap::with_capacity(1000),
             piece_values,
             max_depth,
-            pesto: PeSTO::default(),
+            max_nodes: None,
+            nodes_searched: 0,
+            print_info: true,
+            stop: Arc::new(AtomicBool::new(false)),
+            pesto: PeSTO,
+            pawn_table: PawnHashTable::new(),
             time_control,
         }
     }
@@ -53,6 +104,21 @@ impl Bot {
         self.time_control = time_control
     }
 
+    /// Stops the search after roughly `max_nodes` nodes, None means no limit
+    pub fn set_max_nodes(&mut self, max_nodes: Option<u64>) {
+        self.max_nodes = max_nodes
+    }
+
+    /// Turns the UCI info output during search on or off
+    pub fn set_print_info(&mut self, print_info: bool) {
+        self.print_info = print_info
+    }
+
+    /// Forgets cached evaluations, needed when the evaluation changes
+    pub fn clear_evaluation_cache(&mut self) {
+        self.evaluation_cache.clear()
+    }
+
     fn make_random_move(moves: Vec<(Move, BitBoard)>) -> (Move, BitBoard) {
         let mut rng = rand::rng();
         let i = rng.random_range(0..moves.len());
@@ -60,40 +126,36 @@ impl Bot {
         moves.into_iter().nth(i).unwrap()
     }
 
-    fn evaluate_position(
-        &mut self,
-        board: &mut Board,
-        move_gen_masks: &MoveGenMasks,
-        hasher: &ZobristHasher,
-    ) -> i32 {
+    fn should_stop(&mut self) -> bool {
+        if let Some(max_nodes) = self.max_nodes {
+            if self.nodes_searched >= max_nodes {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        self.stop.load(Ordering::Relaxed) || UCI_STOP.load(Ordering::Relaxed)
+    }
+
+    fn evaluate_position(&mut self, board: &Board, move_gen_masks: &MoveGenMasks) -> i32 {
         if let Some(eval_value) = self.evaluation_cache.get(&board.zobrist) {
             return *eval_value;
         }
-        let mut eval_value = 0;
-        let n_legal_moves = self.get_number_of_moves(board, move_gen_masks, hasher);
-        if n_legal_moves == 0 {
-            if board.is_check(move_gen_masks) {
-                return -CHECKMATE_SCORE;
-            } else {
-                return 0;
+        let mut eval_value = match &board.nnue {
+            Some(nnue) => nnue.evaluate(board.state.turn),
+            None => {
+                self.pesto.calculate_score(board)
+                    + evaluate_pawns(board, &mut self.pawn_table, move_gen_masks)
+                    + evaluate_king_safety(board, move_gen_masks)
+                    + evaluate_mobility(board, move_gen_masks)
             }
+        };
+        if let Some(endgame_eval) = probe_endgame(board, move_gen_masks) {
+            eval_value = endgame_eval.apply(eval_value, board);
         }
-        eval_value += self.pesto.calculate_score(board);
-        eval_value += n_legal_moves;
         self.evaluation_cache.insert(board.zobrist, eval_value);
 
         eval_value
     }
 
-    fn get_number_of_moves(
-        &self,
-        board: &mut Board,
-        move_gen_masks: &MoveGenMasks,
-        hasher: &ZobristHasher,
-    ) -> i32 {
-        board.get_legal_moves(move_gen_masks, hasher).len() as i32
-    }
-
     fn quiescence(
         &mut self,
         mut alpha: i32,
@@ -101,11 +163,22 @@ impl Bot {
         board: &mut Board,
         move_gen_masks: &MoveGenMasks,
         hasher: &ZobristHasher,
+        ply: u8,
     ) -> (i32, u64) {
-        if UCI_STOP.load(Ordering::Relaxed) {
+        if self.should_stop() {
             return (0, 0);
         }
-        let mut best_value = self.evaluate_position(board, move_gen_masks, hasher);
+        self.nodes_searched += 1;
+        let capture_moves = board.get_legal_captures(move_gen_masks, hasher);
+        // a legal capture rules out mate and stalemate, in check or not
+        if capture_moves.is_empty() && board.get_legal_moves(move_gen_masks, hasher).is_empty() {
+            if board.is_check(move_gen_masks) {
+                return (-CHECKMATE_SCORE + ply as i32, 1);
+            }
+            return (0, 1);
+        }
+
+        let mut best_value = self.evaluate_position(board, move_gen_masks);
 
         if best_value >= beta {
             return (best_value, 1);
@@ -117,13 +190,11 @@ impl Bot {
             alpha = best_value;
         }
 
-        let capture_moves = board.get_capture_moves(move_gen_masks, hasher);
-
         for new_move in capture_moves {
             let unmake_move_helper = board.make_move(&new_move, hasher);
             let (opponent_score, nodes) =
-                self.quiescence(-beta, -alpha, board, move_gen_masks, hasher);
-            board.unmake_move(unmake_move_helper);
+                self.quiescence(-beta, -alpha, board, move_gen_masks, hasher, ply + 1);
+            board.unmake_move(unmake_move_helper, hasher);
             let score = -opponent_score;
             nodes_checked += nodes;
 
@@ -142,6 +213,7 @@ impl Bot {
         (best_value, nodes_checked)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn alpha_beta(
         &mut self,
         board: &mut Board,
@@ -150,31 +222,43 @@ impl Bot {
         mut alpha: i32,
         beta: i32,
         depth: u8,
+        ply: u8,
     ) -> (i32, u64) {
         if board.check_repeat_draw() {
             return (0, 1);
         }
         // TODO: Figure out a better way to stop instead of returning 0
-        if UCI_STOP.load(Ordering::Relaxed) {
+        if self.should_stop() {
             return (0, 0);
         }
         if depth == 0 {
-            return self.quiescence(alpha, beta, board, move_gen_masks, hasher);
+            return self.quiescence(alpha, beta, board, move_gen_masks, hasher, ply);
         }
+        self.nodes_searched += 1;
 
         let mut nodes_checked = 0;
         let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
-        if legal_moves.len() == 0 {
-            return (-CHECKMATE_SCORE, 1);
+        if legal_moves.is_empty() {
+            if board.is_check(move_gen_masks) {
+                return (-CHECKMATE_SCORE + ply as i32, 1);
+            }
+            return (0, 1);
         }
         for new_move in legal_moves.iter() {
-            let unmake_move_helper = board.make_move(&new_move, hasher);
-            let (opponent_score, nodes) =
-                self.alpha_beta(board, move_gen_masks, hasher, -beta, -alpha, depth - 1);
+            let unmake_move_helper = board.make_move(new_move, hasher);
+            let (opponent_score, nodes) = self.alpha_beta(
+                board,
+                move_gen_masks,
+                hasher,
+                -beta,
+                -alpha,
+                depth - 1,
+                ply + 1,
+            );
             let score = -opponent_score;
             nodes_checked += nodes;
 
-            board.unmake_move(unmake_move_helper);
+            board.unmake_move(unmake_move_helper, hasher);
 
             if score >= beta {
                 return (beta, nodes_checked);
@@ -193,38 +277,67 @@ impl Bot {
         move_gen_masks: &MoveGenMasks,
         hasher: &ZobristHasher,
     ) -> Move {
+        self.search(board, move_gen_masks, hasher).best_move
+    }
+
+    /// Iterative deepening search within the depth, node and time limits of the bot
+    pub fn search(
+        &mut self,
+        board: &mut Board,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> SearchResult {
         let move_start = Instant::now();
         let move_max_time = self.time_control.get_move_time(&board.state.turn) as u128;
-        println!("info time for move {}", move_max_time);
+        if self.print_info {
+            println!("info time for move {}", move_max_time);
+        }
+        self.stop.store(false, Ordering::Relaxed);
+        let stop = Arc::clone(&self.stop);
         let time_thread = thread::spawn(move || loop {
             if move_start.elapsed().as_millis() > move_max_time {
-                UCI_STOP.store(true, Ordering::Relaxed);
+                stop.store(true, Ordering::Relaxed);
                 return;
             }
-            if UCI_STOP.load(Ordering::Relaxed) {
+            if stop.load(Ordering::Relaxed) || UCI_STOP.load(Ordering::Relaxed) {
                 return;
             }
-            thread::sleep(Duration::from_millis(300));
+            thread::park_timeout(Duration::from_millis(300));
         });
 
-        let mut results: Vec<Move> = Vec::with_capacity(self.max_depth as usize);
+        self.nodes_searched = 0;
+        let mut result: Option<SearchResult> = None;
 
         for depth in 1..=self.max_depth {
             let (best_score, best_move) =
                 self.get_best_move_for_depth(depth, board, move_gen_masks, hasher);
-
-            if best_score == CHECKMATE_SCORE {
-                return best_move;
+            let stopped = self.should_stop();
+            let found_mate = is_mate_score(best_score) && best_score > 0;
+
+            // an interrupted depth is only used if nothing better is available
+            if best_move != Move::new() && (found_mate || !stopped || result.is_none()) {
+                result = Some(SearchResult {
+                    best_move,
+                    score: best_score,
+                    depth,
+                    nodes: self.nodes_searched,
+                });
             }
 
-            if UCI_STOP.load(Ordering::Relaxed) {
+            if found_mate || stopped {
                 break;
             }
-            results.push(best_move);
         }
-        UCI_STOP.store(true, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+        time_thread.thread().unpark();
         time_thread.join().unwrap();
-        results.into_iter().filter(|the_move| the_move != &Move::new()).last().unwrap()
+
+        result.unwrap_or_else(|| SearchResult {
+            best_move: board.get_legal_moves(move_gen_masks, hasher)[0],
+            score: 0,
+            depth: 0,
+            nodes: self.nodes_searched,
+        })
     }
 
     pub fn get_best_move_for_depth(
@@ -242,16 +355,16 @@ impl Bot {
         let start = Instant::now();
 
         for new_move in board.get_legal_moves(move_gen_masks, hasher) {
-            if UCI_STOP.load(Ordering::Relaxed) {
+            if self.should_stop() {
                 break;
             }
             let unmake_move_helper = board.make_move(&new_move, hasher);
             let (opponent_score, nodes) =
-                self.alpha_beta(board, move_gen_masks, hasher, -beta, -alpha, depth - 1);
-            board.unmake_move(unmake_move_helper);
+                self.alpha_beta(board, move_gen_masks, hasher, -beta, -alpha, depth - 1, 1);
+            board.unmake_move(unmake_move_helper, hasher);
             let score = -opponent_score;
 
-            if score == CHECKMATE_SCORE {
+            if score >= CHECKMATE_SCORE - 1 {
                 return (score, new_move);
             }
 
@@ -261,15 +374,23 @@ impl Bot {
             }
             nodes_checked += nodes;
         }
-        let elapsed = start.elapsed().as_micros();
-
-        println!("info depth {} seldepth {}", depth, self.max_depth);
-        println!(
-            "info score cp {}  depth {} nodes {}",
-            alpha, depth, nodes_checked
-        );
-        println!("info nps {}", (nodes_checked as u128 * 1_000_000) / elapsed);
-        println!("Checked {} nodes", nodes_checked);
+        let elapsed = start.elapsed().as_micros().max(1);
+
+        if self.print_info {
+            let score = if is_mate_score(alpha) {
+                let plies = CHECKMATE_SCORE - alpha.abs();
+                format!("mate {}", alpha.signum() * (plies + 1) / 2)
+            } else {
+                format!("cp {}", alpha)
+            };
+            println!("info depth {} seldepth {}", depth, self.max_depth);
+            println!(
+                "info score {}  depth {} nodes {}",
+                score, depth, nodes_checked
+            );
+            println!("info nps {}", (nodes_checked as u128 * 1_000_000) / elapsed);
+            println!("Checked {} nodes", nodes_checked);
+        }
         best_move
     }
 }
@@ -284,7 +405,12 @@ impl Default for Bot {
             evaluation_cache: HashMap::with_capacity(1000),
             piece_values,
             max_depth: 5,
-            pesto: PeSTO::default(),
+            max_nodes: None,
+            nodes_searched: 0,
+            print_info: true,
+            stop: Arc::new(AtomicBool::new(false)),
+            pesto: PeSTO,
+            pawn_table: PawnHashTable::new(),
             time_control: TimeControl::max(),
         }
     }
@@ -310,6 +436,21 @@ mod test_bot_evaluation {
         println!("{}", best_move.to_long_string())
     }
 
+    #[test]
+    fn test_search_with_fen_half_move_clock() {
+        let move_gen_masks = MoveGenMasks::load();
+        let hasher = ZobristHasher::load();
+        // the clock is far larger than the one position recorded since the FEN
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 20 40", &hasher).unwrap();
+        assert!(!board.check_repeat_draw());
+
+        let mut bot = Bot::with_depth(3, TimeControl::max());
+        let best_move = bot.get_best_move(&mut board, &move_gen_masks, &hasher);
+        assert!(board
+            .get_legal_moves(&move_gen_masks, &hasher)
+            .contains(&best_move));
+    }
+
     #[test]
     fn test_a() {
         let move_gen_masks = MoveGenMasks::load();
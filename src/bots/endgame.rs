@@ -0,0 +1,548 @@
+// https://www.chessprogramming.org/Endgame_Evaluation
+
+use std::sync::OnceLock;
+
+use crate::{
+    board::Board,
+    moves::{
+        geometry::{pawn_attacks, Geometry, FILE_A, FILE_H},
+        move_mask_gen::MoveGenMasks,
+    },
+    types::{
+        piece::{Color, Pieces},
+        square::Square,
+    },
+};
+
+const KNOWN_WIN: i32 = 10_000;
+/// scale factors are out of this
+pub const SCALE_NORMAL: i32 = 64;
+const SCALE_OPPOSITE_BISHOPS: i32 = 16;
+const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 46;
+const SCALE_DRAW: i32 = 0;
+const MOP_UP_VALUES: [i32; 6] = [900, 500, 330, 320, 100, 0];
+const DARK_SQUARES: u64 = 0xaa55_aa55_aa55_aa55;
+
+/// 0 in the center, 6 in the corners
+fn center_distance(square: usize) -> i32 {
+    let file = (square % 8) as i32;
+    let rank = (square / 8) as i32;
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+fn is_dark(square: usize) -> bool {
+    DARK_SQUARES & (1 << square) != 0
+}
+
+/// What the endgame module knows about a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndgameEval {
+    /// replaces the evaluation, from the side to move's point of view
+    Score(i32),
+    /// multiplies the evaluation by factor / SCALE_NORMAL, indexed by the
+    /// color the evaluation favors
+    Scale([i32; 2]),
+}
+
+impl EndgameEval {
+    pub fn apply(&self, eval: i32, board: &Board) -> i32 {
+        match self {
+            EndgameEval::Score(score) => *score,
+            EndgameEval::Scale(factors) => {
+                let leader = if eval > 0 {
+                    board.state.turn
+                } else {
+                    board.state.opponent
+                };
+                eval * factors[leader] / SCALE_NORMAL
+            }
+        }
+    }
+}
+
+/// Piece counts per color
+struct Material {
+    counts: [[u32; 6]; 2],
+}
+
+impl Material {
+    fn from_board(board: &Board) -> Self {
+        let mut counts = [[0; 6]; 2];
+        for (color, pieces) in board.pieces.iter().enumerate() {
+            for (piece, piece_board) in pieces.iter().enumerate() {
+                counts[color][piece] = piece_board.as_u64().count_ones();
+            }
+        }
+        Self { counts }
+    }
+
+    fn non_pawn_pieces(&self, color: usize) -> u32 {
+        self.counts[color][Pieces::QUEEN]
+            + self.counts[color][Pieces::ROOK]
+            + self.counts[color][Pieces::BISHOP]
+            + self.counts[color][Pieces::KNIGHT]
+    }
+
+    fn is_lone_king(&self, color: usize) -> bool {
+        self.non_pawn_pieces(color) == 0 && self.counts[color][Pieces::PAWN] == 0
+    }
+
+    /// only these pieces besides the king
+    fn is_exactly(&self, color: usize, pieces: &[(usize, u32)]) -> bool {
+        (0..5).all(|piece| {
+            let expected = pieces
+                .iter()
+                .find(|(p, _)| *p == piece)
+                .map_or(0, |(_, n)| *n);
+            self.counts[color][piece] == expected
+        })
+    }
+}
+
+/// Dedicated evaluation or scaling for known endgames, None for everything else
+pub fn probe_endgame(board: &Board, move_gen_masks: &MoveGenMasks) -> Option<EndgameEval> {
+    let geometry = &move_gen_masks.geometry;
+    let material = Material::from_board(board);
+
+    for strong in [Color::WHITE, Color::BLACK] {
+        let weak = 1 - strong;
+        if !material.is_lone_king(weak) {
+            continue;
+        }
+
+        let score = if material.is_exactly(strong, &[(Pieces::PAWN, 1)]) {
+            Some(evaluate_kpk(board, strong, move_gen_masks))
+        } else if material.is_exactly(strong, &[(Pieces::BISHOP, 1), (Pieces::KNIGHT, 1)]) {
+            Some(evaluate_kbnk(board, strong, geometry))
+        } else if material.counts[strong][Pieces::QUEEN] + material.counts[strong][Pieces::ROOK] > 0
+        {
+            Some(evaluate_kxk(board, strong, &material, geometry))
+        } else {
+            None
+        };
+
+        if let Some(score) = score {
+            let score = if board.state.turn == strong {
+                score
+            } else {
+                -score
+            };
+            return Some(EndgameEval::Score(score));
+        }
+    }
+
+    let factors = [
+        scale_factor(board, &material, Color::WHITE, geometry),
+        scale_factor(board, &material, Color::BLACK, geometry),
+    ];
+    if factors == [SCALE_NORMAL; 2] {
+        None
+    } else {
+        Some(EndgameEval::Scale(factors))
+    }
+}
+
+/// How much of an advantage for `strong` should count
+fn scale_factor(board: &Board, material: &Material, strong: usize, geometry: &Geometry) -> i32 {
+    let weak = 1 - strong;
+
+    // without pawns a single minor or two knights can't mate
+    if material.counts[strong][Pieces::PAWN] == 0
+        && material.counts[strong][Pieces::QUEEN] + material.counts[strong][Pieces::ROOK] == 0
+        && ((material.counts[strong][Pieces::BISHOP] == 0
+            && material.counts[strong][Pieces::KNIGHT] <= 2)
+            || material.non_pawn_pieces(strong) <= 1)
+    {
+        return SCALE_DRAW;
+    }
+
+    if is_wrong_rook_pawn(board, material, strong, geometry) {
+        return SCALE_DRAW;
+    }
+
+    if material.counts[strong][Pieces::BISHOP] == 1
+        && material.counts[weak][Pieces::BISHOP] == 1
+        && is_dark(board.pieces[strong][Pieces::BISHOP].get_one().as_usize())
+            != is_dark(board.pieces[weak][Pieces::BISHOP].get_one().as_usize())
+    {
+        if material.non_pawn_pieces(strong) == 1 && material.non_pawn_pieces(weak) == 1 {
+            return SCALE_OPPOSITE_BISHOPS;
+        }
+        return SCALE_OPPOSITE_BISHOPS_WITH_PIECES;
+    }
+
+    SCALE_NORMAL
+}
+
+/// Bishop and rook pawns where the bishop can't cover the promotion square
+/// and the defending king sits in front of it
+fn is_wrong_rook_pawn(
+    board: &Board,
+    material: &Material,
+    strong: usize,
+    geometry: &Geometry,
+) -> bool {
+    let weak = 1 - strong;
+    if !material.is_exactly(
+        strong,
+        &[
+            (Pieces::BISHOP, 1),
+            (Pieces::PAWN, material.counts[strong][Pieces::PAWN]),
+        ],
+    ) || material.counts[strong][Pieces::PAWN] == 0
+        || material.non_pawn_pieces(weak) > 0
+    {
+        return false;
+    }
+
+    let pawns = board.pieces[strong][Pieces::PAWN].as_u64();
+    let file = if pawns & !FILE_A == 0 {
+        0
+    } else if pawns & !FILE_H == 0 {
+        7
+    } else {
+        return false;
+    };
+
+    let promotion_square = if strong == Color::WHITE {
+        56 + file
+    } else {
+        file
+    };
+    let bishop_square = board.pieces[strong][Pieces::BISHOP].get_one().as_usize();
+    let weak_king = board.pieces[weak][Pieces::KING].get_one();
+
+    is_dark(bishop_square) != is_dark(promotion_square)
+        && geometry.distance(&weak_king, &Square::new(promotion_square as u8)) <= 1
+}
+
+/// Mating with a heavy piece, push the lone king to the edge
+fn evaluate_kxk(board: &Board, strong: usize, material: &Material, geometry: &Geometry) -> i32 {
+    let weak = 1 - strong;
+    let strong_king = board.pieces[strong][Pieces::KING].get_one();
+    let weak_king = board.pieces[weak][Pieces::KING].get_one();
+
+    let material_score: i32 = (0..5)
+        .map(|piece| material.counts[strong][piece] as i32 * MOP_UP_VALUES[piece])
+        .sum();
+
+    KNOWN_WIN + material_score + 20 * center_distance(weak_king.as_usize())
+        - 10 * geometry.distance(&strong_king, &weak_king) as i32
+}
+
+/// Bishop and knight mate, the lone king has to go to a corner of the bishop's color
+fn evaluate_kbnk(board: &Board, strong: usize, geometry: &Geometry) -> i32 {
+    let weak = 1 - strong;
+    let strong_king = board.pieces[strong][Pieces::KING].get_one();
+    let weak_king = board.pieces[weak][Pieces::KING].get_one();
+    let bishop = board.pieces[strong][Pieces::BISHOP].get_one().as_usize();
+
+    let corners = if is_dark(bishop) { [0, 63] } else { [7, 56] };
+    let corner_distance = corners
+        .iter()
+        .map(|corner| geometry.distance(&weak_king, &Square::new(*corner)) as i32)
+        .min()
+        .unwrap();
+
+    KNOWN_WIN
+        + MOP_UP_VALUES[Pieces::BISHOP]
+        + MOP_UP_VALUES[Pieces::KNIGHT]
+        + 60 * (7 - corner_distance)
+        + 10 * center_distance(weak_king.as_usize())
+        - 10 * geometry.distance(&strong_king, &weak_king) as i32
+}
+
+/// King and pawn against king, exact with the bitbase
+fn evaluate_kpk(board: &Board, strong: usize, move_gen_masks: &MoveGenMasks) -> i32 {
+    let weak = 1 - strong;
+    let pawn = board.pieces[strong][Pieces::PAWN].get_one();
+    let strong_king = board.pieces[strong][Pieces::KING].get_one();
+    let weak_king = board.pieces[weak][Pieces::KING].get_one();
+
+    if !kpk_probe(
+        strong_king,
+        pawn,
+        weak_king,
+        board.state.turn == strong,
+        strong,
+        move_gen_masks,
+    ) {
+        return 0;
+    }
+
+    let relative_rank = if strong == Color::WHITE {
+        pawn.get_rank()
+    } else {
+        7 - pawn.get_rank()
+    } as i32;
+    KNOWN_WIN + MOP_UP_VALUES[Pieces::PAWN] + 20 * relative_rank
+}
+
+// https://www.chessprogramming.org/KPK
+// indexed by white king, black king, side to move and the pawn on files a-d, ranks 2-7
+const KPK_SIZE: usize = 2 * 24 * 64 * 64;
+
+const KPK_INVALID: u8 = 0;
+const KPK_UNKNOWN: u8 = 1;
+const KPK_DRAW: u8 = 2;
+const KPK_WIN: u8 = 4;
+
+static KPK_BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn kpk_index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    white_king
+        | (black_king << 6)
+        | ((!white_to_move as usize) << 12)
+        | ((pawn % 8) << 13)
+        | ((6 - pawn / 8) << 15)
+}
+
+fn kpk_classify_initial(
+    white_to_move: bool,
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+    move_gen_masks: &MoveGenMasks,
+) -> u8 {
+    let king_moves = |square: usize| move_gen_masks.king_moves[square].0;
+    let geometry = &move_gen_masks.geometry;
+    let distance =
+        |a: usize, b: usize| geometry.distance(&Square::new(a as u8), &Square::new(b as u8));
+    if distance(white_king, black_king) <= 1
+        || white_king == pawn
+        || black_king == pawn
+        || (white_to_move && pawn_attacks(1 << pawn, Color::WHITE) & (1 << black_king) != 0)
+    {
+        return KPK_INVALID;
+    }
+
+    let push_square = pawn + 8;
+    if white_to_move
+        && pawn / 8 == 6
+        && white_king != push_square
+        && black_king != push_square
+        && (distance(black_king, push_square) > 1 || distance(white_king, push_square) == 1)
+    {
+        return KPK_WIN;
+    }
+
+    if !white_to_move {
+        let black_moves = king_moves(black_king);
+        let white_control = king_moves(white_king) | pawn_attacks(1 << pawn, Color::WHITE);
+        let stalemate = black_moves & !white_control == 0;
+        let takes_pawn = black_moves & (1 << pawn) & !king_moves(white_king) != 0;
+        if stalemate || takes_pawn {
+            return KPK_DRAW;
+        }
+    }
+
+    KPK_UNKNOWN
+}
+
+fn kpk_classify(
+    db: &[u8],
+    move_gen_masks: &MoveGenMasks,
+    white_to_move: bool,
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+) -> u8 {
+    let mut children = 0;
+
+    if white_to_move {
+        for king_move in move_gen_masks.king_moves[white_king].get_ones() {
+            children |= db[kpk_index(false, king_move.as_usize(), black_king, pawn)];
+        }
+        let push_square = pawn + 8;
+        if pawn / 8 < 6 {
+            children |= db[kpk_index(false, white_king, black_king, push_square)];
+        }
+        if pawn / 8 == 1 && push_square != white_king && push_square != black_king {
+            children |= db[kpk_index(false, white_king, black_king, pawn + 16)];
+        }
+
+        if children & KPK_WIN != 0 {
+            KPK_WIN
+        } else if children & KPK_UNKNOWN != 0 {
+            KPK_UNKNOWN
+        } else {
+            KPK_DRAW
+        }
+    } else {
+        for king_move in move_gen_masks.king_moves[black_king].get_ones() {
+            children |= db[kpk_index(true, white_king, king_move.as_usize(), pawn)];
+        }
+
+        if children & KPK_DRAW != 0 {
+            KPK_DRAW
+        } else if children & KPK_UNKNOWN != 0 {
+            KPK_UNKNOWN
+        } else {
+            KPK_WIN
+        }
+    }
+}
+
+/// Iterates until no unknown position can be resolved, what's left is a draw
+fn generate_kpk_bitbase(move_gen_masks: &MoveGenMasks) -> Vec<u64> {
+    let mut db = vec![KPK_INVALID; KPK_SIZE];
+    let positions = || {
+        (0..KPK_SIZE).map(|index| {
+            let white_king = index & 0x3f;
+            let black_king = (index >> 6) & 0x3f;
+            let white_to_move = (index >> 12) & 1 == 0;
+            let pawn = (6 - (index >> 15)) * 8 + ((index >> 13) & 3);
+            (index, white_to_move, white_king, black_king, pawn)
+        })
+    };
+
+    for (index, white_to_move, white_king, black_king, pawn) in positions() {
+        db[index] =
+            kpk_classify_initial(white_to_move, white_king, black_king, pawn, move_gen_masks);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, white_to_move, white_king, black_king, pawn) in positions() {
+            if db[index] == KPK_UNKNOWN {
+                let result = kpk_classify(
+                    &db,
+                    move_gen_masks,
+                    white_to_move,
+                    white_king,
+                    black_king,
+                    pawn,
+                );
+                if result != KPK_UNKNOWN {
+                    db[index] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bitbase = vec![0_u64; KPK_SIZE / 64];
+    for (index, result) in db.into_iter().enumerate() {
+        if result == KPK_WIN {
+            bitbase[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bitbase
+}
+
+/// Whether the side with the pawn wins, built on first use
+pub fn kpk_probe(
+    strong_king: Square,
+    pawn: Square,
+    weak_king: Square,
+    strong_to_move: bool,
+    strong: usize,
+    move_gen_masks: &MoveGenMasks,
+) -> bool {
+    let bitbase = KPK_BITBASE.get_or_init(|| generate_kpk_bitbase(move_gen_masks));
+
+    // flip so the pawn is white and on files a-d
+    let mut squares = [
+        strong_king.as_usize(),
+        weak_king.as_usize(),
+        pawn.as_usize(),
+    ];
+    if strong == Color::BLACK {
+        squares = squares.map(|square| square ^ 56);
+    }
+    if squares[2] % 8 > 3 {
+        squares = squares.map(|square| square ^ 7);
+    }
+
+    let index = kpk_index(strong_to_move, squares[0], squares[1], squares[2]);
+    bitbase[index / 64] & (1 << (index % 64)) != 0
+}
+
+#[cfg(test)]
+mod test_endgame {
+    use super::*;
+    use crate::{bots::test_utils::mirror_fen, utils::zobrist::ZobristHasher};
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn probe(fen: &str) -> Option<EndgameEval> {
+        let board = Board::from_fen(fen, &HASHER).unwrap();
+        probe_endgame(&board, &MOVE_GEN_MASKS)
+    }
+
+    fn score(fen: &str) -> i32 {
+        match probe(fen) {
+            Some(EndgameEval::Score(score)) => score,
+            other => panic!("{} gave {:?}", fen, other),
+        }
+    }
+
+    #[test]
+    fn test_kpk_bitbase() {
+        // opposition decides
+        assert_eq!(score("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), 0);
+        assert!(score("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1") < -KNOWN_WIN);
+        // king in front of its pawn
+        assert!(score("8/8/8/8/4K3/8/4P3/k7 w - - 0 1") > KNOWN_WIN);
+        // the defending king reaches the rook pawn's corner
+        assert_eq!(score("k7/8/8/8/8/8/P7/7K w - - 0 1"), 0);
+        // outside the square of the pawn
+        assert!(score("8/8/8/1P6/8/8/7k/K7 w - - 0 1") > KNOWN_WIN);
+        assert_eq!(score("8/8/4k3/1P6/8/8/8/K7 b - - 0 1"), 0);
+
+        let fens = [
+            "8/4k3/8/4K3/4P3/8/8/8 b - - 0 1",
+            "8/8/8/8/4K3/8/4P3/k7 w - - 0 1",
+            "8/8/8/6P1/8/8/2k5/7K b - - 0 1",
+        ];
+        for fen in fens {
+            assert_eq!(score(fen), score(&mirror_fen(fen)), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_mop_up() {
+        // bishop on the dark squares, a1 is the right corner
+        let right_corner = score("8/8/8/8/4N3/2K5/8/k1B5 w - - 0 1");
+        let wrong_corner = score("8/8/8/8/4N3/5K2/8/2B4k w - - 0 1");
+        assert!(right_corner > wrong_corner);
+
+        let edge = score("2k5/8/2K5/8/8/8/8/R7 w - - 0 1");
+        let center = score("8/8/8/3k4/8/8/2K5/R7 w - - 0 1");
+        assert!(edge > center);
+        assert_eq!(
+            score("8/8/8/3k4/8/8/2K5/R7 b - - 0 1"),
+            -score("8/8/8/3k4/8/8/2K5/R7 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn test_scale_factors() {
+        assert_eq!(
+            probe("4k3/5p2/8/2b5/8/8/3PP3/4KB2 w - - 0 1"),
+            Some(EndgameEval::Scale([SCALE_OPPOSITE_BISHOPS; 2]))
+        );
+        assert_eq!(
+            probe("4k3/5p2/8/2b3n1/8/8/3PP3/4KBN1 w - - 0 1"),
+            Some(EndgameEval::Scale([SCALE_OPPOSITE_BISHOPS_WITH_PIECES; 2]))
+        );
+        assert_eq!(probe("4k3/5p2/8/8/2b5/8/3PP3/4KB2 w - - 0 1"), None);
+
+        // a dark bishop can't help promote on a8
+        assert_eq!(
+            probe("k7/8/8/8/8/8/P7/2B1K3 w - - 0 1"),
+            Some(EndgameEval::Scale([SCALE_DRAW, SCALE_DRAW]))
+        );
+        assert_eq!(
+            probe("k7/8/8/8/8/8/P7/3BK3 w - - 0 1"),
+            Some(EndgameEval::Scale([SCALE_NORMAL, SCALE_DRAW]))
+        );
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3BK3 b - - 0 1", &HASHER).unwrap();
+        let eval = probe_endgame(&board, &MOVE_GEN_MASKS).unwrap();
+        assert_eq!(eval.apply(-300, &board), 0);
+    }
+}
@@ -0,0 +1,177 @@
+// https://www.chessprogramming.org/King_Safety
+
+use crate::{
+    board::Board,
+    moves::{
+        geometry::{pawn_attacks, FILE_A},
+        move_mask_gen::MoveGenMasks,
+    },
+    types::{
+        bitboard::BitBoard,
+        piece::{Color, Pieces},
+        square::Square,
+    },
+};
+
+/// own pawn one and two ranks in front of the king
+const PAWN_SHIELD_BONUS: [i32; 2] = [14, 7];
+/// no own pawn on the king file or a file next to it
+const SEMI_OPEN_FILE_PENALTY: i32 = -14;
+/// no pawns at all on that file
+const OPEN_FILE_PENALTY: i32 = -10;
+/// per attacked king zone square, indexed by piece
+const ATTACK_WEIGHTS: [i32; 6] = [5, 3, 2, 2, 0, 0];
+/// percentage of the attack units that count, by number of attackers
+const ATTACKER_COUNT_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+const MAX_ATTACK_PENALTY: i32 = 500;
+/// a check the defender can't answer by taking the checking square, by piece
+const SAFE_CHECK_PENALTY: [i32; 6] = [30, 40, 20, 25, 0, 0];
+
+/// Every square attacked by `color`
+fn attacked_squares(board: &Board, color: usize, move_gen_masks: &MoveGenMasks) -> BitBoard {
+    let mut attacks = BitBoard::new(pawn_attacks(
+        board.pieces[color][Pieces::PAWN].as_u64(),
+        color,
+    ));
+    for piece in [
+        Pieces::QUEEN,
+        Pieces::ROOK,
+        Pieces::BISHOP,
+        Pieces::KNIGHT,
+        Pieces::KING,
+    ] {
+        for square in board.pieces[color][piece].get_ones() {
+            attacks |= move_gen_masks.piece_attacks(piece, &square, board.all_pieces);
+        }
+    }
+    attacks
+}
+
+/// (mg, eg) safety of `color`'s king, negative is bad for `color`
+fn king_safety(board: &Board, color: usize, move_gen_masks: &MoveGenMasks) -> (i32, i32) {
+    let opponent = 1 - color;
+    let king_square = board.pieces[color][Pieces::KING].get_one();
+    let king_file = king_square.get_file() as i8;
+    let king_rank = king_square.get_rank() as i8;
+    let forward = if color == Color::WHITE { 1 } else { -1 };
+    let mut mg = 0;
+
+    for file in (king_file - 1).max(0)..=(king_file + 1).min(7) {
+        let own_pawns = board.pieces[color][Pieces::PAWN];
+        let opponent_pawns = board.pieces[opponent][Pieces::PAWN];
+        let file_mask = BitBoard::new(FILE_A << file);
+
+        if (own_pawns & file_mask).is_empty() {
+            mg += SEMI_OPEN_FILE_PENALTY;
+            if (opponent_pawns & file_mask).is_empty() {
+                mg += OPEN_FILE_PENALTY;
+            }
+        }
+
+        for (distance, bonus) in PAWN_SHIELD_BONUS.iter().enumerate() {
+            let rank = king_rank + forward * (distance as i8 + 1);
+            if (0..8).contains(&rank)
+                && own_pawns.read_square(&Square::new((rank * 8 + file) as u8))
+            {
+                mg += bonus;
+            }
+        }
+    }
+
+    let occupancy = board.all_pieces;
+    let king_zone = move_gen_masks.king_moves[king_square.as_usize()]
+        | BitBoard::zeros_with_one_bit(&king_square);
+    let defended = attacked_squares(board, color, move_gen_masks);
+    let rook_checks = move_gen_masks.rook_moves(&king_square, occupancy);
+    let bishop_checks = move_gen_masks.bishop_moves(&king_square, occupancy);
+    let check_squares = [
+        rook_checks | bishop_checks,
+        rook_checks,
+        bishop_checks,
+        move_gen_masks.knight_moves[king_square.as_usize()],
+    ];
+
+    let mut n_attackers = 0;
+    let mut attack_units = 0;
+    let mut safe_checks = 0;
+    for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+        for square in board.pieces[opponent][piece].get_ones() {
+            let attacks = move_gen_masks.piece_attacks(piece, &square, occupancy);
+
+            let zone_attacks = (attacks & king_zone).as_u64().count_ones() as i32;
+            if zone_attacks > 0 {
+                n_attackers += 1;
+                attack_units += ATTACK_WEIGHTS[piece] * zone_attacks;
+            }
+
+            let safe = attacks & check_squares[piece] & !board.colors[opponent] & !defended;
+            if !safe.is_empty() {
+                safe_checks += SAFE_CHECK_PENALTY[piece];
+            }
+        }
+    }
+
+    let scale = ATTACKER_COUNT_SCALE[n_attackers.min(7)];
+    let attack_penalty = (attack_units * attack_units * scale / 200).min(MAX_ATTACK_PENALTY);
+    mg -= attack_penalty + safe_checks;
+
+    (mg, -safe_checks / 2)
+}
+
+/// Shelter and king attacks of both sides, for the side to move
+pub fn evaluate_king_safety(board: &Board, move_gen_masks: &MoveGenMasks) -> i32 {
+    let (white_mg, white_eg) = king_safety(board, Color::WHITE, move_gen_masks);
+    let (black_mg, black_eg) = king_safety(board, Color::BLACK, move_gen_masks);
+
+    board
+        .pesto
+        .taper(white_mg - black_mg, white_eg - black_eg, board.state.turn)
+}
+
+#[cfg(test)]
+mod test_king_safety {
+    use super::*;
+    use crate::{bots::test_utils::mirror_fen, utils::zobrist::ZobristHasher};
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn score(fen: &str) -> i32 {
+        let board = Board::from_fen(fen, &HASHER).unwrap();
+        evaluate_king_safety(&board, &MOVE_GEN_MASKS)
+    }
+
+    #[test]
+    fn test_king_safety_is_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bq1rk1/ppp2ppp/2n2n2/3pp3/1bPP4/2N1PN2/PP3PPP/R1BQKB1R w KQ - 0 1",
+            "6k1/5p1p/6pQ/8/8/8/5PPP/3R2K1 b - - 0 1",
+            "r4rk1/pp3p1p/2p3pQ/4N3/3P4/8/PP3PPP/R5K1 w - - 0 1",
+        ];
+        for fen in fens {
+            assert_eq!(score(fen), score(&mirror_fen(fen)), "{}", fen);
+        }
+        assert_eq!(score(fens[0]), 0);
+    }
+
+    #[test]
+    fn test_pawn_shield() {
+        let intact = score("r4rk1/ppp2ppp/8/8/8/8/PPP2PPP/R4RK1 b - - 0 1");
+        let pushed = score("r4rk1/ppp5/6pp/5p2/8/8/PPP2PPP/R4RK1 b - - 0 1");
+        let open = score("r4rk1/ppp5/8/8/8/8/PPP2PPP/R4RK1 b - - 0 1");
+
+        assert!(intact > pushed);
+        assert!(pushed > open);
+    }
+
+    #[test]
+    fn test_king_attack() {
+        // black to move, white queen and knight swarm the king
+        let quiet = score("r4rk1/ppp2ppp/8/8/8/8/PPPQ1PPP/R3NRK1 b - - 0 1");
+        let attacked = score("r4rk1/ppp2p1p/6pQ/6N1/8/8/PPP2PPP/R4RK1 b - - 0 1");
+
+        assert!(quiet - attacked > 50, "{} {}", quiet, attacked);
+    }
+}
@@ -0,0 +1,127 @@
+// https://www.chessprogramming.org/Mobility
+
+use crate::{
+    board::Board,
+    moves::{geometry::pawn_attacks, move_mask_gen::MoveGenMasks},
+    types::{
+        bitboard::BitBoard,
+        piece::{Color, Pieces},
+    },
+};
+
+/// (mg, eg) by number of reachable squares
+#[rustfmt::skip]
+const KNIGHT_MOBILITY: [(i32, i32); 9] = [
+    (-31, -40), (-26, -28), (-6, -15), (-2, -7), (1, 4), (6, 7), (11, 11), (14, 13), (16, 16),
+];
+#[rustfmt::skip]
+const BISHOP_MOBILITY: [(i32, i32); 14] = [
+    (-24, -29), (-10, -11), (8, -1), (13, 6), (19, 12), (25, 21), (27, 27), (31, 28), (31, 32),
+    (34, 36), (40, 39), (40, 43), (45, 44), (49, 48),
+];
+#[rustfmt::skip]
+const ROOK_MOBILITY: [(i32, i32); 15] = [
+    (-30, -39), (-10, -8), (1, 11), (1, 19), (1, 35), (5, 49), (11, 51), (15, 60), (20, 67),
+    (20, 69), (20, 79), (24, 82), (28, 84), (28, 84), (31, 86),
+];
+#[rustfmt::skip]
+const QUEEN_MOBILITY: [(i32, i32); 28] = [
+    (-15, -24), (-6, -15), (-4, -3), (-4, 9), (10, 20), (11, 27), (11, 29), (17, 37), (19, 39),
+    (26, 48), (32, 48), (32, 50), (32, 60), (33, 63), (33, 65), (33, 66), (36, 68), (36, 70),
+    (38, 73), (39, 75), (46, 75), (54, 84), (54, 84), (54, 85), (55, 91), (57, 91), (57, 96),
+    (58, 109),
+];
+
+fn mobility_bonus(piece: usize, n_squares: usize) -> (i32, i32) {
+    match piece {
+        Pieces::QUEEN => QUEEN_MOBILITY[n_squares],
+        Pieces::ROOK => ROOK_MOBILITY[n_squares],
+        Pieces::BISHOP => BISHOP_MOBILITY[n_squares],
+        Pieces::KNIGHT => KNIGHT_MOBILITY[n_squares],
+        _ => (0, 0),
+    }
+}
+
+/// (mg, eg) mobility of `color`'s pieces. Squares with own pieces or
+/// attacked by enemy pawns don't count.
+fn mobility(board: &Board, color: usize, move_gen_masks: &MoveGenMasks) -> (i32, i32) {
+    let opponent = 1 - color;
+    let enemy_pawn_attacks = BitBoard::new(pawn_attacks(
+        board.pieces[opponent][Pieces::PAWN].as_u64(),
+        opponent,
+    ));
+    let available = !(board.colors[color] | enemy_pawn_attacks);
+    let mut mg = 0;
+    let mut eg = 0;
+
+    for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+        for square in board.pieces[color][piece].get_ones() {
+            let attacks = move_gen_masks.piece_attacks(piece, &square, board.all_pieces);
+            let n_squares = (attacks & available).as_u64().count_ones() as usize;
+            let (piece_mg, piece_eg) = mobility_bonus(piece, n_squares);
+            mg += piece_mg;
+            eg += piece_eg;
+        }
+    }
+
+    (mg, eg)
+}
+
+/// Reachable squares of both sides' pieces, for the side to move
+pub fn evaluate_mobility(board: &Board, move_gen_masks: &MoveGenMasks) -> i32 {
+    let (white_mg, white_eg) = mobility(board, Color::WHITE, move_gen_masks);
+    let (black_mg, black_eg) = mobility(board, Color::BLACK, move_gen_masks);
+
+    board
+        .pesto
+        .taper(white_mg - black_mg, white_eg - black_eg, board.state.turn)
+}
+
+#[cfg(test)]
+mod test_mobility {
+    use super::*;
+    use crate::{bots::test_utils::mirror_fen, utils::zobrist::ZobristHasher};
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn score(fen: &str) -> i32 {
+        let board = Board::from_fen(fen, &HASHER).unwrap();
+        evaluate_mobility(&board, &MOVE_GEN_MASKS)
+    }
+
+    #[test]
+    fn test_mobility_is_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pp3ppp/2np1n2/4p3/2P1P3/2N2N2/PP3PPP/R1BQKB1R b KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ];
+        for fen in fens {
+            assert_eq!(score(fen), score(&mirror_fen(fen)), "{}", fen);
+        }
+        assert_eq!(score(fens[0]), 0);
+    }
+
+    #[test]
+    fn test_mobility_counts() {
+        let board = Board::from_fen("4k3/8/8/3p4/8/8/8/N3K3 w - - 0 1", &HASHER).unwrap();
+        // a1 knight reaches b3 and c2
+        assert_eq!(
+            mobility(&board, Color::WHITE, &MOVE_GEN_MASKS),
+            KNIGHT_MOBILITY[2]
+        );
+
+        // d5 attacks c4 and e4, leaving the d4 rook 9 of its 11 squares
+        let board = Board::from_fen("4k3/8/8/3p4/3R4/8/8/4K3 w - - 0 1", &HASHER).unwrap();
+        assert_eq!(
+            mobility(&board, Color::WHITE, &MOVE_GEN_MASKS),
+            ROOK_MOBILITY[9]
+        );
+
+        let centralized = score("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let cornered = score("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        assert!(centralized > cornered);
+    }
+}
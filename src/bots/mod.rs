@@ -1,3 +1,10 @@
 pub mod bot;
-mod pesto;
+pub mod endgame;
+pub mod king_safety;
+pub mod mobility;
+pub mod nnue;
+pub mod pawns;
+pub mod pesto;
+#[cfg(test)]
+mod test_utils;
 pub mod time_control;
@@ -0,0 +1,368 @@
+// https://www.chessprogramming.org/NNUE
+
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::types::{bitboard::BitBoard, piece::Color, square::Square};
+
+const MAGIC: &[u8; 4] = b"RCNN";
+const INPUT_SIZE: usize = 768;
+/// feature transformer clamp
+const QA: i32 = 255;
+/// output layer quantisation
+const QB: i32 = 64;
+const EVAL_SCALE: i32 = 400;
+
+#[derive(Debug)]
+pub enum NnueError {
+    Io(io::Error),
+    InvalidFormat(String),
+}
+
+impl fmt::Display for NnueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NnueError::Io(e) => write!(f, "Error reading network: {}", e),
+            NnueError::InvalidFormat(e) => write!(f, "Invalid network: {}", e),
+        }
+    }
+}
+
+impl Error for NnueError {}
+
+impl From<io::Error> for NnueError {
+    fn from(e: io::Error) -> Self {
+        NnueError::Io(e)
+    }
+}
+
+/// (768 -> N) x 2 -> 1 network.
+///
+/// File layout, little endian: `RCNN`, u32 N, i16 feature weights [768][N],
+/// i16 feature biases [N], i16 output weights [2N] (side to move first),
+/// i16 output bias. Weights are quantised by 255 in the feature transformer
+/// and 64 in the output layer.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn new(
+        feature_weights: Vec<i16>,
+        feature_biases: Vec<i16>,
+        output_weights: Vec<i16>,
+        output_bias: i16,
+    ) -> Result<Self, NnueError> {
+        let hidden_size = feature_biases.len();
+        if hidden_size == 0
+            || feature_weights.len() != INPUT_SIZE * hidden_size
+            || output_weights.len() != 2 * hidden_size
+        {
+            return Err(NnueError::InvalidFormat(format!(
+                "layer sizes {} {} {} don't match",
+                feature_weights.len(),
+                feature_biases.len(),
+                output_weights.len()
+            )));
+        }
+        Ok(Self {
+            hidden_size,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, NnueError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, NnueError> {
+        let mut header = [0; 8];
+        bytes.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(NnueError::InvalidFormat("missing RCNN header".to_owned()));
+        }
+        let hidden_size = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+
+        let expected = (INPUT_SIZE + 3) * hidden_size + 1;
+        if bytes.len() != 2 * expected {
+            return Err(NnueError::InvalidFormat(format!(
+                "expected {} bytes after the header, found {}",
+                2 * expected,
+                bytes.len()
+            )));
+        }
+        let mut values = bytes
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut take = |n: usize| values.by_ref().take(n).collect::<Vec<i16>>();
+
+        let feature_weights = take(INPUT_SIZE * hidden_size);
+        let feature_biases = take(hidden_size);
+        let output_weights = take(2 * hidden_size);
+        let output_bias = take(1)[0];
+        Self::new(feature_weights, feature_biases, output_weights, output_bias)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((self.hidden_size as u32).to_le_bytes());
+        for value in self
+            .feature_weights
+            .iter()
+            .chain(&self.feature_biases)
+            .chain(&self.output_weights)
+            .chain([&self.output_bias])
+        {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    /// Input index seen from `perspective`, own pieces first and own back rank on rank 1
+    fn feature_index(perspective: usize, color: usize, piece: usize, square: &Square) -> usize {
+        let (relative_color, relative_square) = if perspective == Color::WHITE {
+            (color, square.as_usize())
+        } else {
+            (1 - color, square.as_usize() ^ 56)
+        };
+        relative_color * 384 + piece * 64 + relative_square
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+
+    fn add_feature(&self, values: &mut [i16], feature: usize) {
+        for (value, weight) in values.iter_mut().zip(self.feature_weights(feature)) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    fn remove_feature(&self, values: &mut [i16], feature: usize) {
+        for (value, weight) in values.iter_mut().zip(self.feature_weights(feature)) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    /// Accumulator built from scratch
+    pub fn accumulator(&self, pieces: &[[BitBoard; 6]; 2]) -> Accumulator {
+        let mut accumulator = Accumulator {
+            values: [self.feature_biases.clone(), self.feature_biases.clone()],
+        };
+        for (color, color_pieces) in pieces.iter().enumerate() {
+            for (piece, piece_board) in color_pieces.iter().enumerate() {
+                for square in piece_board.get_ones() {
+                    for perspective in [Color::WHITE, Color::BLACK] {
+                        let feature = Self::feature_index(perspective, color, piece, &square);
+                        self.add_feature(&mut accumulator.values[perspective], feature);
+                    }
+                }
+            }
+        }
+        accumulator
+    }
+
+    /// Score in centipawns from `turn`'s point of view
+    pub fn evaluate(&self, accumulator: &Accumulator, turn: usize) -> i32 {
+        let (us_weights, them_weights) = self.output_weights.split_at(self.hidden_size);
+        let mut output = 0;
+        for (values, weights) in [
+            (&accumulator.values[turn], us_weights),
+            (&accumulator.values[1 - turn], them_weights),
+        ] {
+            output += values
+                .iter()
+                .zip(weights)
+                .map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32)
+                .sum::<i32>();
+        }
+        (output + self.output_bias as i32 * QA) * EVAL_SCALE / (QA * QB)
+    }
+}
+
+/// Feature transformer output for both perspectives
+#[derive(Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    pub values: [Vec<i16>; 2],
+}
+
+/// (color, piece, square) of a network input
+pub type Feature = (usize, usize, Square);
+
+/// Accumulators of the current line, one per ply, updated by
+/// `Board::make_move` / `unmake_move`
+#[derive(Clone)]
+pub struct NnueState {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    top: usize,
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>, pieces: &[[BitBoard; 6]; 2]) -> Self {
+        let root = network.accumulator(pieces);
+        Self {
+            network,
+            stack: vec![root],
+            top: 0,
+        }
+    }
+
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.stack[self.top]
+    }
+
+    /// Pushes the accumulator of the position after a move, updated with the
+    /// (color, piece, square) features the move removed and added
+    pub fn push(&mut self, removed: &[Feature], added: &[Feature]) {
+        if self.top + 1 == self.stack.len() {
+            self.stack.push(self.stack[self.top].clone());
+        } else {
+            let (previous, next) = self.stack.split_at_mut(self.top + 1);
+            next[0].clone_from(&previous[self.top]);
+        }
+        self.top += 1;
+
+        let accumulator = &mut self.stack[self.top];
+        for perspective in [Color::WHITE, Color::BLACK] {
+            let values = &mut accumulator.values[perspective];
+            for (color, piece, square) in removed {
+                let feature = Network::feature_index(perspective, *color, *piece, square);
+                self.network.remove_feature(values, feature);
+            }
+            for (color, piece, square) in added {
+                let feature = Network::feature_index(perspective, *color, *piece, square);
+                self.network.add_feature(values, feature);
+            }
+        }
+    }
+
+    pub fn pop(&mut self) {
+        debug_assert!(self.top > 0, "popped the root accumulator");
+        self.top -= 1;
+    }
+
+    pub fn evaluate(&self, turn: usize) -> i32 {
+        self.network.evaluate(self.accumulator(), turn)
+    }
+}
+
+#[cfg(test)]
+mod test_nnue {
+    use super::*;
+    use crate::{
+        board::Board, bots::test_utils::mirror_fen, moves::move_mask_gen::MoveGenMasks,
+        utils::zobrist::ZobristHasher,
+    };
+    use once_cell::sync::Lazy;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    /// Deterministic random network
+    pub fn test_network(hidden_size: usize, seed: u64) -> Network {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut random = |n: usize, range: i16| -> Vec<i16> {
+            (0..n).map(|_| rng.random_range(-range..=range)).collect()
+        };
+        Network::new(
+            random(INPUT_SIZE * hidden_size, 40),
+            random(hidden_size, 60),
+            random(2 * hidden_size, 50),
+            random(1, 50)[0],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_network_bytes_round_trip() {
+        let network = test_network(16, 1);
+        let bytes = network.to_bytes();
+        assert!(Network::from_bytes(&bytes).unwrap() == network);
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut wrong_header = bytes.clone();
+        wrong_header[0] = b'X';
+        assert!(Network::from_bytes(&wrong_header).is_err());
+    }
+
+    #[test]
+    fn test_nnue_is_symmetric() {
+        let network = Arc::new(test_network(32, 2));
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+        ];
+        for fen in fens {
+            let mut board = Board::from_fen(fen, &HASHER).unwrap();
+            let mut mirrored = Board::from_fen(&mirror_fen(fen), &HASHER).unwrap();
+            board.enable_nnue(network.clone());
+            mirrored.enable_nnue(network.clone());
+            let score = board.nnue.as_ref().unwrap().evaluate(board.state.turn);
+            let mirrored_score = mirrored
+                .nnue
+                .as_ref()
+                .unwrap()
+                .evaluate(mirrored.state.turn);
+            assert_eq!(score, mirrored_score, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_nnue_incremental_random_games() {
+        let network = Arc::new(test_network(32, 3));
+        let mut rng = StdRng::seed_from_u64(0x22e);
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        ];
+
+        for fen in fens {
+            for _ in 0..5 {
+                let mut board = Board::from_fen(fen, &HASHER).unwrap();
+                board.enable_nnue(network.clone());
+                for _ in 0..100 {
+                    let legal_moves = board.get_legal_moves(&MOVE_GEN_MASKS, &HASHER);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    let before = board.nnue.as_ref().unwrap().accumulator().clone();
+
+                    let helper = board.make_move(&the_move, &HASHER);
+                    let nnue = board.nnue.as_ref().unwrap();
+                    assert!(*nnue.accumulator() == network.accumulator(&board.pieces));
+
+                    board.unmake_move(helper, &HASHER);
+                    assert!(*board.nnue.as_ref().unwrap().accumulator() == before);
+                    board.make_move(&the_move, &HASHER);
+                }
+            }
+        }
+    }
+}
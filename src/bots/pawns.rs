@@ -0,0 +1,398 @@
+// https://www.chessprogramming.org/Pawn_Structure
+
+use crate::{
+    board::Board,
+    moves::{
+        geometry::{pawn_attacks, FILE_A, FILE_H},
+        move_mask_gen::MoveGenMasks,
+    },
+    types::{
+        bitboard::BitBoard,
+        piece::{Color, Pieces},
+        square::Square,
+    },
+    utils::zobrist::ZobristHash,
+};
+
+/// (mg, eg) by rank from the pawn's own side
+const PASSED_PAWN_BONUS: [(i32, i32); 8] = [
+    (0, 0),
+    (2, 8),
+    (5, 12),
+    (12, 25),
+    (25, 50),
+    (45, 90),
+    (70, 140),
+    (0, 0),
+];
+const CONNECTED_PAWN_BONUS: [(i32, i32); 8] = [
+    (0, 0),
+    (3, 2),
+    (5, 4),
+    (8, 6),
+    (14, 12),
+    (24, 25),
+    (40, 45),
+    (0, 0),
+];
+const ISOLATED_PAWN_PENALTY: (i32, i32) = (-6, -14);
+const DOUBLED_PAWN_PENALTY: (i32, i32) = (-10, -22);
+const BACKWARD_PAWN_PENALTY: (i32, i32) = (-8, -10);
+/// eg only, the square in front of a passed pawn is taken
+const BLOCKED_PASSED_PAWN_FACTOR: i32 = 2;
+/// the enemy king can't catch the pawn and has nothing else to stop it with
+const UNSTOPPABLE_PAWN_BONUS: i32 = 300;
+/// slots in `PawnHashTable`, a power of two
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+const fn file_masks() -> [u64; 8] {
+    let mut masks = [0; 8];
+    let mut file = 0;
+    while file < 8 {
+        masks[file] = FILE_A << file;
+        file += 1;
+    }
+    masks
+}
+
+const fn adjacent_file_masks() -> [u64; 8] {
+    let mut masks = [0; 8];
+    let mut file = 0;
+    while file < 8 {
+        if file > 0 {
+            masks[file] |= FILE_A << (file - 1);
+        }
+        if file < 7 {
+            masks[file] |= FILE_A << (file + 1);
+        }
+        file += 1;
+    }
+    masks
+}
+
+/// squares strictly in front of every square, [color][square]
+const fn forward_masks() -> [[u64; 64]; 2] {
+    let mut masks = [[0; 64]; 2];
+    let mut square = 0;
+    while square < 64 {
+        let file_mask = FILE_A << (square % 8);
+        let rank = square / 8;
+        let above = if rank == 7 {
+            0
+        } else {
+            u64::MAX << ((rank + 1) * 8)
+        };
+        let below = if rank == 0 {
+            0
+        } else {
+            u64::MAX >> ((8 - rank) * 8)
+        };
+        masks[Color::WHITE][square] = file_mask & above;
+        masks[Color::BLACK][square] = file_mask & below;
+        square += 1;
+    }
+    masks
+}
+
+static FILE_MASKS: [u64; 8] = file_masks();
+static ADJACENT_FILE_MASKS: [u64; 8] = adjacent_file_masks();
+static FORWARD_MASKS: [[u64; 64]; 2] = forward_masks();
+
+/// Squares in front on its own and the adjacent files
+fn passed_pawn_mask(square: usize, color: usize) -> u64 {
+    let file = square % 8;
+    forward_rows(square, color) & (FILE_MASKS[file] | ADJACENT_FILE_MASKS[file])
+}
+
+/// every square on a rank in front of `square`
+fn forward_rows(square: usize, color: usize) -> u64 {
+    let rank = square / 8;
+    if color == Color::WHITE {
+        if rank == 7 {
+            0
+        } else {
+            u64::MAX << ((rank + 1) * 8)
+        }
+    } else if rank == 0 {
+        0
+    } else {
+        u64::MAX >> ((8 - rank) * 8)
+    }
+}
+
+fn relative_rank(square: usize, color: usize) -> usize {
+    if color == Color::WHITE {
+        square / 8
+    } else {
+        7 - square / 8
+    }
+}
+
+/// Pawn only part of the evaluation, white minus black
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PawnEntry {
+    pub mg: i32,
+    pub eg: i32,
+    pub passed: [BitBoard; 2],
+}
+
+impl PawnEntry {
+    pub fn from_board(board: &Board) -> Self {
+        let pawns = [
+            board.pieces[Color::WHITE][Pieces::PAWN].as_u64(),
+            board.pieces[Color::BLACK][Pieces::PAWN].as_u64(),
+        ];
+        let mut entry = Self {
+            mg: 0,
+            eg: 0,
+            passed: [BitBoard::zeros(); 2],
+        };
+
+        for color in [Color::WHITE, Color::BLACK] {
+            let opponent = 1 - color;
+            let own_pawns = pawns[color];
+            let opponent_pawns = pawns[opponent];
+            let own_attacks = pawn_attacks(own_pawns, color);
+            let opponent_attacks = pawn_attacks(opponent_pawns, opponent);
+            let sign = if color == Color::WHITE { 1 } else { -1 };
+            let mut mg = 0;
+            let mut eg = 0;
+
+            for square in BitBoard::new(own_pawns).get_ones() {
+                let i = square.as_usize();
+                let file = i % 8;
+                let bit = 1_u64 << i;
+                let rank = relative_rank(i, color);
+
+                if own_pawns & ADJACENT_FILE_MASKS[file] == 0 {
+                    mg += ISOLATED_PAWN_PENALTY.0;
+                    eg += ISOLATED_PAWN_PENALTY.1;
+                }
+
+                // only the rearmost pawn of a file counts as doubled
+                if own_pawns & FORWARD_MASKS[color][i] != 0 {
+                    mg += DOUBLED_PAWN_PENALTY.0;
+                    eg += DOUBLED_PAWN_PENALTY.1;
+                }
+
+                let phalanx = own_pawns & ((bit << 1) & !FILE_A | (bit >> 1) & !FILE_H);
+                let supported = own_attacks & bit;
+                if phalanx | supported != 0 {
+                    mg += CONNECTED_PAWN_BONUS[rank].0;
+                    eg += CONNECTED_PAWN_BONUS[rank].1;
+                }
+
+                // can't be defended by a pawn and can't safely push
+                let stop_square = if color == Color::WHITE {
+                    bit << 8
+                } else {
+                    bit >> 8
+                };
+                let behind_or_level = !forward_rows(i, color);
+                let possible_supporters = own_pawns & ADJACENT_FILE_MASKS[file] & behind_or_level;
+                if possible_supporters == 0
+                    && own_pawns & ADJACENT_FILE_MASKS[file] != 0
+                    && opponent_attacks & stop_square != 0
+                {
+                    mg += BACKWARD_PAWN_PENALTY.0;
+                    eg += BACKWARD_PAWN_PENALTY.1;
+                }
+
+                if opponent_pawns & passed_pawn_mask(i, color) == 0
+                    && own_pawns & FORWARD_MASKS[color][i] == 0
+                {
+                    entry.passed[color].set_one(&square);
+                    mg += PASSED_PAWN_BONUS[rank].0;
+                    eg += PASSED_PAWN_BONUS[rank].1;
+                }
+            }
+
+            entry.mg += sign * mg;
+            entry.eg += sign * eg;
+        }
+
+        entry
+    }
+}
+
+/// Caches `PawnEntry` by the pawn only zobrist key, a new entry always replaces
+/// the one in its slot
+pub struct PawnHashTable {
+    entries: Vec<Option<(ZobristHash, PawnEntry)>>,
+}
+
+impl PawnHashTable {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![None; PAWN_TABLE_SIZE],
+        }
+    }
+
+    fn index(&self, key: ZobristHash) -> usize {
+        key.get_value() as usize & (PAWN_TABLE_SIZE - 1)
+    }
+
+    pub fn probe(&mut self, board: &Board) -> PawnEntry {
+        let index = self.index(board.pawn_key);
+        match self.entries[index] {
+            Some((key, entry)) if key == board.pawn_key => entry,
+            _ => {
+                let entry = PawnEntry::from_board(board);
+                self.entries[index] = Some((board.pawn_key, entry));
+                entry
+            }
+        }
+    }
+}
+
+impl Default for PawnHashTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pawn structure score from the side to move's point of view
+pub fn evaluate_pawns(
+    board: &Board,
+    pawn_table: &mut PawnHashTable,
+    move_gen_masks: &MoveGenMasks,
+) -> i32 {
+    let entry = pawn_table.probe(board);
+    let mg = entry.mg;
+    let mut eg = entry.eg;
+
+    for color in [Color::WHITE, Color::BLACK] {
+        let opponent = 1 - color;
+        let sign = if color == Color::WHITE { 1 } else { -1 };
+        // pawns and king only, PeSTO game phase counts every other piece
+        let opponent_has_pieces = board.pesto.game_phase[opponent] > 0;
+        let opponent_king = board.pieces[opponent][Pieces::KING].get_one();
+
+        for square in entry.passed[color].get_ones() {
+            let i = square.as_usize();
+            let rank = relative_rank(i, color);
+            let stop_square = if color == Color::WHITE { i + 8 } else { i - 8 };
+
+            if board
+                .all_pieces
+                .read_square(&Square::new(stop_square as u8))
+            {
+                eg -= sign * PASSED_PAWN_BONUS[rank].1 / BLOCKED_PASSED_PAWN_FACTOR;
+            }
+
+            if !opponent_has_pieces {
+                let promotion_square = Square::new(if color == Color::WHITE {
+                    56 + i % 8
+                } else {
+                    i % 8
+                } as u8);
+                // a pawn on its starting rank can double push
+                let pawn_distance = (7 - rank).min(5) as i32;
+                let tempo = (board.state.turn == opponent) as i32;
+                let king_distance = move_gen_masks
+                    .geometry
+                    .distance(&opponent_king, &promotion_square)
+                    as i32
+                    - tempo;
+                if king_distance > pawn_distance {
+                    eg += sign * UNSTOPPABLE_PAWN_BONUS;
+                }
+            }
+        }
+    }
+
+    board.pesto.taper(mg, eg, board.state.turn)
+}
+
+#[cfg(test)]
+mod test_pawns {
+    use super::*;
+    use crate::bots::test_utils::mirror_fen;
+    use crate::utils::zobrist::ZobristHasher;
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    #[test]
+    fn test_pawn_structure_terms() {
+        // c4 passed and supported, d3 backward, e5 isolated
+        let board = Board::from_fen("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1", &HASHER).unwrap();
+        let entry = PawnEntry::from_board(&board);
+
+        assert_eq!(entry.mg, 8 + 12 - 8 + 6);
+        assert_eq!(entry.eg, 6 + 25 - 10 + 14);
+        assert_eq!(entry.passed[Color::WHITE].get_ones(), vec![Square::new(26)]);
+        assert!(entry.passed[Color::BLACK].is_empty());
+
+        // doubled and isolated on the a file, h7 is passed
+        let board = Board::from_fen("4k3/7p/8/8/8/P7/P7/4K3 w - - 0 1", &HASHER).unwrap();
+        let entry = PawnEntry::from_board(&board);
+        assert_eq!(entry.mg, 2 * -6 - 10 + 5 - (-6 + 2));
+        assert_eq!(entry.passed[Color::WHITE].get_ones(), vec![Square::new(16)]);
+        assert_eq!(entry.passed[Color::BLACK].get_ones(), vec![Square::new(55)]);
+    }
+
+    #[test]
+    fn test_pawn_evaluation_is_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1",
+            "r1bqkb1r/pp3ppp/2np1n2/4p3/2P1P3/2N2N2/PP3PPP/R1BQKB1R b KQkq - 0 1",
+            "8/5k2/6p1/1p5p/1P3P1P/6P1/8/3K4 w - - 0 1",
+            "8/8/8/P7/8/8/8/k6K b - - 0 1",
+        ];
+        let mut pawn_table = PawnHashTable::new();
+
+        for fen in fens {
+            let board = Board::from_fen(fen, &HASHER).unwrap();
+            let mirrored = Board::from_fen(&mirror_fen(fen), &HASHER).unwrap();
+            assert_eq!(
+                evaluate_pawns(&board, &mut pawn_table, &MOVE_GEN_MASKS),
+                evaluate_pawns(&mirrored, &mut pawn_table, &MOVE_GEN_MASKS),
+                "{}",
+                fen
+            );
+        }
+        assert_eq!(
+            evaluate_pawns(&Board::new(&HASHER), &mut pawn_table, &MOVE_GEN_MASKS),
+            0
+        );
+    }
+
+    #[test]
+    fn test_unstoppable_passed_pawn() {
+        let mut pawn_table = PawnHashTable::new();
+        let outside = Board::from_fen("7k/8/8/P7/8/8/8/7K w - - 0 1", &HASHER).unwrap();
+        let inside = Board::from_fen("1k6/8/8/P7/8/8/8/7K w - - 0 1", &HASHER).unwrap();
+        // with black to move the king steps into the square in time
+        let tempo = Board::from_fen("4k3/8/8/P7/8/8/8/7K b - - 0 1", &HASHER).unwrap();
+        let no_tempo = Board::from_fen("4k3/8/8/P7/8/8/8/7K w - - 0 1", &HASHER).unwrap();
+
+        let outside_score = evaluate_pawns(&outside, &mut pawn_table, &MOVE_GEN_MASKS);
+        let inside_score = evaluate_pawns(&inside, &mut pawn_table, &MOVE_GEN_MASKS);
+        assert!(outside_score - inside_score >= UNSTOPPABLE_PAWN_BONUS);
+        assert!(
+            evaluate_pawns(&no_tempo, &mut pawn_table, &MOVE_GEN_MASKS)
+                + evaluate_pawns(&tempo, &mut pawn_table, &MOVE_GEN_MASKS)
+                >= UNSTOPPABLE_PAWN_BONUS
+        );
+
+        // all four positions share the same pawns
+        assert_eq!(pawn_table.entries.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn test_pawn_table_replaces_colliding_entries() {
+        let mut pawn_table = PawnHashTable::new();
+        let first = Board::from_fen("4k3/pp6/8/8/8/8/P7/4K3 w - - 0 1", &HASHER).unwrap();
+        let mut second = Board::from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1", &HASHER).unwrap();
+        // same slot, different key
+        second.pawn_key = ZobristHash(first.pawn_key.get_value() ^ PAWN_TABLE_SIZE as u64);
+
+        for board in [&first, &second, &first] {
+            assert!(pawn_table.probe(board) == PawnEntry::from_board(board));
+        }
+        assert_eq!(pawn_table.entries.iter().flatten().count(), 1);
+    }
+}
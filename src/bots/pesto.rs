@@ -2,7 +2,7 @@
 
 use crate::{
     board::Board,
-    types::{piece::Pieces, square::Square},
+    types::{piece::Color, square::Square},
 };
 
 static PESTO_GAME_PHASE_INC: [i32; 6] = [4, 2, 1, 1, 0, 0];
@@ -154,54 +154,201 @@ static PESTO_TABLE_EG_KING: [i32; 64] = [
     -53, -34, -21, -11, -28, -14, -24, -43
 ];
 
-pub struct PeSTO {
-    game_phase_inc: [i32; 6],
-    mg_table: [[[i32; 64]; 6]; 2],
-    eg_table: [[[i32; 64]; 6]; 2],
+const fn build_table(values: [i32; 6], tables: [[i32; 64]; 6]) -> [[[i32; 64]; 6]; 2] {
+    let mut table = [[[0; 64]; 6]; 2];
+    let mut piece = 0;
+    while piece < 6 {
+        let mut bit = 0;
+        while bit < 64 {
+            table[0][piece][bit] = values[piece] + tables[piece][bit ^ 56];
+            table[1][piece][bit] = values[piece] + tables[piece][bit];
+            bit += 1;
+        }
+        piece += 1;
+    }
+    table
 }
 
-impl PeSTO {
-    pub fn default() -> Self {
-        let mut mg_table: [[[i32; 64]; 6]; 2] = [[[0; 64]; 6]; 2];
-        let mut eg_table: [[[i32; 64]; 6]; 2] = [[[0; 64]; 6]; 2];
-        for piece in 0..=5_usize {
-            let tables = match piece {
-                Pieces::QUEEN => [PESTO_TABLE_EG_QUEEN, PESTO_TABLE_MG_QUEEN],
-                Pieces::ROOK => [PESTO_TABLE_EG_ROOK, PESTO_TABLE_MG_ROOK],
-                Pieces::BISHOP => [PESTO_TABLE_EG_BISHOP, PESTO_TABLE_MG_BISHOP],
-                Pieces::KNIGHT => [PESTO_TABLE_EG_KNIGHT, PESTO_TABLE_MG_KNIGHT],
-                Pieces::PAWN => [PESTO_TABLE_EG_PAWN, PESTO_TABLE_MG_PAWN],
-                Pieces::KING => [PESTO_TABLE_EG_KING, PESTO_TABLE_MG_KING],
-                _ => panic!("boom"),
-            };
-            for bit in 0..64 {
-                let other_bit = bit ^ 56;
-
-                eg_table[0][piece][bit] = PESTO_EG_VALUE[piece] + tables[0][other_bit];
-                mg_table[0][piece][bit] = PESTO_MG_VALUE[piece] + tables[1][other_bit];
-
-                eg_table[1][piece][bit] = PESTO_EG_VALUE[piece] + tables[0][bit];
-                mg_table[1][piece][bit] = PESTO_MG_VALUE[piece] + tables[1][bit];
+/// tables in piece order, a8 first
+static PESTO_MG_TABLES: [[i32; 64]; 6] = [
+    PESTO_TABLE_MG_QUEEN,
+    PESTO_TABLE_MG_ROOK,
+    PESTO_TABLE_MG_BISHOP,
+    PESTO_TABLE_MG_KNIGHT,
+    PESTO_TABLE_MG_PAWN,
+    PESTO_TABLE_MG_KING,
+];
+static PESTO_EG_TABLES: [[i32; 64]; 6] = [
+    PESTO_TABLE_EG_QUEEN,
+    PESTO_TABLE_EG_ROOK,
+    PESTO_TABLE_EG_BISHOP,
+    PESTO_TABLE_EG_KNIGHT,
+    PESTO_TABLE_EG_PAWN,
+    PESTO_TABLE_EG_KING,
+];
+
+/// [color][piece][square], piece values included
+static PESTO_MG_TABLE: [[[i32; 64]; 6]; 2] = build_table(PESTO_MG_VALUE, PESTO_MG_TABLES);
+static PESTO_EG_TABLE: [[[i32; 64]; 6]; 2] = build_table(PESTO_EG_VALUE, PESTO_EG_TABLES);
+
+/// Weights per phase, 6 piece values followed by the 6 tables
+const PESTO_PHASE_WEIGHTS: usize = 6 + 6 * 64;
+/// Mg weights followed by eg weights
+pub const PESTO_WEIGHT_COUNT: usize = 2 * PESTO_PHASE_WEIGHTS;
+
+/// phase 0 is mg, 1 is eg
+pub fn pesto_value_index(phase: usize, piece: usize) -> usize {
+    phase * PESTO_PHASE_WEIGHTS + piece
+}
+
+/// `square` is a table index, a8 first like the tables above
+pub fn pesto_table_index(phase: usize, piece: usize, square: usize) -> usize {
+    phase * PESTO_PHASE_WEIGHTS + 6 + piece * 64 + square
+}
+
+/// The hard coded values and tables as one weight vector
+pub fn pesto_weights() -> Vec<i32> {
+    let mut weights = vec![0; PESTO_WEIGHT_COUNT];
+    for (phase, (values, tables)) in [
+        (PESTO_MG_VALUE, PESTO_MG_TABLES),
+        (PESTO_EG_VALUE, PESTO_EG_TABLES),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        for piece in 0..6 {
+            weights[pesto_value_index(phase, piece)] = values[piece];
+            for square in 0..64 {
+                weights[pesto_table_index(phase, piece, square)] = tables[piece][square];
+            }
+        }
+    }
+    weights
+}
+
+/// Linear features of the PeSTO score, white's point of view.
+/// The score is the sum of weight * coefficient over the returned pairs.
+pub fn pesto_features(board: &Board) -> Vec<(usize, f64)> {
+    let game_phase = (board.pesto.game_phase[0] + board.pesto.game_phase[1]).min(24);
+    let phase_factors = [game_phase as f64 / 24.0, (24 - game_phase) as f64 / 24.0];
+    let mut features = Vec::new();
+
+    for (color, pieces) in board.pieces.iter().enumerate() {
+        let sign = if color == Color::WHITE { 1.0 } else { -1.0 };
+        for (piece, piece_board) in pieces.iter().enumerate() {
+            for square in piece_board.get_ones() {
+                let table_square = if color == Color::WHITE {
+                    square.as_usize() ^ 56
+                } else {
+                    square.as_usize()
+                };
+                for (phase, factor) in phase_factors.iter().enumerate() {
+                    features.push((pesto_value_index(phase, piece), sign * factor));
+                    features.push((pesto_table_index(phase, piece, table_square), sign * factor));
+                }
+            }
+        }
+    }
+    features
+}
+
+/// PeSTO sums per color, kept up to date by `Board::make_move` / `unmake_move`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PestoScore {
+    pub mg: [i32; 2],
+    pub eg: [i32; 2],
+    pub game_phase: [i32; 2],
+}
+
+impl PestoScore {
+    pub fn from_board(board: &Board) -> Self {
+        let mut pesto_score = Self::default();
+        for (color, pieces) in board.pieces.iter().enumerate() {
+            for (piece, piece_board) in pieces.iter().enumerate() {
+                for square in piece_board.get_ones() {
+                    pesto_score.add_piece(piece, color, &square);
+                }
             }
         }
+        pesto_score
+    }
+
+    pub fn add_piece(&mut self, piece: usize, color: usize, square: &Square) {
+        self.mg[color] += PESTO_MG_TABLE[color][piece][square.as_usize()];
+        self.eg[color] += PESTO_EG_TABLE[color][piece][square.as_usize()];
+        self.game_phase[color] += PESTO_GAME_PHASE_INC[piece];
+    }
 
-        Self {
-            game_phase_inc: PESTO_GAME_PHASE_INC,
-            mg_table,
-            eg_table,
+    pub fn remove_piece(&mut self, piece: usize, color: usize, square: &Square) {
+        self.mg[color] -= PESTO_MG_TABLE[color][piece][square.as_usize()];
+        self.eg[color] -= PESTO_EG_TABLE[color][piece][square.as_usize()];
+        self.game_phase[color] -= PESTO_GAME_PHASE_INC[piece];
+    }
+
+    pub fn move_piece(
+        &mut self,
+        piece: usize,
+        color: usize,
+        origin: &Square,
+        destination: &Square,
+    ) {
+        self.mg[color] += PESTO_MG_TABLE[color][piece][destination.as_usize()]
+            - PESTO_MG_TABLE[color][piece][origin.as_usize()];
+        self.eg[color] += PESTO_EG_TABLE[color][piece][destination.as_usize()]
+            - PESTO_EG_TABLE[color][piece][origin.as_usize()];
+    }
+
+    /// tapered score from `color`'s point of view
+    pub fn score(&self, color: usize) -> i32 {
+        let mg_score = self.mg[Color::WHITE] - self.mg[Color::BLACK];
+        let eg_score = self.eg[Color::WHITE] - self.eg[Color::BLACK];
+        self.taper(mg_score, eg_score, color)
+    }
+
+    /// Blends white's (mg, eg) by the game phase, then turns it to `color`'s point of view
+    pub fn taper(&self, mg: i32, eg: i32, color: usize) -> i32 {
+        let mg_phase = (self.game_phase[0] + self.game_phase[1]).min(24);
+        let eg_phase = 24 - mg_phase;
+        let score = (mg * mg_phase + eg * eg_phase) / 24;
+
+        if color == Color::WHITE {
+            score
+        } else {
+            -score
         }
     }
+}
 
+pub struct PeSTO;
+
+impl PeSTO {
     pub fn calculate_score(&self, board: &Board) -> i32 {
+        debug_assert_eq!(board.pesto, PestoScore::from_board(board));
+        board.pesto.score(board.state.turn)
+    }
+}
+
+#[cfg(test)]
+mod test_pesto {
+    use super::*;
+    use crate::{moves::move_mask_gen::MoveGenMasks, utils::zobrist::ZobristHasher};
+    use once_cell::sync::Lazy;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    /// the old full board loop
+    fn reference_score(board: &Board) -> i32 {
         let mut mg: [i32; 2] = [0; 2];
         let mut eg: [i32; 2] = [0; 2];
         let mut game_phase: i32 = 0;
 
         for bit in 0..64 {
             if let Some(piece) = board.get_piece_on_square(&Square::new(bit)) {
-                mg[piece.color] += self.mg_table[piece.color][piece.piece][bit as usize];
-                eg[piece.color] += self.eg_table[piece.color][piece.piece][bit as usize];
-                game_phase += self.game_phase_inc[piece.piece];
+                mg[piece.color()] += PESTO_MG_TABLE[piece.color()][piece.piece()][bit as usize];
+                eg[piece.color()] += PESTO_EG_TABLE[piece.color()][piece.piece()][bit as usize];
+                game_phase += PESTO_GAME_PHASE_INC[piece.piece()];
             }
         }
 
@@ -212,4 +359,68 @@ impl PeSTO {
 
         (mg_score * mg_phase + eg_score * eg_phase) / 24
     }
+
+    #[test]
+    fn test_pesto_start_position() {
+        let board = Board::new(&HASHER);
+        assert_eq!(PeSTO.calculate_score(&board), 0);
+        assert_eq!(board.pesto.game_phase, [12, 12]);
+    }
+
+    #[test]
+    fn test_pesto_features_match_score() {
+        let weights = pesto_weights();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &HASHER).unwrap();
+            let score: f64 = pesto_features(&board)
+                .iter()
+                .map(|(index, coefficient)| weights[*index] as f64 * coefficient)
+                .sum();
+            let expected = board.pesto.score(Color::WHITE) as f64;
+            assert!(
+                (score - expected).abs() <= 1.0,
+                "{}: {} {}",
+                fen,
+                score,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_pesto_incremental_random_games() {
+        let mut rng = StdRng::seed_from_u64(0x9e57);
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+
+        for fen in fens {
+            for _ in 0..10 {
+                let mut board = Board::from_fen(fen, &HASHER).unwrap();
+                for _ in 0..100 {
+                    let legal_moves = board.get_legal_moves(&MOVE_GEN_MASKS, &HASHER);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    let before = board.pesto;
+
+                    let helper = board.make_move(&the_move, &HASHER);
+                    assert_eq!(board.pesto, PestoScore::from_board(&board));
+                    assert_eq!(PeSTO.calculate_score(&board), reference_score(&board));
+
+                    board.unmake_move(helper, &HASHER);
+                    assert_eq!(board.pesto, before);
+                    board.make_move(&the_move, &HASHER);
+                }
+            }
+        }
+    }
 }
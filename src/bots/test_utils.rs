@@ -0,0 +1,21 @@
+/// same position with colors swapped
+pub fn mirror_fen(fen: &str) -> String {
+    let parts: Vec<&str> = fen.split_whitespace().collect();
+    let ranks: Vec<String> = parts[0]
+        .split('/')
+        .rev()
+        .map(|rank| {
+            rank.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let turn = if parts[1] == "w" { "b" } else { "w" };
+    format!("{} {} - - 0 1", ranks.join("/"), turn)
+}
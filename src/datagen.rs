@@ -0,0 +1,827 @@
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{
+    board::{Board, FenError, FenOptions},
+    bots::{
+        bot::{is_mate_score, Bot},
+        time_control::TimeControl,
+    },
+    moves::move_mask_gen::MoveGenMasks,
+    types::{
+        piece::{Color, Pieces},
+        square::Square,
+        state::{LONG, SHORT},
+    },
+    utils::{
+        command::{default_threads, thread_pool, ArgError, Args},
+        zobrist::ZobristHasher,
+    },
+};
+
+/// adjudicate a win after this many plies above WIN_SCORE
+const WIN_PLIES: usize = 6;
+const WIN_SCORE: i32 = 2000;
+/// adjudicate a draw after DRAW_MIN_PLY once the score stays within DRAW_SCORE
+const DRAW_PLIES: usize = 10;
+const DRAW_SCORE: i32 = 10;
+const DRAW_MIN_PLY: usize = 80;
+
+#[derive(Debug)]
+pub enum DatagenError {
+    Io(io::Error),
+    InvalidArgument(ArgError),
+    InvalidFen(FenError),
+    InvalidData(String),
+}
+
+impl fmt::Display for DatagenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatagenError::Io(e) => write!(f, "Io error: {}", e),
+            DatagenError::InvalidArgument(e) => write!(f, "{}", e),
+            DatagenError::InvalidFen(e) => write!(f, "Invalid fen: {}", e),
+            DatagenError::InvalidData(e) => write!(f, "Invalid data: {}", e),
+        }
+    }
+}
+
+impl Error for DatagenError {}
+
+impl From<io::Error> for DatagenError {
+    fn from(e: io::Error) -> Self {
+        DatagenError::Io(e)
+    }
+}
+
+impl From<ArgError> for DatagenError {
+    fn from(e: ArgError) -> Self {
+        DatagenError::InvalidArgument(e)
+    }
+}
+
+/// Game result from white's point of view
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    BlackWin = 0,
+    Draw = 1,
+    WhiteWin = 2,
+}
+
+impl GameResult {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(GameResult::BlackWin),
+            1 => Some(GameResult::Draw),
+            2 => Some(GameResult::WhiteWin),
+            _ => None,
+        }
+    }
+
+    fn win_for(color: usize) -> Self {
+        if color == Color::WHITE {
+            GameResult::WhiteWin
+        } else {
+            GameResult::BlackWin
+        }
+    }
+
+    /// 1.0, 0.5 or 0.0
+    pub fn as_f32(&self) -> f32 {
+        *self as u8 as f32 / 2.0
+    }
+}
+
+/// A position with its search score and game result in 32 bytes, little endian:
+///
+/// occupancy u64, pieces [u8; 16] with a nibble per occupied square in square
+/// order (color << 3 | piece), turn << 7 | en passant square (64 for none) u8,
+/// castling u8 (KQkq in bits 0-3, rooks on the a and h files only), half moves u8, result u8, full moves u16,
+/// score i16 from white's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedPosition {
+    occupancy: u64,
+    pieces: [u8; 16],
+    turn_en_passant: u8,
+    castling: u8,
+    half_moves: u8,
+    result: u8,
+    full_moves: u16,
+    score: i16,
+}
+
+impl PackedPosition {
+    pub const SIZE: usize = 32;
+
+    pub fn new(board: &Board, score: i32, result: GameResult) -> Self {
+        let mut pieces = [0; 16];
+        for (i, square) in board.all_pieces.get_ones().into_iter().enumerate() {
+            let piece = board.get_piece_on_square(&square).unwrap();
+            let nibble = ((piece.color() << 3) | piece.piece()) as u8;
+            pieces[i / 2] |= nibble << (4 * (i % 2));
+        }
+        let en_passant = board.state.en_passant.map_or(64, |square| square.as_u8());
+        let castling = (0..4)
+            .filter(|bit| board.state.castling.read_bit(*bit))
+            .fold(0, |castling, bit| castling | (1 << bit));
+
+        Self {
+            occupancy: board.all_pieces.as_u64(),
+            pieces,
+            turn_en_passant: ((board.state.turn as u8) << 7) | en_passant,
+            castling,
+            half_moves: board.state.half_moves,
+            result: result as u8,
+            full_moves: board.state.full_moves,
+            score: score.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+        }
+    }
+
+    /// Whether the castling byte can hold the board's rights, Chess960 rook files can't
+    /// be packed
+    pub fn can_pack_castling(board: &Board) -> bool {
+        let castling = board.state.castling;
+        [Color::WHITE, Color::BLACK].into_iter().all(|color| {
+            (!castling.has_right(color, SHORT) || castling.rook_file(color, SHORT) == 7)
+                && (!castling.has_right(color, LONG) || castling.rook_file(color, LONG) == 0)
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24] = self.turn_en_passant;
+        bytes[25] = self.castling;
+        bytes[26] = self.half_moves;
+        bytes[27] = self.result;
+        bytes[28..30].copy_from_slice(&self.full_moves.to_le_bytes());
+        bytes[30..32].copy_from_slice(&self.score.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<Self, DatagenError> {
+        let position = Self {
+            occupancy: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            pieces: bytes[8..24].try_into().unwrap(),
+            turn_en_passant: bytes[24],
+            castling: bytes[25],
+            half_moves: bytes[26],
+            result: bytes[27],
+            full_moves: u16::from_le_bytes([bytes[28], bytes[29]]),
+            score: i16::from_le_bytes([bytes[30], bytes[31]]),
+        };
+        if position.occupancy.count_ones() > 32
+            || GameResult::from_u8(position.result).is_none()
+            || position.turn_en_passant & 0x7f > 64
+        {
+            return Err(DatagenError::InvalidData(format!("{:?}", position)));
+        }
+        Ok(position)
+    }
+
+    pub fn score(&self) -> i16 {
+        self.score
+    }
+
+    pub fn result(&self) -> GameResult {
+        GameResult::from_u8(self.result).unwrap()
+    }
+
+    pub fn fen(&self) -> String {
+        let mut squares: [Option<u8>; 64] = [None; 64];
+        for (i, square) in (0..64)
+            .filter(|square| self.occupancy & (1 << square) != 0)
+            .enumerate()
+        {
+            squares[square] = Some((self.pieces[i / 2] >> (4 * (i % 2))) & 0xf);
+        }
+
+        let mut ranks = Vec::new();
+        for rank in (0..8).rev() {
+            let mut rank_fen = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match squares[rank * 8 + file] {
+                    Some(nibble) => {
+                        if empty > 0 {
+                            rank_fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let symbol = ['q', 'r', 'b', 'n', 'p', 'k'][(nibble & 7) as usize % 6];
+                        rank_fen.push(if nibble >> 3 == Color::WHITE as u8 {
+                            symbol.to_ascii_uppercase()
+                        } else {
+                            symbol
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank_fen.push_str(&empty.to_string());
+            }
+            ranks.push(rank_fen);
+        }
+
+        let turn = if self.turn_en_passant >> 7 == 0 {
+            "w"
+        } else {
+            "b"
+        };
+        let castling: String = "KQkq"
+            .chars()
+            .enumerate()
+            .filter(|(bit, _)| self.castling & (1 << bit) != 0)
+            .map(|(_, symbol)| symbol)
+            .collect();
+        let castling = if castling.is_empty() {
+            "-".to_owned()
+        } else {
+            castling
+        };
+        let en_passant = match self.turn_en_passant & 0x7f {
+            64 => "-".to_owned(),
+            square => Square::new(square).to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            turn,
+            castling,
+            en_passant,
+            self.half_moves,
+            self.full_moves
+        )
+    }
+}
+
+/// `FEN;score;result` with the score in centipawns and the result 1.0, 0.5 or 0.0,
+/// both from white's point of view
+pub fn text_line(position: &PackedPosition) -> String {
+    format!(
+        "{};{};{:.1}",
+        position.fen(),
+        position.score(),
+        position.result().as_f32()
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct DatagenOptions {
+    pub games: usize,
+    pub nodes: u64,
+    pub random_plies: usize,
+    /// games longer than this are drawn
+    pub max_plies: usize,
+    pub threads: usize,
+    pub seed: u64,
+    /// fens to start from instead of the start position
+    pub book: Vec<String>,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            nodes: 5000,
+            random_plies: 8,
+            max_plies: 400,
+            threads: 1,
+            seed: 0,
+            book: Vec::new(),
+        }
+    }
+}
+
+fn is_insufficient_material(board: &Board) -> bool {
+    let pieces = &board.pieces;
+    let heavy_or_pawns = [Pieces::QUEEN, Pieces::ROOK, Pieces::PAWN]
+        .iter()
+        .any(|piece| !(pieces[Color::WHITE][*piece] | pieces[Color::BLACK][*piece]).is_empty());
+    let minors = (pieces[Color::WHITE][Pieces::BISHOP]
+        | pieces[Color::WHITE][Pieces::KNIGHT]
+        | pieces[Color::BLACK][Pieces::BISHOP]
+        | pieces[Color::BLACK][Pieces::KNIGHT])
+        .as_u64()
+        .count_ones();
+    !heavy_or_pawns && minors <= 1
+}
+
+/// Start position or a book position followed by random moves, None if the
+/// random moves ended the game
+fn random_opening(
+    rng: &mut StdRng,
+    options: &DatagenOptions,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Result<Option<Board>, DatagenError> {
+    let mut board = if options.book.is_empty() {
+        Board::new(hasher)
+    } else {
+        let fen = &options.book[rng.random_range(0..options.book.len())];
+        let board = Board::from_fen_with_options(fen, hasher, &FenOptions::lenient())
+            .map_err(DatagenError::InvalidFen)?;
+        if !PackedPosition::can_pack_castling(&board) {
+            return Err(DatagenError::InvalidData(format!(
+                "{} has Chess960 castling rights",
+                fen
+            )));
+        }
+        board
+    };
+
+    for _ in 0..options.random_plies {
+        let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
+        if legal_moves.is_empty() {
+            return Ok(None);
+        }
+        let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+        board.make_move(&the_move, hasher);
+    }
+
+    if board.get_legal_moves(move_gen_masks, hasher).is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(board))
+}
+
+/// Plays one game against itself and returns its quiet positions
+pub fn play_selfplay_game(
+    seed: u64,
+    options: &DatagenOptions,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Result<Vec<PackedPosition>, DatagenError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = loop {
+        if let Some(board) = random_opening(&mut rng, options, move_gen_masks, hasher)? {
+            break board;
+        }
+    };
+
+    let mut bot = Bot::with_depth(u8::MAX, TimeControl::max());
+    bot.set_max_nodes(Some(options.nodes));
+    bot.set_print_info(false);
+
+    let mut positions: Vec<(Board, i32)> = Vec::new();
+    let mut win_plies = 0;
+    let mut draw_plies = 0;
+    let mut ply = 0;
+
+    let result = loop {
+        let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
+        let in_check = board.is_check(move_gen_masks);
+        if legal_moves.is_empty() {
+            break if in_check {
+                GameResult::win_for(board.state.opponent)
+            } else {
+                GameResult::Draw
+            };
+        }
+        if ply >= options.max_plies
+            || board.state.half_moves >= 100
+            || board.check_repeat_draw()
+            || is_insufficient_material(&board)
+        {
+            break GameResult::Draw;
+        }
+
+        let search = bot.search(&mut board, move_gen_masks, hasher);
+        let white_score = if board.state.turn == Color::WHITE {
+            search.score
+        } else {
+            -search.score
+        };
+
+        let best_move = search.best_move;
+        if !in_check && !best_move.is_tactical() && !is_mate_score(search.score) {
+            positions.push((board.clone(), white_score));
+        }
+
+        win_plies = if search.score.abs() >= WIN_SCORE {
+            win_plies + 1
+        } else {
+            0
+        };
+        if win_plies >= WIN_PLIES {
+            break if white_score > 0 {
+                GameResult::WhiteWin
+            } else {
+                GameResult::BlackWin
+            };
+        }
+        draw_plies = if ply >= DRAW_MIN_PLY && search.score.abs() <= DRAW_SCORE {
+            draw_plies + 1
+        } else {
+            0
+        };
+        if draw_plies >= DRAW_PLIES {
+            break GameResult::Draw;
+        }
+
+        board.make_move(&best_move, hasher);
+        ply += 1;
+    };
+
+    Ok(positions
+        .iter()
+        .map(|(board, score)| PackedPosition::new(board, *score, result))
+        .collect())
+}
+
+/// Appends whole games to the data file. `<file>.progress` holds the index below
+/// which every game is finished, the number of positions written and the indices
+/// of the games finished above it, anything past those positions is cut off when
+/// resuming. Games finish out of order with several threads.
+pub struct DataWriter {
+    file: File,
+    progress_path: PathBuf,
+    /// every game below this one is written
+    pub finished_below: usize,
+    finished_above: BTreeSet<usize>,
+    pub positions: u64,
+}
+
+impl DataWriter {
+    pub fn open(path: &Path) -> Result<Self, DatagenError> {
+        let mut progress_path = path.as_os_str().to_owned();
+        progress_path.push(".progress");
+        let progress_path = PathBuf::from(progress_path);
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (finished_below, positions, finished_above) = match fs::read_to_string(&progress_path) {
+            Ok(progress) => {
+                let values: Vec<u64> = progress
+                    .split_whitespace()
+                    .map(|value| value.parse::<u64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| DatagenError::InvalidData(progress.clone()))?;
+                let [finished_below, positions, ref finished_above @ ..] = values[..] else {
+                    return Err(DatagenError::InvalidData(progress));
+                };
+                let finished_above: BTreeSet<usize> =
+                    finished_above.iter().map(|game| *game as usize).collect();
+
+                let recorded = positions * PackedPosition::SIZE as u64;
+                if file.metadata()?.len() < recorded {
+                    return Err(DatagenError::InvalidData(format!(
+                        "{} is shorter than its progress file",
+                        path.display()
+                    )));
+                }
+                // positions of a game that was being written when interrupted
+                file.set_len(recorded)?;
+                (finished_below as usize, positions, finished_above)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if file.metadata()?.len() > 0 {
+                    return Err(DatagenError::InvalidData(format!(
+                        "{} has data but no progress file",
+                        path.display()
+                    )));
+                }
+                (0, 0, BTreeSet::new())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            file,
+            progress_path,
+            finished_below,
+            finished_above,
+            positions,
+        })
+    }
+
+    pub fn games(&self) -> usize {
+        self.finished_below + self.finished_above.len()
+    }
+
+    pub fn is_finished(&self, game: usize) -> bool {
+        game < self.finished_below || self.finished_above.contains(&game)
+    }
+
+    pub fn write_game(
+        &mut self,
+        game: usize,
+        positions: &[PackedPosition],
+    ) -> Result<(), DatagenError> {
+        let bytes: Vec<u8> = positions
+            .iter()
+            .flat_map(|position| position.to_bytes())
+            .collect();
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        self.positions += positions.len() as u64;
+        self.finished_above.insert(game);
+        while self.finished_above.remove(&self.finished_below) {
+            self.finished_below += 1;
+        }
+
+        let mut progress = format!("{} {}", self.finished_below, self.positions);
+        for game in &self.finished_above {
+            progress.push_str(&format!(" {}", game));
+        }
+        let mut temporary_path = self.progress_path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        fs::write(&temporary_path, progress + "\n")?;
+        fs::rename(&temporary_path, &self.progress_path)?;
+        Ok(())
+    }
+}
+
+pub fn read_positions(path: &Path) -> Result<Vec<PackedPosition>, DatagenError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut positions = Vec::new();
+    let mut bytes = [0; PackedPosition::SIZE];
+    loop {
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => positions.push(PackedPosition::from_bytes(&bytes)?),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(positions),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Plays the games that are missing from `path`
+pub fn generate(
+    path: &Path,
+    options: &DatagenOptions,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Result<(), DatagenError> {
+    let writer = DataWriter::open(path)?;
+    if writer.games() > 0 {
+        println!(
+            "resuming after {} games and {} positions",
+            writer.games(),
+            writer.positions
+        );
+    }
+    let missing_games: Vec<usize> = (writer.finished_below..options.games)
+        .filter(|game| !writer.is_finished(*game))
+        .collect();
+    let writer = Mutex::new(writer);
+
+    let pool = thread_pool(options.threads)?;
+
+    pool.install(|| {
+        missing_games.into_par_iter().try_for_each(|game| {
+            let seed = options.seed ^ (game as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            let positions = play_selfplay_game(seed, options, move_gen_masks, hasher)?;
+
+            let mut writer = writer.lock().unwrap();
+            writer.write_game(game, &positions)?;
+            let games = writer.games();
+            if games % 10 == 0 || games == options.games {
+                println!(
+                    "games {}/{} positions {}",
+                    games, options.games, writer.positions
+                );
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Writes every position of a data file as a `FEN;score;result` line
+pub fn export_text(input: &Path, output: &Path) -> Result<usize, DatagenError> {
+    let positions = read_positions(input)?;
+    let mut writer = BufWriter::new(File::create(output)?);
+    for position in &positions {
+        writeln!(writer, "{}", text_line(position))?;
+    }
+    writer.flush()?;
+    Ok(positions.len())
+}
+
+fn read_book(path: &Path) -> Result<Vec<String>, DatagenError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut fens = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // epd operations after the four position fields are ignored
+        let fen = line
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        fens.push(fen);
+    }
+    if fens.is_empty() {
+        return Err(DatagenError::InvalidData(format!(
+            "{} has no positions",
+            path.display()
+        )));
+    }
+    Ok(fens)
+}
+
+/// `datagen <file> [games N] [nodes N] [random_plies N] [max_plies N] [book <file>] [threads N] [seed N]`
+/// or `datagen export <file> <text file>`
+pub fn datagen_command(args: &[String]) -> Result<(), DatagenError> {
+    let mut args = Args::new(
+        args,
+        "datagen <file> [games N] [nodes N] [random_plies N] [max_plies N] \
+         [book <file>] [threads N] [seed N] | datagen export <file> <text file>",
+    );
+
+    let path = args.value()?;
+    if path == "export" {
+        let input = args.value()?;
+        let output = args.value()?;
+        let n_positions = export_text(Path::new(input), Path::new(output))?;
+        println!("exported {} positions", n_positions);
+        return Ok(());
+    }
+
+    let mut options = DatagenOptions {
+        threads: default_threads(),
+        ..DatagenOptions::default()
+    };
+    while let Some(arg) = args.next() {
+        match arg {
+            "games" => options.games = args.parse()?,
+            "nodes" => options.nodes = args.parse()?,
+            "random_plies" => options.random_plies = args.parse()?,
+            "max_plies" => options.max_plies = args.parse()?,
+            "book" => options.book = read_book(Path::new(args.value()?))?,
+            "threads" => options.threads = args.parse()?,
+            "seed" => options.seed = args.parse()?,
+            other => return Err(ArgError::Invalid(other.to_owned()).into()),
+        }
+    }
+
+    let move_gen_masks = MoveGenMasks::load();
+    let hasher = ZobristHasher::load();
+    generate(Path::new(path), &options, &move_gen_masks, &hasher)
+}
+
+#[cfg(test)]
+mod test_datagen {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn temporary_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut progress = path.clone().into_os_string();
+        progress.push(".progress");
+        let _ = fs::remove_file(progress);
+        path
+    }
+
+    #[test]
+    fn test_packed_position_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w Kq d6 0 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b - - 12 40",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            // the king is off the e file but the rooks are on a and h
+            "1k5r/8/8/8/8/8/8/RK5R w KQk - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &HASHER).unwrap();
+            assert!(PackedPosition::can_pack_castling(&board));
+            let packed = PackedPosition::new(&board, -321, GameResult::BlackWin);
+            let unpacked = PackedPosition::from_bytes(&packed.to_bytes()).unwrap();
+            assert_eq!(unpacked, packed);
+            assert_eq!(unpacked.fen(), fen);
+            assert_eq!(unpacked.score(), -321);
+            assert_eq!(text_line(&unpacked), format!("{};-321;0.0", fen));
+        }
+
+        let mut bytes = PackedPosition::new(&Board::new(&HASHER), 0, GameResult::Draw).to_bytes();
+        bytes[27] = 3;
+        assert!(PackedPosition::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_selfplay_game() {
+        let options = DatagenOptions {
+            nodes: 200,
+            max_plies: 30,
+            ..DatagenOptions::default()
+        };
+        let positions = play_selfplay_game(7, &options, &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert!(!positions.is_empty());
+        assert!(positions.len() <= 30);
+
+        let result = positions[0].result();
+        for position in &positions {
+            assert_eq!(position.result(), result);
+            let board = Board::from_fen(&position.fen(), &HASHER).unwrap();
+            assert!(!board.is_check(&MOVE_GEN_MASKS));
+        }
+
+        let again = play_selfplay_game(7, &options, &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert_eq!(positions.len(), again.len());
+    }
+
+    #[test]
+    fn test_selfplay_rejects_chess960_castling() {
+        let fen = "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GBgb - 0 1";
+        let board = Board::from_fen(fen, &HASHER).unwrap();
+        assert!(!PackedPosition::can_pack_castling(&board));
+
+        let options = DatagenOptions {
+            nodes: 200,
+            book: vec![fen.to_owned()],
+            ..DatagenOptions::default()
+        };
+        assert!(matches!(
+            play_selfplay_game(7, &options, &MOVE_GEN_MASKS, &HASHER),
+            Err(DatagenError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_writer_resumes() {
+        let path = temporary_path("datagen-resume");
+        let position = PackedPosition::new(&Board::new(&HASHER), 15, GameResult::Draw);
+
+        let mut writer = DataWriter::open(&path).unwrap();
+        writer.write_game(0, &[position, position]).unwrap();
+        writer.write_game(1, &[position]).unwrap();
+        drop(writer);
+
+        // a game cut off half way
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&position.to_bytes()[..20]).unwrap();
+        drop(file);
+
+        let mut writer = DataWriter::open(&path).unwrap();
+        assert_eq!((writer.games(), writer.positions), (2, 3));
+        writer.write_game(2, &[position]).unwrap();
+        drop(writer);
+
+        let positions = read_positions(&path).unwrap();
+        assert_eq!(positions.len(), 4);
+        assert!(positions.iter().all(|p| *p == position));
+
+        let text_path = temporary_path("datagen-export.txt");
+        assert_eq!(export_text(&path, &text_path).unwrap(), 4);
+        let text = fs::read_to_string(&text_path).unwrap();
+        assert_eq!(text.lines().count(), 4);
+        fs::remove_file(text_path).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writer_resumes_games_finished_out_of_order() {
+        let path = temporary_path("datagen-out-of-order");
+        let position = PackedPosition::new(&Board::new(&HASHER), 15, GameResult::Draw);
+
+        let mut writer = DataWriter::open(&path).unwrap();
+        for game in [0, 3, 2] {
+            writer.write_game(game, &[position]).unwrap();
+        }
+        drop(writer);
+
+        let mut writer = DataWriter::open(&path).unwrap();
+        assert_eq!((writer.finished_below, writer.games()), (1, 3));
+        let missing: Vec<usize> = (0..6).filter(|game| !writer.is_finished(*game)).collect();
+        assert_eq!(missing, vec![1, 4, 5]);
+        writer.write_game(1, &[position]).unwrap();
+        drop(writer);
+
+        let writer = DataWriter::open(&path).unwrap();
+        assert_eq!((writer.finished_below, writer.games()), (4, 4));
+        assert_eq!(read_positions(&path).unwrap().len(), 4);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writer_keeps_data_without_progress() {
+        let path = temporary_path("datagen-no-progress");
+        let position = PackedPosition::new(&Board::new(&HASHER), 15, GameResult::Draw);
+        fs::write(&path, position.to_bytes()).unwrap();
+
+        assert!(matches!(
+            DataWriter::open(&path),
+            Err(DatagenError::InvalidData(_))
+        ));
+        assert_eq!(read_positions(&path).unwrap(), vec![position]);
+        fs::remove_file(&path).unwrap();
+    }
+}
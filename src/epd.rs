@@ -0,0 +1,525 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{
+    board::Board,
+    bots::{bot::Bot, time_control::TimeControl},
+    moves::{move_mask_gen::MoveGenMasks, moves_utils::Move, san::SanError},
+    utils::{
+        command::{ArgError, Args},
+        zobrist::ZobristHasher,
+    },
+};
+
+#[derive(Debug)]
+pub enum EpdError {
+    Io(io::Error),
+    InvalidLine(String),
+    InvalidFen(String),
+    InvalidMove(SanError),
+    InvalidArgument(ArgError),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::Io(e) => write!(f, "Error reading EPD: {}", e),
+            EpdError::InvalidLine(line) => write!(f, "Invalid EPD line: {}", line),
+            EpdError::InvalidFen(e) => write!(f, "Invalid EPD position: {}", e),
+            EpdError::InvalidMove(e) => write!(f, "Invalid EPD move: {}", e),
+            EpdError::InvalidArgument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for EpdError {}
+
+impl From<io::Error> for EpdError {
+    fn from(e: io::Error) -> Self {
+        EpdError::Io(e)
+    }
+}
+
+impl From<SanError> for EpdError {
+    fn from(e: SanError) -> Self {
+        EpdError::InvalidMove(e)
+    }
+}
+
+impl From<ArgError> for EpdError {
+    fn from(e: ArgError) -> Self {
+        EpdError::InvalidArgument(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpdOperation {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+/// A single EPD record: the first four FEN fields followed by `opcode operands;` operations
+#[derive(Clone, Debug)]
+pub struct EpdEntry {
+    pub position: String,
+    pub operations: Vec<EpdOperation>,
+}
+
+impl EpdEntry {
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let line = line.trim();
+        let invalid_line = || EpdError::InvalidLine(line.to_owned());
+
+        let position_fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        if position_fields.len() != 4 {
+            return Err(invalid_line());
+        }
+        // the operations are what follows the fourth field, quoted operands keep their spacing
+        let rest = position_fields.iter().fold(line, |rest, field| {
+            rest.trim_start().strip_prefix(field).unwrap_or(rest)
+        });
+        let operations = parse_operations(rest.trim_start()).ok_or_else(invalid_line)?;
+
+        Ok(Self {
+            position: position_fields.join(" "),
+            operations,
+        })
+    }
+
+    pub fn get_operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|operation| operation.opcode == opcode)
+            .map(|operation| operation.operands.as_slice())
+    }
+
+    fn get_first_operand(&self, opcode: &str) -> Option<&str> {
+        self.get_operands(opcode)
+            .and_then(|operands| operands.first())
+            .map(|operand| operand.as_str())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.get_first_operand("id")
+    }
+
+    /// Comment opcodes c0 to c9
+    pub fn comment(&self, n: u8) -> Option<&str> {
+        self.get_first_operand(&format!("c{}", n))
+    }
+
+    /// Full move count for a direct mate, `dm` opcode
+    pub fn direct_mate(&self) -> Option<i32> {
+        self.get_first_operand("dm")
+            .and_then(|operand| operand.parse::<i32>().ok())
+    }
+
+    /// Six field FEN, counters are taken from hmvc and fmvn when present
+    pub fn fen(&self) -> String {
+        let half_moves = self.get_first_operand("hmvc").unwrap_or("0");
+        let full_moves = self.get_first_operand("fmvn").unwrap_or("1");
+        format!("{} {} {}", self.position, half_moves, full_moves)
+    }
+
+    pub fn board(&self, hasher: &ZobristHasher) -> Result<Board, EpdError> {
+        Board::from_fen(&self.fen(), hasher).map_err(|e| EpdError::InvalidFen(e.to_string()))
+    }
+
+    pub fn best_moves(
+        &self,
+        board: &mut Board,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Result<Vec<Move>, EpdError> {
+        self.resolve_moves("bm", board, move_gen_masks, hasher)
+    }
+
+    pub fn avoid_moves(
+        &self,
+        board: &mut Board,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Result<Vec<Move>, EpdError> {
+        self.resolve_moves("am", board, move_gen_masks, hasher)
+    }
+
+    fn resolve_moves(
+        &self,
+        opcode: &str,
+        board: &mut Board,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Result<Vec<Move>, EpdError> {
+        let mut moves = Vec::new();
+        for san in self.get_operands(opcode).unwrap_or(&[]) {
+            moves.push(board.parse_san(san, move_gen_masks, hasher)?);
+        }
+        Ok(moves)
+    }
+}
+
+fn parse_operations(input: &str) -> Option<Vec<EpdOperation>> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = input.chars();
+    let mut token = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // quoted operands may contain spaces and semicolons
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => return None,
+                    }
+                }
+                tokens.push(quoted);
+            }
+            ';' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if tokens.is_empty() {
+                    continue;
+                }
+                let opcode = tokens.remove(0);
+                operations.push(EpdOperation {
+                    opcode,
+                    operands: std::mem::take(&mut tokens),
+                });
+            }
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    // the last operation does not need a terminating semicolon
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push(EpdOperation {
+            opcode,
+            operands: tokens,
+        });
+    }
+
+    Some(operations)
+}
+
+pub fn read_epd_file(path: &Path) -> Result<Vec<EpdEntry>, EpdError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(EpdEntry::parse(&line)?);
+    }
+    Ok(entries)
+}
+
+/// Search budget for every position of a suite
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EpdLimits {
+    pub depth: Option<u8>,
+    pub move_time: Option<u32>,
+    pub nodes: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SuiteScore {
+    pub solved: usize,
+    pub total: usize,
+    /// entries with an invalid position or move, or no legal move to search
+    pub skipped: usize,
+}
+
+/// Searches every entry that has a bm, am or dm opcode and checks the bot's answer,
+/// entries that can't be searched are reported and skipped
+pub fn run_epd_entries(
+    entries: &[EpdEntry],
+    limits: &EpdLimits,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> SuiteScore {
+    let mut score = SuiteScore::default();
+    let time_control = match limits.move_time {
+        Some(move_time) => TimeControl::new(0, 0, 0, 0, Some(move_time)),
+        None => TimeControl::max(),
+    };
+    let mut bot = Bot::with_depth(limits.depth.unwrap_or(u8::MAX), time_control);
+    bot.set_max_nodes(limits.nodes);
+    bot.set_print_info(false);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let id = entry
+            .id()
+            .map(|id| id.to_owned())
+            .unwrap_or(format!("#{}", i + 1));
+        let resolved = entry.board(hasher).and_then(|mut board| {
+            let best_moves = entry.best_moves(&mut board, move_gen_masks, hasher)?;
+            let avoid_moves = entry.avoid_moves(&mut board, move_gen_masks, hasher)?;
+            Ok((board, best_moves, avoid_moves))
+        });
+        let (mut board, best_moves, avoid_moves) = match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                println!("skip {}: {}", id, e);
+                score.skipped += 1;
+                continue;
+            }
+        };
+        let direct_mate = entry.direct_mate();
+        if best_moves.is_empty() && avoid_moves.is_empty() && direct_mate.is_none() {
+            continue;
+        }
+        if board.get_legal_moves(move_gen_masks, hasher).is_empty() {
+            println!("skip {}: no legal moves", id);
+            score.skipped += 1;
+            continue;
+        }
+
+        let result = bot.search(&mut board, move_gen_masks, hasher);
+        let found = result.best_move;
+
+        let solved = (best_moves.is_empty() || best_moves.contains(&found))
+            && !avoid_moves.contains(&found)
+            && direct_mate.is_none_or(|moves| {
+                result
+                    .mate_in()
+                    .is_some_and(|mate_in| mate_in > 0 && mate_in <= moves)
+            });
+
+        score.total += 1;
+        if solved {
+            score.solved += 1;
+        }
+
+        let found_san = board.move_to_san(&found, move_gen_masks, hasher);
+        let mut expected = String::new();
+        for (opcode, moves) in [("bm", &best_moves), ("am", &avoid_moves)] {
+            if !moves.is_empty() {
+                let sans: Vec<String> = moves
+                    .iter()
+                    .map(|the_move| board.move_to_san(the_move, move_gen_masks, hasher))
+                    .collect();
+                expected.push_str(&format!(" {} {}", opcode, sans.join(" ")));
+            }
+        }
+        if let Some(moves) = direct_mate {
+            expected.push_str(&format!(" dm {}", moves));
+        }
+        println!(
+            "{} {}: found {} (depth {}, nodes {}), expected{}",
+            if solved { "ok  " } else { "FAIL" },
+            id,
+            found_san,
+            result.depth,
+            result.nodes,
+            expected
+        );
+    }
+
+    score
+}
+
+/// `epd <file>... [depth N] [movetime MS] [nodes N]`
+pub fn epd_command(args: &[String]) -> Result<(), EpdError> {
+    let mut limits = EpdLimits::default();
+    let mut files: Vec<&str> = Vec::new();
+
+    let usage = "epd <file>... [depth N] [movetime MS] [nodes N]";
+    let mut args = Args::new(args, usage);
+    while let Some(arg) = args.next() {
+        match arg {
+            "depth" => limits.depth = Some(args.parse()?),
+            "movetime" => limits.move_time = Some(args.parse()?),
+            "nodes" => limits.nodes = Some(args.parse()?),
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        return Err(ArgError::Usage(usage).into());
+    }
+    if limits.depth.is_none() && limits.move_time.is_none() && limits.nodes.is_none() {
+        limits.depth = Some(5);
+    }
+
+    let move_gen_masks = MoveGenMasks::load();
+    let hasher = ZobristHasher::load();
+    let mut summaries: Vec<(&str, SuiteScore)> = Vec::with_capacity(files.len());
+
+    for file in files {
+        let entries = read_epd_file(Path::new(file))?;
+        let score = run_epd_entries(&entries, &limits, &move_gen_masks, &hasher);
+        summaries.push((file, score));
+    }
+
+    println!();
+    let mut total = SuiteScore::default();
+    for (file, score) in summaries {
+        println!("{}: {}", file, format_score(&score));
+        total.solved += score.solved;
+        total.total += score.total;
+        total.skipped += score.skipped;
+    }
+    println!("Total: {}", format_score(&total));
+
+    Ok(())
+}
+
+fn format_score(score: &SuiteScore) -> String {
+    let percentage = if score.total == 0 {
+        0.0
+    } else {
+        score.solved as f64 * 100.0 / score.total as f64
+    };
+    let mut summary = format!(
+        "{}/{} solved ({:.1}%)",
+        score.solved, score.total, percentage
+    );
+    if score.skipped > 0 {
+        summary.push_str(&format!(", {} skipped", score.skipped));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test_epd {
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    #[test]
+    fn test_parse_entry() {
+        let entry = EpdEntry::parse(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"quoted; with semicolon\";",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entry.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(entry.id(), Some("WAC.001"));
+        assert_eq!(entry.comment(0), Some("quoted; with semicolon"));
+        assert_eq!(entry.get_operands("bm"), Some(&["Qg6".to_owned()][..]));
+        assert_eq!(entry.comment(1), None);
+
+        let mut board = entry.board(&HASHER).unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        let best_moves = entry
+            .best_moves(&mut board, &MOVE_GEN_MASKS, &HASHER)
+            .unwrap();
+        assert_eq!(best_moves, vec![Move::from_long_str("g3g6")]);
+    }
+
+    #[test]
+    fn test_parse_counters_and_multiple_moves() {
+        let entry = EpdEntry::parse(
+            "4k3/8/8/8/8/8/8/R3K2R w KQ - am Kf2 Kd2; bm O-O O-O-O; hmvc 12; fmvn 40",
+        )
+        .unwrap();
+
+        assert_eq!(entry.fen(), "4k3/8/8/8/8/8/8/R3K2R w KQ - 12 40");
+        let mut board = entry.board(&HASHER).unwrap();
+        assert_eq!(
+            entry
+                .avoid_moves(&mut board, &MOVE_GEN_MASKS, &HASHER)
+                .unwrap()
+                .len(),
+            2
+        );
+        let best_moves = entry
+            .best_moves(&mut board, &MOVE_GEN_MASKS, &HASHER)
+            .unwrap();
+        assert_eq!(best_moves[0].to_long_string(), "e1g1");
+        assert_eq!(best_moves[1].to_long_string(), "e1c1");
+        assert_eq!(entry.direct_mate(), None);
+    }
+
+    #[test]
+    fn test_parse_tabs_and_repeated_spaces() {
+        let entry = EpdEntry::parse(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1\tw  -\t-   bm Qg6;\tid \"WAC  001\";",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entry.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(entry.get_operands("bm"), Some(&["Qg6".to_owned()][..]));
+        assert_eq!(entry.id(), Some("WAC  001"));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        assert!(matches!(
+            EpdEntry::parse("8/8/8 w"),
+            Err(EpdError::InvalidLine(_))
+        ));
+        assert!(matches!(
+            EpdEntry::parse("8/8/8/8/8/8/8/8 w - - id \"unterminated"),
+            Err(EpdError::InvalidLine(_))
+        ));
+
+        let entry = EpdEntry::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Qh5;").unwrap();
+        let mut board = entry.board(&HASHER).unwrap();
+        assert!(matches!(
+            entry.best_moves(&mut board, &MOVE_GEN_MASKS, &HASHER),
+            Err(EpdError::InvalidMove(_))
+        ));
+    }
+
+    #[test]
+    fn test_run_entries() {
+        let entries: Vec<EpdEntry> = [
+            "k7/8/1K6/8/8/8/8/7R w - - bm Rh8#; id \"mate in one\";",
+            "k7/8/1K6/8/8/8/8/7R w - - dm 1; id \"direct mate\";",
+            "k7/8/1K6/8/8/8/8/7R w - - am Rh8; id \"avoid the mate\";",
+            "k7/8/1K6/8/8/8/8/7R w - - c0 \"not scored\";",
+            "k7/8/1K6/8/8/8/8/7R w - - bm Qh8; id \"no queen\";",
+            "k7/1Q6/1K6/8/8/8/8/8 b - - dm 1; id \"already mated\";",
+            "k7/8/1K6/8/8/8/8/7R w - - bm Rh8; id \"after the bad entries\";",
+        ]
+        .iter()
+        .map(|line| EpdEntry::parse(line).unwrap())
+        .collect();
+        let limits = EpdLimits {
+            depth: Some(2),
+            ..Default::default()
+        };
+
+        let score = run_epd_entries(&entries, &limits, &MOVE_GEN_MASKS, &HASHER);
+        assert_eq!(
+            score,
+            SuiteScore {
+                solved: 3,
+                total: 4,
+                skipped: 2
+            }
+        );
+    }
+}
@@ -1,14 +1,19 @@
-use crate::board::Board;
+use crate::board::{Board, FenOptions};
 use crate::bots::bot::Bot;
+use crate::bots::nnue::Network;
 use crate::bots::time_control::TimeControl;
 use crate::moves::moves_utils::Move;
+use crate::perft::{self, PerftOptions};
+use crate::utils::command::default_threads;
 use crate::{moves::move_mask_gen::MoveGenMasks, utils::zobrist::ZobristHasher};
 use std::sync::atomic::AtomicBool;
 
 use std::io::stdin;
+use std::path::Path;
 use std::process;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 use std::thread;
 
 pub static UCI_STOP: AtomicBool = AtomicBool::new(false);
@@ -18,6 +23,11 @@ pub struct UCIGame {
     hasher: ZobristHasher,
     bot: Bot,
     board: Board,
+    network: Option<Arc<Network>>,
+    use_nnue: bool,
+    chess960: bool,
+    /// set when the last `position` command could not be applied, `go` refuses to search
+    position_error: Option<String>,
 }
 
 impl UCIGame {
@@ -28,6 +38,10 @@ impl UCIGame {
             hasher: ZobristHasher::load(),
             bot: Bot::default(),
             board: Board::new(&hasher),
+            network: None,
+            use_nnue: false,
+            chess960: false,
+            position_error: None,
         }
     }
 
@@ -72,8 +86,8 @@ impl UCIGame {
             "is_ready" => self.uci_is_ready(),
             "ponderhit" => (), // later (or never)
             "position" => self.uci_position(input),
-            "setoption" => (), // later
-            "stop" => (),      // done in uci_io_loop
+            "setoption" => self.uci_set_option(input),
+            "stop" => (), // done in uci_io_loop
             "uci" => self.uci_uci(),
             "ucinewgame" => (),                            // not necessary?
             "quit" => (),                                  // done in uci_io_loop
@@ -83,12 +97,24 @@ impl UCIGame {
     }
 
     fn uci_go(&mut self, args: Vec<&str>) {
+        if let Some(error) = &self.position_error {
+            println!("info string refusing to search, {}", error);
+            println!("bestmove 0000");
+            return;
+        }
+
+        if args.first() == Some(&"perft") {
+            self.uci_go_perft(&args[1..]);
+            return;
+        }
+
         UCI_STOP.store(false, Ordering::Relaxed);
         let mut wtime = u32::MAX;
         let mut btime = u32::MAX;
         let mut winc = 0;
         let mut binc = 0;
         let mut move_time: Option<u32> = None;
+        self.bot.set_max_nodes(None);
 
         for i in 0..args.len() {
             match args[i] {
@@ -101,7 +127,9 @@ impl UCIGame {
                 "binc" => binc = args[i + 1].parse::<u32>().unwrap(),
                 "movestogo" => (),
                 "depth" => self.bot.set_depth(args[i + 1].parse::<u8>().unwrap()),
-                "nodes" => (),
+                "nodes" => self
+                    .bot
+                    .set_max_nodes(Some(args[i + 1].parse::<u64>().unwrap())),
                 "mate" => (),
                 "movetime" => move_time = Some(args[i + 1].parse::<u32>().unwrap()),
                 _ => continue,
@@ -116,42 +144,143 @@ impl UCIGame {
             .bot
             .get_best_move(&mut self.board, &self.move_gen_masks, &self.hasher);
         self.board.make_move(&bot_move, &self.hasher);
-        println!("bestmove {}", bot_move.to_long_string());
+        println!("bestmove {}", bot_move.to_uci_string(self.chess960));
+    }
+
+    /// not UCI, `go perft N` as in Stockfish
+    fn uci_go_perft(&self, args: &[&str]) {
+        let Some(depth) = args.first().and_then(|depth| depth.parse::<u8>().ok()) else {
+            println!("info string go perft needs a depth");
+            return;
+        };
+        let options = PerftOptions {
+            threads: default_threads(),
+            cache_size_mb: 16,
+            chess960: self.chess960,
+        };
+
+        match perft::perft_divide(
+            &self.board,
+            &self.move_gen_masks,
+            &self.hasher,
+            depth,
+            &options,
+        ) {
+            Ok(result) => println!("{}", result),
+            Err(e) => println!("info string {}", e),
+        }
     }
 
     fn uci_uci(&self) {
         println!("id name {}", env!("CARGO_PKG_NAME"));
         println!("id author {}", env!("CARGO_PKG_AUTHORS"));
-        // TODO: add options
+        println!("option name UseNNUE type check default false");
+        println!("option name EvalFile type string default <empty>");
+        println!("option name UCI_Chess960 type check default false");
         println!("uciok");
     }
 
-    fn uci_position(&mut self, mut args: Vec<&str>) {
-        let start_pos = args.remove(0);
+    fn uci_position(&mut self, args: Vec<&str>) {
+        let mut args = args.into_iter();
 
-        if start_pos == "startpos" {
-            self.board = Board::new(&self.hasher)
-        } else if start_pos == "fen" {
-            let mut fen_string = args.remove(0).to_owned();
-            for _ in 0..5 {
-                fen_string.push(' ');
-                fen_string.push_str(args.remove(0));
+        let board = match args.next() {
+            Some("startpos") => Ok(Board::new(&self.hasher)),
+            Some("fen") => {
+                let fen_string = args
+                    .by_ref()
+                    .take_while(|arg| *arg != "moves")
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+                Board::from_fen_with_options(&fen_string, &self.hasher, &FenOptions::lenient())
+                    .map_err(|e| format!("invalid fen {}: {}", fen_string, e))
             }
-            self.board = match Board::from_fen(&fen_string, &self.hasher) {
-                Ok(board) => board,
-                Err(e) => panic!("{}", e),
-            };
-        } else {
-            panic!("unexpected position argument")
+            Some(other) => Err(format!("unexpected position argument {}", other)),
+            None => Err("missing position argument".to_owned()),
+        };
+
+        self.board = match board {
+            Ok(board) => board,
+            Err(e) => {
+                println!("info string {}", e);
+                self.position_error = Some(e);
+                return;
+            }
+        };
+        self.position_error = None;
+        if let (Some(network), true) = (&self.network, self.use_nnue) {
+            self.board.enable_nnue(network.clone());
         }
 
-        if !args.is_empty() && args.remove(0) == "moves" {
-            for move_str in args {
-                let move_to_make = Move::from_long_str(move_str);
-                self.board
+        // "moves" was already consumed for fen positions, castling is accepted both as
+        // king takes rook and as the two square king move
+        for move_str in args.skip_while(|arg| *arg == "moves") {
+            let result = match Move::parse_long_str(move_str) {
+                Some(move_to_make) => self
+                    .board
                     .check_and_make_move(&move_to_make, &self.move_gen_masks, &self.hasher)
+                    .map(|_| ())
+                    .map_err(|e| format!("illegal move {}: {}", move_str, e)),
+                None => Err(format!("invalid move {}", move_str)),
+            };
+            if let Err(e) = result {
+                println!("info string {}", e);
+                self.position_error = Some(e);
+                return;
+            }
+        }
+    }
+
+    /// `setoption name <name> [value <value>]`
+    fn uci_set_option(&mut self, args: Vec<&str>) {
+        let mut args = args.into_iter().skip_while(|arg| *arg == "name");
+        let name = args
+            .by_ref()
+            .take_while(|arg| *arg != "value")
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value = args.collect::<Vec<&str>>().join(" ");
+
+        match name.to_lowercase().as_str() {
+            "usennue" => match value.as_str() {
+                "true" => self.use_nnue = true,
+                "false" => self.use_nnue = false,
+                _ => println!("info string UseNNUE expects true or false, got {}", value),
+            },
+            "evalfile" => match Network::load(Path::new(&value)) {
+                Ok(network) => {
+                    println!(
+                        "info string loaded {} with {} hidden neurons",
+                        value,
+                        network.hidden_size()
+                    );
+                    self.network = Some(Arc::new(network));
+                }
+                Err(e) => println!("info string {}", e),
+            },
+            "uci_chess960" => match value.as_str() {
+                "true" => self.chess960 = true,
+                "false" => self.chess960 = false,
+                _ => println!(
+                    "info string UCI_Chess960 expects true or false, got {}",
+                    value
+                ),
+            },
+            _ => println!("info string unknown option {}", name),
+        }
+        self.apply_evaluation();
+    }
+
+    /// NNUE needs both the option and a loaded network, handcrafted evaluation otherwise
+    fn apply_evaluation(&mut self) {
+        match (&self.network, self.use_nnue) {
+            (Some(network), true) => self.board.enable_nnue(network.clone()),
+            (None, true) => {
+                println!("info string UseNNUE is set but no EvalFile is loaded");
+                self.board.disable_nnue();
             }
+            _ => self.board.disable_nnue(),
         }
+        self.bot.clear_evaluation_cache();
     }
 
     fn uci_is_ready(&self) {
@@ -2,9 +2,13 @@
 
 pub mod board;
 pub mod bots;
+pub mod datagen;
+pub mod epd;
 pub mod game;
 mod magic;
 pub mod moves;
 pub mod perft;
+pub mod pgn;
+pub mod tuner;
 pub mod types;
 pub mod utils;
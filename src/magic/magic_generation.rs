@@ -1,27 +1,53 @@
 use rayon::prelude::*;
-use std::{collections::HashMap, fmt::Display, time::Instant};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Write},
+    fs, io,
+    path::Path,
+    time::Instant,
+};
 
 use rand::Rng;
 
 use crate::{
+    magic::magics::{BISHOP_MAGICS, ROOK_MAGICS},
     moves::move_mask_gen::{
-        generate_bishop_moves, generate_relevant_blockers, generate_rook_moves,
+        generate_bishop_moves, generate_magic_entries, generate_moves_with_magics,
+        generate_relevant_blockers, generate_rook_moves,
     },
     types::{bitboard::BitBoard, piece::Pieces, square::Square},
+    utils::command::{ArgError, Args},
 };
 
+const MAGICS_SOURCE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/magic/magics.rs");
+const MOVES_FOLDER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/moves/");
+
+/// Fancy magic, `offset` points into the shared slider move table
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Magic {
     pub mask: BitBoard,
     pub magic: u64,
     pub shift: u8,
+    pub offset: usize,
 }
 
 impl Magic {
-    pub fn new(mask: BitBoard, magic: u64, shift: u8) -> Self {
-        Self { mask, magic, shift }
+    pub fn new(mask: BitBoard, magic: u64, shift: u8, offset: usize) -> Self {
+        Self {
+            mask,
+            magic,
+            shift,
+            offset,
+        }
     }
 
     pub fn get_index(&self, board_mask: BitBoard) -> usize {
+        self.offset + self.get_local_index(board_mask)
+    }
+
+    /// index into this magic's own part of the table
+    pub fn get_local_index(&self, board_mask: BitBoard) -> usize {
         let blocker_mask = board_mask & self.mask;
         (blocker_mask.0.overflowing_mul(self.magic).0 >> self.shift) as usize
     }
@@ -39,7 +65,9 @@ impl Display for Magic {
     }
 }
 
-fn find_magic(square: &Square, piece: usize, timeout_seconds: u64) -> (u8, u64) {
+/// (shift, magic) with the biggest shift found in time, None if not even the
+/// smallest shift worked
+fn find_magic(square: &Square, piece: usize, timeout_seconds: u64) -> Option<(u8, u64)> {
     let mask = generate_relevant_blockers(square, piece);
     let (_, moves) = if piece == Pieces::ROOK {
         generate_rook_moves(square)
@@ -47,17 +75,15 @@ fn find_magic(square: &Square, piece: usize, timeout_seconds: u64) -> (u8, u64)
         generate_bishop_moves(square)
     };
     let mut rng = rand::rng();
-    let mut best_shift = 0;
-    let mut best_magic = 0;
+    let mut best = None;
 
     'outer: for shift in 52..60 {
         let now = Instant::now();
         loop {
             let magic: u64 = rng.random::<u64>() & rng.random::<u64>() & rng.random::<u64>();
-            let magic_struct = Magic { mask, magic, shift };
+            let magic_struct = Magic::new(mask, magic, shift, 0);
             if verify_magic(&moves, &magic_struct).is_ok() {
-                best_shift = shift;
-                best_magic = magic;
+                best = Some((shift, magic));
                 break;
             }
 
@@ -67,7 +93,7 @@ fn find_magic(square: &Square, piece: usize, timeout_seconds: u64) -> (u8, u64)
         }
     }
 
-    (best_shift, best_magic)
+    best
 }
 
 struct IncompatibleMagic;
@@ -93,15 +119,240 @@ fn verify_magic(
     Ok(())
 }
 
-pub fn find_all_magics(piece: usize, timeout_seconds: u64) -> Vec<(u8, u64, u8)> {
-    let mut output: Vec<(u8, u64, u8)> = Vec::new();
-
+pub fn find_all_magics(piece: usize, timeout_seconds: u64) -> Result<Vec<Magic>, MagicError> {
     (0..64)
         .into_par_iter()
         .map(|i| {
-            let (best_shift, best_magic) = find_magic(&Square::new(i), piece, timeout_seconds);
-            (i, best_magic, best_shift)
+            let square = Square::new(i);
+            let (best_shift, best_magic) = find_magic(&square, piece, timeout_seconds)
+                .ok_or(MagicError::NoMagicFound(square))?;
+            let mask = generate_relevant_blockers(&square, piece);
+            Ok(Magic::new(mask, best_magic, best_shift, 0))
         })
-        .collect_into_vec(&mut output);
-    output
+        .collect()
+}
+
+/// Sets the offsets of all magics so they share one table. Biggest tables are
+/// placed first, each at the lowest offset where its entries don't clash with
+/// the ones already placed, so tables overlap where they can.
+/// Returns the table length.
+pub fn pack_magics(rook_magics: &mut [Magic], bishop_magics: &mut [Magic]) -> usize {
+    let mut all_magics: Vec<(usize, Square, &mut Magic)> = rook_magics
+        .iter_mut()
+        .enumerate()
+        .map(|(i, magic)| (Pieces::ROOK, Square::new(i as u8), magic))
+        .chain(
+            bishop_magics
+                .iter_mut()
+                .enumerate()
+                .map(|(i, magic)| (Pieces::BISHOP, Square::new(i as u8), magic)),
+        )
+        .collect();
+    all_magics.sort_by_key(|(_, _, magic)| magic.shift);
+
+    let mut table: Vec<Option<BitBoard>> = Vec::new();
+    for (piece, square, magic) in all_magics {
+        let entries = generate_magic_entries(&square, piece, magic);
+
+        let offset = (0..=table.len())
+            .find(|offset| {
+                entries.iter().all(|(index, moves)| {
+                    table
+                        .get(offset + index)
+                        .is_none_or(|entry| entry.is_none_or(|stored| stored == *moves))
+                })
+            })
+            .unwrap();
+
+        for (index, moves) in entries {
+            if table.len() <= offset + index {
+                table.resize(offset + index + 1, None);
+            }
+            table[offset + index] = Some(moves);
+        }
+        magic.offset = offset;
+    }
+
+    table.len()
+}
+
+/// Rust source of `magics.rs` for the given magics
+pub fn magics_source(rook_magics: &[Magic], bishop_magics: &[Magic]) -> String {
+    let mut source = String::new();
+    source.push_str("// Generated by the `magics` command, do not edit by hand.\n");
+    source.push_str("use super::magic_generation::Magic;\n");
+    source.push_str("use crate::types::bitboard::BitBoard;\n");
+
+    for (name, magics) in [
+        ("ROOK_MAGICS", rook_magics),
+        ("BISHOP_MAGICS", bishop_magics),
+    ] {
+        writeln!(source).unwrap();
+        writeln!(source, "pub const {}: [Magic; 64] = [", name).unwrap();
+        for magic in magics {
+            writeln!(source, "    Magic {{").unwrap();
+            writeln!(
+                source,
+                "        mask: BitBoard(0x{:x}),",
+                magic.mask.as_u64()
+            )
+            .unwrap();
+            writeln!(source, "        magic: 0x{:x},", magic.magic).unwrap();
+            writeln!(source, "        shift: {},", magic.shift).unwrap();
+            writeln!(source, "        offset: {},", magic.offset).unwrap();
+            writeln!(source, "    }},").unwrap();
+        }
+        writeln!(source, "];").unwrap();
+    }
+
+    source
+}
+
+#[derive(Debug)]
+pub enum MagicError {
+    Io(io::Error),
+    InvalidArgument(ArgError),
+    NoMagicFound(Square),
+}
+
+impl fmt::Display for MagicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagicError::Io(e) => write!(f, "IO error: {}", e),
+            MagicError::InvalidArgument(e) => write!(f, "{}", e),
+            MagicError::NoMagicFound(square) => {
+                write!(
+                    f,
+                    "No magic found for {} in time, try a longer timeout",
+                    square
+                )
+            }
+        }
+    }
+}
+
+impl Error for MagicError {}
+
+impl From<io::Error> for MagicError {
+    fn from(e: io::Error) -> Self {
+        MagicError::Io(e)
+    }
+}
+
+impl From<ArgError> for MagicError {
+    fn from(e: ArgError) -> Self {
+        MagicError::InvalidArgument(e)
+    }
+}
+
+/// `magics [timeout SECONDS] [keep]`
+///
+/// Searches new magics (or keeps the current ones with `keep`), packs them
+/// into one table and writes both `magics.rs` and the move files.
+pub fn magics_command(args: &[String]) -> Result<(), MagicError> {
+    let mut timeout_seconds = 10;
+    let mut keep = false;
+
+    let mut args = Args::new(args, "magics [timeout SECONDS] [keep]");
+    while let Some(arg) = args.next() {
+        match arg {
+            "timeout" => timeout_seconds = args.parse()?,
+            "keep" => keep = true,
+            other => return Err(ArgError::Invalid(other.to_owned()).into()),
+        }
+    }
+    // magics.rs and the slider table only work together, don't write one without the other
+    let magics_folder = Path::new(MAGICS_SOURCE_PATH).parent().unwrap();
+    for folder in [magics_folder, Path::new(MOVES_FOLDER_PATH)] {
+        if !folder.is_dir() {
+            return Err(MagicError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} does not exist", folder.display()),
+            )));
+        }
+    }
+
+    let (mut rook_magics, mut bishop_magics) = if keep {
+        (ROOK_MAGICS.to_vec(), BISHOP_MAGICS.to_vec())
+    } else {
+        (
+            find_all_magics(Pieces::ROOK, timeout_seconds)?,
+            find_all_magics(Pieces::BISHOP, timeout_seconds)?,
+        )
+    };
+
+    let unpacked_len: usize = rook_magics
+        .iter()
+        .chain(bishop_magics.iter())
+        .map(|magic| 1 << (64 - magic.shift))
+        .sum();
+    let table_len = pack_magics(&mut rook_magics, &mut bishop_magics);
+    println!(
+        "Slider table: {} entries ({} without overlapping)",
+        table_len, unpacked_len
+    );
+
+    // move files first, if they fail magics.rs still matches the old ones
+    generate_moves_with_magics(MOVES_FOLDER_PATH, &rook_magics, &bishop_magics)?;
+    fs::write(
+        MAGICS_SOURCE_PATH,
+        magics_source(&rook_magics, &bishop_magics),
+    )?;
+    println!(
+        "Wrote {} and the move files, rebuild to use them",
+        MAGICS_SOURCE_PATH
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_magics {
+    use super::*;
+    use crate::moves::move_mask_gen::{
+        create_blocker_boards, generate_slider_moves_from_blockers, generate_slider_table,
+        MoveGenMasks,
+    };
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+
+    #[test]
+    fn test_magics_match_slider_moves() {
+        let noise = BitBoard::new(0x9d39_247e_3377_6d41);
+
+        for (piece, magics) in [
+            (Pieces::ROOK, &ROOK_MAGICS),
+            (Pieces::BISHOP, &BISHOP_MAGICS),
+        ] {
+            for (i, magic) in magics.iter().enumerate() {
+                let square = Square::new(i as u8);
+                assert!(magic.mask == generate_relevant_blockers(&square, piece));
+
+                let blockers = create_blocker_boards(&magic.mask);
+                let expected_moves = generate_slider_moves_from_blockers(&square, &blockers, piece);
+                for (blocker, expected) in blockers.into_iter().zip(expected_moves) {
+                    // pieces outside of the mask must not change the index
+                    let board_mask = blocker | (noise & !magic.mask);
+                    let moves = MOVE_GEN_MASKS.slider_moves[magic.get_index(board_mask)];
+                    assert!(moves == expected, "square {} piece {}", square, piece);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_magics_source_is_up_to_date() {
+        let mut rook_magics = ROOK_MAGICS.to_vec();
+        let mut bishop_magics = BISHOP_MAGICS.to_vec();
+        pack_magics(&mut rook_magics, &mut bishop_magics);
+
+        assert!(rook_magics == ROOK_MAGICS.to_vec());
+        assert!(bishop_magics == BISHOP_MAGICS.to_vec());
+        assert_eq!(
+            magics_source(&ROOK_MAGICS, &BISHOP_MAGICS),
+            include_str!("magics.rs")
+        );
+        assert!(generate_slider_table(&ROOK_MAGICS, &BISHOP_MAGICS) == MOVE_GEN_MASKS.slider_moves);
+    }
 }
@@ -1,2 +1,4 @@
 pub mod magic_generation;
 pub mod magics;
+#[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+pub mod pext;
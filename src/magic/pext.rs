@@ -0,0 +1,157 @@
+use std::arch::x86_64::_pext_u64;
+
+use crate::{
+    moves::move_mask_gen::{
+        create_blocker_boards, generate_relevant_blockers, generate_slider_moves_from_blockers,
+    },
+    types::{bitboard::BitBoard, piece::Pieces, square::Square},
+};
+
+/// Slider moves indexed with PEXT instead of magics. Every square gets exactly
+/// 2^(relevant blockers) entries, so nothing is wasted.
+pub struct PextTables {
+    rook_masks: [u64; 64],
+    bishop_masks: [u64; 64],
+    rook_offsets: [usize; 64],
+    bishop_offsets: [usize; 64],
+    moves: Vec<BitBoard>,
+}
+
+impl PextTables {
+    /// None if the CPU has no BMI2
+    pub fn detect() -> Option<Self> {
+        if is_x86_feature_detected!("bmi2") {
+            Some(Self::generate())
+        } else {
+            None
+        }
+    }
+
+    fn generate() -> Self {
+        let mut tables = Self {
+            rook_masks: [0; 64],
+            bishop_masks: [0; 64],
+            rook_offsets: [0; 64],
+            bishop_offsets: [0; 64],
+            moves: Vec::new(),
+        };
+
+        for piece in [Pieces::ROOK, Pieces::BISHOP] {
+            for i in 0..64 {
+                let square = Square::new(i as u8);
+                let mask = generate_relevant_blockers(&square, piece);
+                // the nth blocker board has the bits of n spread over the mask,
+                // which is exactly what pext takes back out
+                let blockers = create_blocker_boards(&mask);
+                let offset = tables.moves.len();
+                tables.moves.extend(generate_slider_moves_from_blockers(
+                    &square, &blockers, piece,
+                ));
+
+                if piece == Pieces::ROOK {
+                    tables.rook_masks[i] = mask.as_u64();
+                    tables.rook_offsets[i] = offset;
+                } else {
+                    tables.bishop_masks[i] = mask.as_u64();
+                    tables.bishop_offsets[i] = offset;
+                }
+            }
+        }
+
+        tables
+    }
+
+    pub fn rook_moves(&self, square: &Square, occupancy: BitBoard) -> BitBoard {
+        let i = square.as_usize();
+        // SAFETY: tables are only created by `detect` when the CPU has BMI2
+        let index = unsafe { pext(occupancy.as_u64(), self.rook_masks[i]) };
+        self.moves[self.rook_offsets[i] + index as usize]
+    }
+
+    pub fn bishop_moves(&self, square: &Square, occupancy: BitBoard) -> BitBoard {
+        let i = square.as_usize();
+        // SAFETY: tables are only created by `detect` when the CPU has BMI2
+        let index = unsafe { pext(occupancy.as_u64(), self.bishop_masks[i]) };
+        self.moves[self.bishop_offsets[i] + index as usize]
+    }
+}
+
+#[target_feature(enable = "bmi2")]
+fn pext(value: u64, mask: u64) -> u64 {
+    _pext_u64(value, mask)
+}
+
+#[cfg(test)]
+mod test_pext {
+    use super::*;
+    use crate::{
+        board::Board, moves::move_mask_gen::MoveGenMasks, perft::perft,
+        utils::zobrist::ZobristHasher,
+    };
+    use once_cell::sync::Lazy;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn masks_pair() -> Option<(MoveGenMasks, MoveGenMasks)> {
+        let pext_masks = MoveGenMasks::load();
+        pext_masks.pext_tables.as_ref()?;
+
+        let mut magic_masks = MoveGenMasks::load();
+        magic_masks.pext_tables = None;
+        Some((pext_masks, magic_masks))
+    }
+
+    #[test]
+    fn test_pext_matches_magics() {
+        let Some((pext_masks, magic_masks)) = masks_pair() else {
+            return;
+        };
+        assert_eq!(pext_masks.slider_backend(), "pext");
+        assert_eq!(magic_masks.slider_backend(), "magic");
+
+        let mut rng = StdRng::seed_from_u64(0xb312);
+        for _ in 0..2000 {
+            let occupancy = BitBoard::new(rng.random::<u64>() & rng.random::<u64>());
+            for i in 0..64 {
+                let square = Square::new(i);
+                assert!(
+                    pext_masks.rook_moves(&square, occupancy)
+                        == magic_masks.rook_moves(&square, occupancy)
+                );
+                assert!(
+                    pext_masks.bishop_moves(&square, occupancy)
+                        == magic_masks.bishop_moves(&square, occupancy)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_pext_perft_equivalence() {
+        let Some((pext_masks, magic_masks)) = masks_pair() else {
+            return;
+        };
+        let positions = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                8902,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                97862,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 2812),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                9467,
+            ),
+        ];
+
+        for (fen, expected) in positions {
+            let mut board = Board::from_fen(fen, &HASHER).unwrap();
+            assert_eq!(perft(&mut board, &pext_masks, &HASHER, 3, None), expected);
+            assert_eq!(perft(&mut board, &magic_masks, &HASHER, 3, None), expected);
+        }
+    }
+}
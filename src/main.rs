@@ -2,16 +2,58 @@
 
 mod board;
 mod bots;
+mod datagen;
+mod epd;
 mod game;
 mod magic;
 mod moves;
 mod perft;
+mod pgn;
+mod tuner;
 mod types;
 mod utils;
 
+use std::{env, process};
+
 use game::UCIGame;
 
 fn main() {
-    let mut game = UCIGame::new();
-    game.uci_io_loop();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("datagen") => {
+            if let Err(e) = datagen::datagen_command(&args[1..]) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("epd") => {
+            if let Err(e) = epd::epd_command(&args[1..]) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("magics") => {
+            if let Err(e) = magic::magic_generation::magics_command(&args[1..]) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("perft") => {
+            if let Err(e) = perft::perft_command(&args[1..]) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("tune") => {
+            if let Err(e) = tuner::tune_command(&args[1..]) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        _ => {
+            let mut game = UCIGame::new();
+            game.uci_io_loop();
+        }
+    }
 }
@@ -0,0 +1,204 @@
+use crate::types::{bitboard::BitBoard, piece::Color, square::Square};
+
+pub const NORTH: usize = 0;
+pub const NORTH_EAST: usize = 1;
+pub const EAST: usize = 2;
+pub const SOUTH_EAST: usize = 3;
+pub const SOUTH: usize = 4;
+pub const SOUTH_WEST: usize = 5;
+pub const WEST: usize = 6;
+pub const NORTH_WEST: usize = 7;
+
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+
+/// (rank, file) step of each direction, indexed by the constants above
+const DIRECTION_STEPS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Squares attacked by `pawns` of `color`
+pub fn pawn_attacks(pawns: u64, color: usize) -> u64 {
+    if color == Color::WHITE {
+        ((pawns << 7) & !FILE_H) | ((pawns << 9) & !FILE_A)
+    } else {
+        ((pawns >> 9) & !FILE_H) | ((pawns >> 7) & !FILE_A)
+    }
+}
+
+/// Square relationships that do not depend on the position, for pins, discovered
+/// checks and SEE
+pub struct Geometry {
+    /// 64 * 64, squares strictly between two aligned squares
+    between: Vec<BitBoard>,
+    /// 64 * 64, the full line through two aligned squares, edge to edge
+    line: Vec<BitBoard>,
+    /// 8 * 64, squares from a square towards the edge, the square excluded
+    rays: Vec<BitBoard>,
+    chebyshev: Vec<u8>,
+    manhattan: Vec<u8>,
+}
+
+impl Geometry {
+    pub fn new() -> Self {
+        let mut rays = vec![BitBoard::zeros(); 8 * 64];
+        for direction in 0..8 {
+            for square in 0..64 {
+                rays[direction * 64 + square] = walk(square as u8, direction);
+            }
+        }
+
+        let mut between = vec![BitBoard::zeros(); 64 * 64];
+        let mut line = vec![BitBoard::zeros(); 64 * 64];
+        for a in 0..64_usize {
+            for direction in 0..8 {
+                let ray = rays[direction * 64 + a];
+                let opposite = rays[((direction + 4) % 8) * 64 + a];
+                for b in ray.get_ones() {
+                    let b = b.as_usize();
+                    between[a * 64 + b] = ray & rays[((direction + 4) % 8) * 64 + b];
+                    line[a * 64 + b] =
+                        ray | opposite | BitBoard::zeros_with_one_bit(&Square::new(a as u8));
+                }
+            }
+        }
+
+        let mut chebyshev = vec![0; 64 * 64];
+        let mut manhattan = vec![0; 64 * 64];
+        for a in 0..64_u8 {
+            for b in 0..64_u8 {
+                let (a_square, b_square) = (Square::new(a), Square::new(b));
+                let rank_distance = a_square.get_rank().abs_diff(b_square.get_rank());
+                let file_distance = a_square.get_file().abs_diff(b_square.get_file());
+                let index = a as usize * 64 + b as usize;
+                chebyshev[index] = rank_distance.max(file_distance);
+                manhattan[index] = rank_distance + file_distance;
+            }
+        }
+
+        Self {
+            between,
+            line,
+            rays,
+            chebyshev,
+            manhattan,
+        }
+    }
+
+    /// Empty unless `a` and `b` share a rank, file or diagonal
+    pub fn between(&self, a: &Square, b: &Square) -> BitBoard {
+        self.between[a.as_usize() * 64 + b.as_usize()]
+    }
+
+    /// Empty unless `a` and `b` share a rank, file or diagonal
+    pub fn line(&self, a: &Square, b: &Square) -> BitBoard {
+        self.line[a.as_usize() * 64 + b.as_usize()]
+    }
+
+    pub fn ray(&self, direction: usize, square: &Square) -> BitBoard {
+        self.rays[direction * 64 + square.as_usize()]
+    }
+
+    pub fn aligned(&self, a: &Square, b: &Square, c: &Square) -> bool {
+        self.line(a, b).read_square(c)
+    }
+
+    /// King moves between two squares
+    pub fn distance(&self, a: &Square, b: &Square) -> u8 {
+        self.chebyshev[a.as_usize() * 64 + b.as_usize()]
+    }
+
+    pub fn manhattan_distance(&self, a: &Square, b: &Square) -> u8 {
+        self.manhattan[a.as_usize() * 64 + b.as_usize()]
+    }
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn walk(square: u8, direction: usize) -> BitBoard {
+    let (rank_step, file_step) = DIRECTION_STEPS[direction];
+    let mut rank = (square / 8) as i8 + rank_step;
+    let mut file = (square % 8) as i8 + file_step;
+    let mut ray = BitBoard::zeros();
+    while (0..8).contains(&rank) && (0..8).contains(&file) {
+        ray.set_one(&Square::new((rank * 8 + file) as u8));
+        rank += rank_step;
+        file += file_step;
+    }
+    ray
+}
+
+#[cfg(test)]
+mod test_geometry {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn squares(names: &[&str]) -> BitBoard {
+        let mut bitboard = BitBoard::zeros();
+        for name in names {
+            bitboard.set_one(&Square::from_str(name).unwrap());
+        }
+        bitboard
+    }
+
+    #[test]
+    fn test_between_and_line() {
+        let geometry = Geometry::new();
+        let square = |name: &str| Square::from_str(name).unwrap();
+
+        assert!(
+            geometry.between(&square("a1"), &square("d4")) == squares(&["b2", "c3"])
+                && geometry.between(&square("d4"), &square("a1")) == squares(&["b2", "c3"])
+        );
+        assert!(
+            geometry.between(&square("e1"), &square("e8"))
+                == squares(&["e2", "e3", "e4", "e5", "e6", "e7"])
+        );
+        assert!(geometry.between(&square("e1"), &square("e2")).is_empty());
+        assert!(geometry.between(&square("a1"), &square("b3")).is_empty());
+
+        assert!(
+            geometry.line(&square("c3"), &square("e5"))
+                == squares(&["a1", "b2", "c3", "d4", "e5", "f6", "g7", "h8"])
+        );
+        assert!(
+            geometry.line(&square("h2"), &square("c2"))
+                == squares(&["a2", "b2", "c2", "d2", "e2", "f2", "g2", "h2"])
+        );
+        assert!(geometry.line(&square("a1"), &square("b3")).is_empty());
+        assert!(geometry.aligned(&square("b7"), &square("d5"), &square("h1")));
+        assert!(!geometry.aligned(&square("b7"), &square("d5"), &square("h2")));
+    }
+
+    #[test]
+    fn test_rays_and_distances() {
+        let geometry = Geometry::new();
+        let square = |name: &str| Square::from_str(name).unwrap();
+
+        assert!(geometry.ray(NORTH, &square("d5")) == squares(&["d6", "d7", "d8"]));
+        assert!(geometry.ray(SOUTH_WEST, &square("d5")) == squares(&["c4", "b3", "a2"]));
+        assert!(geometry.ray(EAST, &square("h4")).is_empty());
+        assert!(geometry.ray(NORTH_EAST, &square("a7")) == squares(&["b8"]));
+
+        assert_eq!(geometry.distance(&square("a1"), &square("h8")), 7);
+        assert_eq!(geometry.distance(&square("e4"), &square("g5")), 2);
+        assert_eq!(
+            geometry.manhattan_distance(&square("a1"), &square("h8")),
+            14
+        );
+        assert_eq!(geometry.manhattan_distance(&square("e4"), &square("g5")), 3);
+        assert_eq!(geometry.distance(&square("c6"), &square("c6")), 0);
+    }
+}
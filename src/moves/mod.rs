@@ -1,3 +1,5 @@
+pub mod geometry;
 pub mod move_mask_gen;
 pub mod moves_calculation;
 pub mod moves_utils;
+pub mod san;
@@ -1,23 +1,32 @@
 use bincode::{deserialize_from, serialize_into};
 use std::collections::HashMap;
 use std::fs::File;
+use std::io;
 
+#[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+use crate::magic::pext::PextTables;
 use crate::{
-    magic::magics::{BISHOP_MAGICS, ROOK_MAGICS},
+    magic::{
+        magic_generation::Magic,
+        magics::{BISHOP_MAGICS, ROOK_MAGICS},
+    },
+    moves::geometry::Geometry,
     types::{bitboard::BitBoard, piece::Pieces, square::Square},
 };
 
 const MOVES_FOLDER_PATH: &str = "./data/moves/";
 const KING_MOVES_FILE: &str = "king.bin";
 const KNIGHT_MOVES_FILE: &str = "knight.bin";
-const ROOK_MOVES_FILE: &str = "rook.bin";
-const BISHOP_MOVES_FILE: &str = "bishop.bin";
+const SLIDER_MOVES_FILE: &str = "slider.bin";
 
 pub struct MoveGenMasks {
     pub king_moves: Vec<BitBoard>,
     pub knight_moves: Vec<BitBoard>,
-    pub rook_moves: Vec<Vec<BitBoard>>,
-    pub bishop_moves: Vec<Vec<BitBoard>>,
+    /// rook and bishop moves for all squares, indexed by `Magic::get_index`
+    pub slider_moves: Vec<BitBoard>,
+    pub geometry: Geometry,
+    #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+    pub pext_tables: Option<PextTables>,
 }
 
 impl MoveGenMasks {
@@ -32,19 +41,57 @@ impl MoveGenMasks {
         let mut reader = File::open(format!("{}{}", path, KNIGHT_MOVES_FILE)).unwrap();
         let knight_moves = deserialize_from::<&mut File, Vec<BitBoard>>(&mut reader).unwrap();
 
-        let mut reader = File::open(format!("{}{}", path, ROOK_MOVES_FILE)).unwrap();
-        let rook_moves = deserialize_from::<&mut File, Vec<Vec<BitBoard>>>(&mut reader).unwrap();
-
-        let mut reader = File::open(format!("{}{}", path, BISHOP_MOVES_FILE)).unwrap();
-        let bishop_moves = deserialize_from::<&mut File, Vec<Vec<BitBoard>>>(&mut reader).unwrap();
+        let mut reader = File::open(format!("{}{}", path, SLIDER_MOVES_FILE)).unwrap();
+        let slider_moves = deserialize_from::<&mut File, Vec<BitBoard>>(&mut reader).unwrap();
 
         Self {
             king_moves,
             knight_moves,
-            rook_moves,
-            bishop_moves,
+            slider_moves,
+            geometry: Geometry::new(),
+            #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+            pext_tables: PextTables::detect(),
         }
     }
+
+    pub fn rook_moves(&self, square: &Square, occupancy: BitBoard) -> BitBoard {
+        #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+        if let Some(pext_tables) = &self.pext_tables {
+            return pext_tables.rook_moves(square, occupancy);
+        }
+        self.slider_moves[ROOK_MAGICS[square.as_usize()].get_index(occupancy)]
+    }
+
+    pub fn bishop_moves(&self, square: &Square, occupancy: BitBoard) -> BitBoard {
+        #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+        if let Some(pext_tables) = &self.pext_tables {
+            return pext_tables.bishop_moves(square, occupancy);
+        }
+        self.slider_moves[BISHOP_MAGICS[square.as_usize()].get_index(occupancy)]
+    }
+
+    /// squares attacked by `piece` on `square`, pawns are not handled here
+    pub fn piece_attacks(&self, piece: usize, square: &Square, occupancy: BitBoard) -> BitBoard {
+        match piece {
+            Pieces::QUEEN => {
+                self.rook_moves(square, occupancy) | self.bishop_moves(square, occupancy)
+            }
+            Pieces::ROOK => self.rook_moves(square, occupancy),
+            Pieces::BISHOP => self.bishop_moves(square, occupancy),
+            Pieces::KNIGHT => self.knight_moves[square.as_usize()],
+            Pieces::KING => self.king_moves[square.as_usize()],
+            _ => BitBoard::zeros(),
+        }
+    }
+
+    /// "pext" or "magic"
+    pub fn slider_backend(&self) -> &'static str {
+        #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+        if self.pext_tables.is_some() {
+            return "pext";
+        }
+        "magic"
+    }
 }
 
 fn generate_knight_moves(square: &Square) -> BitBoard {
@@ -125,30 +172,50 @@ pub fn generate_rook_moves(square: &Square) -> (BitBoard, HashMap<BitBoard, BitB
     (relevant_blockers, output)
 }
 
-pub fn generate_magic_moves(square: &Square, piece: usize) -> Vec<BitBoard> {
+/// (local index, moves) of every blocker subset for the magic on `square`
+pub fn generate_magic_entries(
+    square: &Square,
+    piece: usize,
+    magic: &Magic,
+) -> Vec<(usize, BitBoard)> {
     let relevant_blockers = generate_relevant_blockers(square, piece);
 
     let blockers_mask = create_blocker_boards(&relevant_blockers);
     let legal_moves_mask = generate_slider_moves_from_blockers(square, &blockers_mask, piece);
 
-    let magic = if piece == Pieces::ROOK {
-        &ROOK_MAGICS[square.as_usize()]
-    } else {
-        &BISHOP_MAGICS[square.as_usize()]
-    };
-    let mut output: Vec<BitBoard> = vec![BitBoard::zeros(); 2_usize.pow((64 - magic.shift) as u32)];
+    let mut output: HashMap<usize, BitBoard> = HashMap::with_capacity(blockers_mask.len());
 
     for (blockers, legal_moves) in blockers_mask.into_iter().zip(legal_moves_mask) {
-        let index = magic.get_index(blockers);
-        if ![legal_moves, BitBoard::zeros()].contains(&output[index]) {
+        let index = magic.get_local_index(blockers);
+        if *output.entry(index).or_insert(legal_moves) != legal_moves {
             panic!(
                 "Magic seems to be wrong for square {} and piece {}",
                 square, piece
             );
         }
-        output[index] = legal_moves;
     }
-    output
+    output.into_iter().collect()
+}
+
+/// Shared rook and bishop table, the magics must already be packed
+pub fn generate_slider_table(rook_magics: &[Magic], bishop_magics: &[Magic]) -> Vec<BitBoard> {
+    let mut table: Vec<BitBoard> = Vec::new();
+
+    for (piece, magics) in [(Pieces::ROOK, rook_magics), (Pieces::BISHOP, bishop_magics)] {
+        for (i, magic) in magics.iter().enumerate() {
+            for (index, moves) in generate_magic_entries(&Square::new(i as u8), piece, magic) {
+                let index = magic.offset + index;
+                if table.len() <= index {
+                    table.resize(index + 1, BitBoard::zeros());
+                }
+                if ![moves, BitBoard::zeros()].contains(&table[index]) {
+                    panic!("Magic offsets overlap for square {} and piece {}", i, piece);
+                }
+                table[index] = moves;
+            }
+        }
+    }
+    table
 }
 
 pub fn generate_bishop_moves(square: &Square) -> (BitBoard, HashMap<BitBoard, BitBoard>) {
@@ -164,7 +231,7 @@ pub fn generate_bishop_moves(square: &Square) -> (BitBoard, HashMap<BitBoard, Bi
     (relevant_blockers, output)
 }
 
-fn generate_slider_moves_from_blockers(
+pub fn generate_slider_moves_from_blockers(
     square: &Square,
     blockers: &[BitBoard],
     piece: usize,
@@ -285,7 +352,7 @@ pub fn generate_relevant_blockers(square: &Square, piece: usize) -> BitBoard {
     sliding_move_mask
 }
 
-fn create_blocker_boards(bitboard: &BitBoard) -> Vec<BitBoard> {
+pub fn create_blocker_boards(bitboard: &BitBoard) -> Vec<BitBoard> {
     let set_bits_indices = bitboard.get_ones();
     let n_patterns = 1 << set_bits_indices.len(); // 2^n
     let mut blocker_boards: Vec<BitBoard> = Vec::with_capacity(n_patterns);
@@ -303,10 +370,16 @@ fn create_blocker_boards(bitboard: &BitBoard) -> Vec<BitBoard> {
 }
 
 pub fn generate_moves() {
+    generate_moves_with_magics(MOVES_FOLDER_PATH, &ROOK_MAGICS, &BISHOP_MAGICS).unwrap();
+}
+
+pub fn generate_moves_with_magics(
+    folder: &str,
+    rook_magics: &[Magic],
+    bishop_magics: &[Magic],
+) -> io::Result<()> {
     let mut king_moves: Vec<BitBoard> = Vec::with_capacity(64);
     let mut knight_moves: Vec<BitBoard> = Vec::with_capacity(64);
-    let mut rook_moves: Vec<Vec<BitBoard>> = Vec::with_capacity(64);
-    let mut bishop_moves: Vec<Vec<BitBoard>> = Vec::with_capacity(64);
     for i in 0..64 {
         let square = Square::new(i);
 
@@ -315,31 +388,18 @@ pub fn generate_moves() {
 
         let knight_bb = generate_knight_moves(&square);
         knight_moves.push(knight_bb);
-
-        let rook_mapping = generate_magic_moves(&square, Pieces::ROOK);
-        rook_moves.push(rook_mapping);
-
-        let bishop_mapping = generate_magic_moves(&square, Pieces::BISHOP);
-        bishop_moves.push(bishop_mapping);
     }
 
-    save_move_file(KING_MOVES_FILE, king_moves);
-    save_move_file(KNIGHT_MOVES_FILE, knight_moves);
-    save_sliding_move_file(ROOK_MOVES_FILE, rook_moves);
-    save_sliding_move_file(BISHOP_MOVES_FILE, bishop_moves);
-}
-
-pub fn save_move_file(file_name: &str, moves: Vec<BitBoard>) {
-    let file = File::create(format!("{}{}", MOVES_FOLDER_PATH, file_name)).unwrap();
-    serialize_into(file, &moves).unwrap();
-}
-
-pub fn save_sliding_move_file(file_name: &str, moves: Vec<Vec<BitBoard>>) {
-    let file = File::create(format!("{}{}", MOVES_FOLDER_PATH, file_name)).unwrap();
-    serialize_into(file, &moves).unwrap();
+    save_move_file(folder, KING_MOVES_FILE, king_moves)?;
+    save_move_file(folder, KNIGHT_MOVES_FILE, knight_moves)?;
+    save_move_file(
+        folder,
+        SLIDER_MOVES_FILE,
+        generate_slider_table(rook_magics, bishop_magics),
+    )
 }
 
-pub fn read_sliding_moves() {
-    let mut reader = File::open(format!("{}{}", MOVES_FOLDER_PATH, KING_MOVES_FILE)).unwrap();
-    let _ = deserialize_from::<&mut File, Vec<Vec<BitBoard>>>(&mut reader).unwrap();
+pub fn save_move_file(folder: &str, file_name: &str, moves: Vec<BitBoard>) -> io::Result<()> {
+    let file = File::create(format!("{}{}", folder, file_name))?;
+    serialize_into(file, &moves).map_err(io::Error::other)
 }
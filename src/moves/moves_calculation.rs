@@ -1,15 +1,9 @@
-use crate::board::Board;
-use crate::magic::magics::{BISHOP_MAGICS, ROOK_MAGICS};
+use crate::board::{Board, IllegalMoveError};
 use crate::moves::move_mask_gen::MoveGenMasks;
-use crate::moves::moves_utils::Move;
+use crate::moves::moves_utils::{Move, MoveKind};
+use crate::types::state::{LONG, SHORT};
 use crate::types::{bitboard::BitBoard, piece::Color, piece::Pieces, square::Square};
 
-const CASTLING_WHITE_LONG_CHECKING: BitBoard = BitBoard(0x1C);
-const CASTLING_WHITE_LONG_BLOCKING: BitBoard = BitBoard(0xE);
-const CASTLING_WHITE_SHORT: BitBoard = BitBoard(0x60);
-const CASTLING_BLACK_LONG_CHECKING: BitBoard = BitBoard(0x1C00000000000000);
-const CASTLING_BLACK_LONG_BLOCKING: BitBoard = BitBoard(0xE00000000000000);
-const CASTLING_BLACK_SHORT: BitBoard = BitBoard(0x6000000000000000);
 const PROMOTION_PIECES: [usize; 4] = [Pieces::QUEEN, Pieces::KNIGHT, Pieces::ROOK, Pieces::BISHOP];
 
 // TODO: Clean this shit up
@@ -24,15 +18,16 @@ pub fn get_pawn_moves(square: Square, board: &Board) -> Vec<Move> {
     let new_square = square + direction * 8;
 
     if !(board.colors[0] | board.colors[1]).read_square(&new_square) {
-        let new_move = Move::from_origin_and_destination(&new_square, &square);
         if !(1..=6).contains(&new_square.get_rank()) {
             for piece in PROMOTION_PIECES {
-                let mut promotion_move = new_move.clone();
-                promotion_move.set_promotion(piece);
-                moves.push(promotion_move);
+                moves.push(Move::with_kind(
+                    &new_square,
+                    &square,
+                    MoveKind::Promotion(piece),
+                ));
             }
         } else {
-            moves.push(new_move);
+            moves.push(Move::from_origin_and_destination(&new_square, &square));
 
             let base_rank = if board.state.turn == Color::WHITE {
                 1
@@ -43,9 +38,7 @@ pub fn get_pawn_moves(square: Square, board: &Board) -> Vec<Move> {
             if square.get_rank() == base_rank {
                 let new_square = square + direction * 16;
                 if !(board.colors[0] | board.colors[1]).read_square(&new_square) {
-                    let mut new_move = Move::from_origin_and_destination(&new_square, &square);
-                    new_move.set_en_passant();
-                    moves.push(new_move);
+                    moves.push(Move::with_kind(&new_square, &square, MoveKind::DoublePush));
                 }
             }
         }
@@ -58,19 +51,28 @@ pub fn get_pawn_moves(square: Square, board: &Board) -> Vec<Move> {
             continue;
         }
         let attacking_square = Square::new((new_rank * 8 + new_file) as u8);
-        if board.colors[board.state.opponent].read_square(&attacking_square)
-            | board.check_en_passant(&attacking_square)
-        {
-            let new_move = Move::from_origin_and_destination(&attacking_square, &square);
+        if board.colors[board.state.opponent].read_square(&attacking_square) {
             if !(1..=6).contains(&new_rank) {
                 for piece in PROMOTION_PIECES {
-                    let mut promotion_move = new_move.clone();
-                    promotion_move.set_promotion(piece);
-                    moves.push(promotion_move);
+                    moves.push(Move::with_kind(
+                        &attacking_square,
+                        &square,
+                        MoveKind::PromotionCapture(piece),
+                    ));
                 }
             } else {
-                moves.push(new_move)
+                moves.push(Move::with_kind(
+                    &attacking_square,
+                    &square,
+                    MoveKind::Capture,
+                ))
             }
+        } else if board.check_en_passant(&attacking_square) {
+            moves.push(Move::with_kind(
+                &attacking_square,
+                &square,
+                MoveKind::EnPassant,
+            ))
         }
     }
 
@@ -82,29 +84,42 @@ pub fn get_knight_moves(
     move_gen_masks: &MoveGenMasks,
     board: &Board,
 ) -> Vec<Move> {
-    (move_gen_masks.knight_moves[square.as_usize()] & !board.colors[board.state.turn])
+    moves_to(
+        square,
+        move_gen_masks.knight_moves[square.as_usize()] & !board.colors[board.state.turn],
+        board,
+    )
+}
+
+/// Quiet moves and captures from `square` to every square of `destinations`
+fn moves_to(square: &Square, destinations: BitBoard, board: &Board) -> Vec<Move> {
+    let opponent = board.colors[board.state.opponent];
+    destinations
         .get_ones()
         .into_iter()
-        .map(|new_square| Move::from_origin_and_destination(&new_square, square))
+        .map(|new_square| {
+            if opponent.read_square(&new_square) {
+                Move::with_kind(&new_square, square, MoveKind::Capture)
+            } else {
+                Move::from_origin_and_destination(&new_square, square)
+            }
+        })
         .collect()
 }
 
 fn get_king_moves(square: &Square, move_gen_masks: &MoveGenMasks, board: &Board) -> Vec<Move> {
-    let mut moves: Vec<Move> = (move_gen_masks.king_moves[square.as_usize()]
-        & !board.colors[board.state.turn])
-        .get_ones()
-        .into_iter()
-        .map(|new_square| Move::from_origin_and_destination(&new_square, square))
-        .collect();
+    let mut moves = moves_to(
+        square,
+        move_gen_masks.king_moves[square.as_usize()] & !board.colors[board.state.turn],
+        board,
+    );
     moves.append(&mut get_castling_moves(square, board, move_gen_masks));
 
     moves
 }
 
 fn get_rook_move_mask(square: &Square, board: &Board, move_gen_masks: &MoveGenMasks) -> BitBoard {
-    let magic = &ROOK_MAGICS[square.as_usize()];
-    let index = magic.get_index(board.all_pieces);
-    move_gen_masks.rook_moves[square.as_usize()][index] & !board.colors[board.state.turn]
+    move_gen_masks.rook_moves(square, board.all_pieces) & !board.colors[board.state.turn]
 }
 
 fn get_slidy_boii_moves(
@@ -122,73 +137,80 @@ fn get_slidy_boii_moves(
             | get_bishop_move_mask(square, board, move_gen_masks)
     };
 
-    let all_desitnations = possible_moves.get_ones();
-    let mut all_moves: Vec<Move> = Vec::with_capacity(all_desitnations.len());
-
-    for new_square in all_desitnations {
-        all_moves.push(Move::from_origin_and_destination(&new_square, square))
-    }
-    all_moves
+    moves_to(square, possible_moves, board)
 }
 
 fn get_bishop_move_mask(square: &Square, board: &Board, move_gen_masks: &MoveGenMasks) -> BitBoard {
-    let magic = &BISHOP_MAGICS[square.as_usize()];
-    let index = magic.get_index(board.all_pieces);
-    move_gen_masks.bishop_moves[square.as_usize()][index] & !board.colors[board.state.turn]
+    move_gen_masks.bishop_moves(square, board.all_pieces) & !board.colors[board.state.turn]
 }
 
+/// Squares between `a` and `b` on the same rank, both included
+fn rank_span(a: &Square, b: &Square) -> BitBoard {
+    let (low, high) = if a.as_u8() < b.as_u8() {
+        (a.as_u8(), b.as_u8())
+    } else {
+        (b.as_u8(), a.as_u8())
+    };
+    BitBoard(((1_u64 << (high - low + 1)) - 1) << low)
+}
+
+/// Works for any Chess960 setup: every square the king and rook travel over must be
+/// empty apart from the two of them, and the king may not pass an attacked square
 fn get_castling_moves(square: &Square, board: &Board, move_gen_masks: &MoveGenMasks) -> Vec<Move> {
-    let (can_short, can_long) = board.state.castling.can_castle(board.state.turn);
-    let mut castling_moves: Vec<Move> = Vec::with_capacity(1);
-    if can_short {
-        let mask = if board.state.turn == Color::WHITE {
-            CASTLING_WHITE_SHORT
-        } else {
-            CASTLING_BLACK_SHORT
-        };
-        if (mask & board.all_pieces).is_empty()
-            & mask
-                .get_ones()
-                .iter()
-                .all(|square_to_check| !is_square_in_check(square_to_check, board, move_gen_masks))
-            & !is_square_in_check(square, board, move_gen_masks)
-        {
-            let (origin, destination) = if board.state.turn == Color::WHITE {
-                (Square::new(4), Square::new(6))
-            } else {
-                (Square::new(60), Square::new(62))
-            };
-            let mut castling_move = Move::from_origin_and_destination(&destination, &origin);
-            castling_move.set_castling();
-            castling_moves.push(castling_move);
-        }
+    [SHORT, LONG]
+        .into_iter()
+        .filter_map(|side| get_castling_candidate(square, board, side))
+        .filter(|castling_move| castling_obstacle(castling_move, board, move_gen_masks).is_none())
+        .collect()
+}
+
+/// Castling move towards `side` if the right is still there and its rook is in place,
+/// the path is not checked
+pub fn get_castling_candidate(square: &Square, board: &Board, side: usize) -> Option<Move> {
+    let turn = board.state.turn;
+    let rook_square = board.state.castling.rook_square(turn, side);
+    if !board.state.castling.has_right(turn, side)
+        || !board.pieces[turn][Pieces::ROOK].read_square(&rook_square)
+        || rook_square.get_rank() != square.get_rank()
+    {
+        return None;
     }
 
-    if can_long {
-        let (checking_mask, blocking_mask) = if board.state.turn == Color::WHITE {
-            (CASTLING_WHITE_LONG_CHECKING, CASTLING_WHITE_LONG_BLOCKING)
-        } else {
-            (CASTLING_BLACK_LONG_CHECKING, CASTLING_BLACK_LONG_BLOCKING)
-        };
-
-        if (blocking_mask & board.all_pieces).is_empty()
-            & checking_mask
-                .get_ones()
-                .iter()
-                .all(|square_to_check| !is_square_in_check(square_to_check, board, move_gen_masks))
-        {
-            let (origin, destination) = if board.state.turn == Color::WHITE {
-                (Square::new(4), Square::new(2))
-            } else {
-                (Square::new(60), Square::new(58))
-            };
-            let mut castling_move = Move::from_origin_and_destination(&destination, &origin);
-            castling_move.set_castling();
-            castling_moves.push(castling_move);
-        }
+    let kind = if side == SHORT {
+        MoveKind::ShortCastle
+    } else {
+        MoveKind::LongCastle
+    };
+    Some(Move::with_kind(&rook_square, square, kind))
+}
+
+/// Why a castling candidate cannot be played, `None` if it can
+pub fn castling_obstacle(
+    castling_move: &Move,
+    board: &Board,
+    move_gen_masks: &MoveGenMasks,
+) -> Option<IllegalMoveError> {
+    let king_square = castling_move.get_origin();
+    let rook_square = castling_move.get_destination();
+    let (king_destination, rook_destination) = castling_move.castling_destinations();
+
+    let blockers = board.all_pieces
+        ^ BitBoard::zeros_with_one_bit(&king_square)
+        ^ BitBoard::zeros_with_one_bit(&rook_square);
+    let king_path = rank_span(&king_square, &king_destination);
+    if !((king_path | rank_span(&rook_square, &rook_destination)) & blockers).is_empty() {
+        return Some(IllegalMoveError::BlockedPath);
     }
 
-    castling_moves
+    if king_path
+        .get_ones()
+        .iter()
+        .any(|square_to_check| is_square_in_check(square_to_check, board, move_gen_masks))
+    {
+        return Some(IllegalMoveError::CastlingThroughCheck);
+    }
+
+    None
 }
 
 pub fn is_square_in_check(square: &Square, board: &Board, move_gen_masks: &MoveGenMasks) -> bool {
@@ -265,6 +287,38 @@ pub fn get_all_moves(board: &Board, move_gen_masks: &MoveGenMasks) -> Vec<Move>
     all_moves
 }
 
+/// Captures only, en passant and capturing promotions included
+pub fn get_all_captures(board: &Board, move_gen_masks: &MoveGenMasks) -> Vec<Move> {
+    let targets = board.colors[board.state.opponent];
+    let mut captures: Vec<Move> = Vec::with_capacity(32);
+    for (piece, piece_board) in board.pieces[board.state.turn].iter().enumerate() {
+        for square in piece_board.get_ones() {
+            let destinations = match piece {
+                Pieces::PAWN => {
+                    captures.extend(
+                        get_pawn_moves(square, board)
+                            .into_iter()
+                            .filter(|the_move| the_move.is_capture()),
+                    );
+                    continue;
+                }
+                Pieces::BISHOP => move_gen_masks.bishop_moves(&square, board.all_pieces),
+                Pieces::KNIGHT => move_gen_masks.knight_moves[square.as_usize()],
+                Pieces::ROOK => move_gen_masks.rook_moves(&square, board.all_pieces),
+                Pieces::KING => move_gen_masks.king_moves[square.as_usize()],
+                Pieces::QUEEN => {
+                    move_gen_masks.rook_moves(&square, board.all_pieces)
+                        | move_gen_masks.bishop_moves(&square, board.all_pieces)
+                }
+                _ => panic!("That is a weird piece"),
+            };
+            captures.append(&mut moves_to(&square, destinations & targets, board));
+        }
+    }
+
+    captures
+}
+
 #[cfg(test)]
 mod test_move_calculation {
     use std::str::FromStr;
@@ -288,9 +342,9 @@ mod test_move_calculation {
             moves[0],
             Move::from_origin_and_destination(&Square::new(16), &square)
         );
-        let mut new_move = Move::from_origin_and_destination(&Square::new(24), &square);
-        new_move.set_en_passant();
+        let new_move = Move::with_kind(&Square::new(24), &square, MoveKind::DoublePush);
         assert_eq!(moves[1], new_move);
+        assert!(moves[1].is_double_push());
     }
 
     #[test]
@@ -305,10 +359,13 @@ mod test_move_calculation {
             moves[0],
             Move::from_origin_and_destination(&Square::from_str("b6").unwrap(), &square)
         );
-        let mut new_move =
-            Move::from_origin_and_destination(&Square::from_str("b5").unwrap(), &square);
-        new_move.set_en_passant();
+        let new_move = Move::with_kind(
+            &Square::from_str("b5").unwrap(),
+            &square,
+            MoveKind::DoublePush,
+        );
         assert_eq!(moves[1], new_move);
+        assert!(moves[1].is_double_push());
     }
 
     #[test]
@@ -348,14 +405,16 @@ mod test_move_calculation {
         let moves = get_castling_moves(&king_square, &board, &move_gen_masks);
         assert_eq!(moves.len(), 2);
         let short = moves.first().unwrap();
-        assert_eq!(short.get_destination().to_string(), "g8");
+        assert_eq!(short.get_destination().to_string(), "h8");
+        assert_eq!(short.to_long_string(), "e8g8");
         assert_eq!(short.get_origin().to_string(), "e8");
-        assert!(short.special_move() == 3);
+        assert!(short.is_castling());
 
         let long = moves.get(1).unwrap();
-        assert_eq!(long.get_destination().to_string(), "c8");
+        assert_eq!(long.get_destination().to_string(), "a8");
+        assert_eq!(long.to_long_string(), "e8c8");
         assert_eq!(long.get_origin().to_string(), "e8");
-        assert!(short.special_move() == 3);
+        assert!(short.is_castling());
     }
 
     #[test]
@@ -371,14 +430,16 @@ mod test_move_calculation {
         let moves = get_castling_moves(&king_square, &board, &move_gen_masks);
         assert_eq!(moves.len(), 2);
         let short = moves.first().unwrap();
-        assert_eq!(short.get_destination().to_string(), "g1");
+        assert_eq!(short.get_destination().to_string(), "h1");
+        assert_eq!(short.to_long_string(), "e1g1");
         assert_eq!(short.get_origin().to_string(), "e1");
-        assert!(short.special_move() == 3);
+        assert!(short.is_castling());
 
         let long = moves.get(1).unwrap();
-        assert_eq!(long.get_destination().to_string(), "c1");
+        assert_eq!(long.get_destination().to_string(), "a1");
+        assert_eq!(long.to_long_string(), "e1c1");
         assert_eq!(long.get_origin().to_string(), "e1");
-        assert!(short.special_move() == 3);
+        assert!(short.is_castling());
     }
 
     #[test]
@@ -390,4 +451,25 @@ mod test_move_calculation {
         let moves = get_all_moves(&board, &move_gen_masks);
         assert_eq!(moves.len(), 20);
     }
+
+    #[test]
+    fn test_get_all_captures() {
+        let move_gen_masks = MoveGenMasks::load();
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &HASHER).unwrap();
+            let captures = get_all_captures(&board, &move_gen_masks);
+            let mut expected: Vec<Move> = get_all_moves(&board, &move_gen_masks)
+                .into_iter()
+                .filter(|the_move| the_move.is_capture())
+                .collect();
+            assert_eq!(captures.len(), expected.len(), "{}", fen);
+            expected.retain(|the_move| !captures.contains(the_move));
+            assert!(expected.is_empty(), "{}", fen);
+        }
+    }
 }
@@ -1,19 +1,91 @@
 use crate::{
-    types::{bitboard::BitBoard, piece::Pieces, square::Square, state::Castling},
+    board::Board,
+    bots::pesto::PestoScore,
+    types::{
+        bitboard::BitBoard,
+        piece::Pieces,
+        square::Square,
+        state::{Castling, LONG, SHORT},
+    },
     utils::zobrist::ZobristHash,
 };
 use std::{
     fmt::{self, Display},
+    hash::{Hash, Hasher},
     str::FromStr,
 };
 
-#[derive(Clone, Copy, Hash)]
+#[derive(Clone, Copy)]
 /// bit 0..5     destination
 /// bit 6..11    origin
-/// bit 12..13   promotion piece (0 queen, 1 rook, 2 bishop, 3 knight)
-/// bit 14..15   1 - promotion flag, 2 en passant, 3 castling
-pub struct Move(pub u16);
+/// bit 12..15   kind, see `MoveKind`
+///
+/// castling moves are encoded as the king capturing its own rook, so they stay
+/// unambiguous in Chess960. Moves are only built through the constructors below,
+/// which never write the unused kinds 6 and 7
+pub struct Move(u16);
 
+/// What a move does besides moving a piece, set by the move generator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    Quiet,
+    DoublePush,
+    Capture,
+    EnPassant,
+    ShortCastle,
+    LongCastle,
+    /// promotion piece, `Pieces::QUEEN` to `Pieces::KNIGHT`
+    Promotion(usize),
+    PromotionCapture(usize),
+}
+
+impl MoveKind {
+    /// 0 quiet, 1 double push, 2 short castle, 3 long castle, 4 capture, 5 en passant,
+    /// 8 + piece promotion, 12 + piece promotion capture
+    fn to_bits(self) -> u16 {
+        match self {
+            MoveKind::Quiet => 0,
+            MoveKind::DoublePush => 1,
+            MoveKind::ShortCastle => 2,
+            MoveKind::LongCastle => 3,
+            MoveKind::Capture => 4,
+            MoveKind::EnPassant => 5,
+            MoveKind::Promotion(piece) => 8 | promotion_bits(piece),
+            MoveKind::PromotionCapture(piece) => 12 | promotion_bits(piece),
+        }
+    }
+
+    /// Only reads bits written by `to_bits`
+    fn from_bits(bits: u16) -> Self {
+        match bits {
+            0 => MoveKind::Quiet,
+            1 => MoveKind::DoublePush,
+            2 => MoveKind::ShortCastle,
+            3 => MoveKind::LongCastle,
+            4 => MoveKind::Capture,
+            5 => MoveKind::EnPassant,
+            8..=11 => MoveKind::Promotion((bits & 3) as usize),
+            12..=15 => MoveKind::PromotionCapture((bits & 3) as usize),
+            _ => panic!("Invalid move kind {}", bits),
+        }
+    }
+}
+
+fn promotion_bits(piece: usize) -> u16 {
+    match piece {
+        (0..=3) => piece as u16,
+        _ => panic!("Cannot promote to king nor pawn"),
+    }
+}
+
+/// State a null move overwrites, given back to `Board::unmake_null_move`
+pub struct NullMoveHelper {
+    pub prev_en_passant: Option<Square>,
+    pub prev_hash: ZobristHash,
+    pub prev_null_move_start: usize,
+}
+
+#[derive(Clone)]
 pub struct UnmakeMoveHelper {
     pub origin: Square,
     pub destination: Square,
@@ -22,9 +94,13 @@ pub struct UnmakeMoveHelper {
     pub capture: Option<usize>,
     pub prev_en_passant: Option<Square>,
     pub prev_hash: ZobristHash,
+    pub prev_pawn_key: ZobristHash,
+    pub prev_material_key: ZobristHash,
+    pub prev_non_pawn_keys: [ZobristHash; 2],
     pub prev_halfmove: u8,
     pub prev_castling: Castling,
-    pub special_move: u8,
+    pub prev_pesto: PestoScore,
+    pub the_move: Move,
 }
 
 impl Move {
@@ -32,25 +108,13 @@ impl Move {
         Self(0)
     }
 
+    /// Quiet move
     pub fn from_origin_and_destination(destination: &Square, origin: &Square) -> Self {
         Self(destination.as_u16() | (origin.as_u16() << 6))
     }
 
-    pub fn set_castling(&mut self) {
-        self.0 |= 0xc000 // 3 << 14
-    }
-
-    pub fn set_promotion(&mut self, piece_type: usize) {
-        let piece_value: u16 = match piece_type {
-            (0..=3) => piece_type as u16,
-            _ => panic!("Cannot promote to king nor pawn"),
-        };
-        self.0 |= piece_value << 12;
-        self.0 |= 0x4000; // 1 << 14
-    }
-
-    pub fn set_en_passant(&mut self) {
-        self.0 |= 0x8000; // 1 << 15
+    pub fn with_kind(destination: &Square, origin: &Square, kind: MoveKind) -> Self {
+        Self(destination.as_u16() | (origin.as_u16() << 6) | (kind.to_bits() << 12))
     }
 
     pub fn get_destination(&self) -> Square {
@@ -61,64 +125,254 @@ impl Move {
         Square::new(((self.0 & 0xfc0) >> 6) as u8) // 0b111111000000
     }
 
-    ///1 - promotion flag, 2 en passant, 3 castling
-    pub fn special_move(&self) -> u16 {
-        self.0 >> 14
+    pub fn kind(&self) -> MoveKind {
+        MoveKind::from_bits(self.0 >> 12)
+    }
+
+    pub fn promotion_piece(&self) -> Option<usize> {
+        match self.kind() {
+            MoveKind::Promotion(piece) | MoveKind::PromotionCapture(piece) => Some(piece),
+            _ => None,
+        }
     }
 
-    pub fn get_promotion_piece(&self) -> usize {
-        ((self.0 & 0x3000) >> 12) as usize
+    /// En passant included
+    pub fn is_capture(&self) -> bool {
+        self.0 & 0x4000 != 0 // kinds 4, 5 and 12..15
     }
+
+    pub fn is_promotion(&self) -> bool {
+        self.0 & 0x8000 != 0 // kinds 8..15
+    }
+
+    /// Captures and promotions, the moves searched by quiescence
+    pub fn is_tactical(&self) -> bool {
+        self.is_capture() || self.is_promotion()
+    }
+
+    pub fn is_castling(&self) -> bool {
+        self.0 >> 13 == 1 // kinds 2 and 3
+    }
+
+    pub fn is_en_passant(&self) -> bool {
+        self.0 >> 12 == 5
+    }
+
+    pub fn is_double_push(&self) -> bool {
+        self.0 >> 12 == 1
+    }
+
+    /// Castling with the rook on the king's right, false for other moves
+    pub fn is_short_castling(&self) -> bool {
+        self.0 >> 12 == 2
+    }
+
+    /// (king, rook) destinations of a castling move, g and f or c and d files
+    pub fn castling_destinations(&self) -> (Square, Square) {
+        let rank = self.get_origin().get_rank() * 8;
+        if self.is_short_castling() {
+            (Square::new(rank + 6), Square::new(rank + 5))
+        } else {
+            (Square::new(rank + 2), Square::new(rank + 3))
+        }
+    }
+
+    /// Origin, destination and promotion only, use `from_uci` for the kind
     pub fn from_long_str(input: &str) -> Self {
         let origin = Square::from_str(&input[0..=1]).unwrap();
         let destination = Square::from_str(&input[2..=3]).unwrap();
 
-        let mut output = Move::from_origin_and_destination(&destination, &origin);
-
-        if let Some(promotion_piece) = input.chars().nth(4) {
-            output.set_promotion(match promotion_piece {
-                'q' => Pieces::QUEEN,
-                'n' => Pieces::KNIGHT,
-                'r' => Pieces::ROOK,
-                'b' => Pieces::BISHOP,
-                _ => panic!("Unexpected promotion piece {}", promotion_piece),
-            });
+        match input.chars().nth(4) {
+            Some(promotion_piece) => Move::with_kind(
+                &destination,
+                &origin,
+                MoveKind::Promotion(char_to_promotion(promotion_piece)),
+            ),
+            None => Move::from_origin_and_destination(&destination, &origin),
         }
+    }
 
-        output
+    /// `from_long_str` for untrusted input, `None` unless it looks like e2e4 or e7e8q
+    pub fn parse_long_str(input: &str) -> Option<Self> {
+        let bytes = input.as_bytes();
+        let is_square =
+            |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+        let valid = match bytes {
+            [a, b, c, d] => is_square(*a, *b) && is_square(*c, *d),
+            [a, b, c, d, promotion] => {
+                is_square(*a, *b) && is_square(*c, *d) && b"qrbn".contains(promotion)
+            }
+            _ => false,
+        };
+        valid.then(|| Self::from_long_str(input))
     }
 
-    pub fn to_long_string(&self) -> String {
+    /// UCI move with its kind read from `board`, castling may be written either way.
+    /// Legality is not checked
+    pub fn from_uci(input: &str, board: &Board) -> Option<Self> {
+        let parsed = Self::parse_long_str(input)?;
+        Self::from_board(
+            &parsed.get_origin(),
+            &parsed.get_destination(),
+            parsed.promotion_piece(),
+            board,
+        )
+    }
+
+    /// Polyglot book encoding: squares as here, promotion 1 knight to 4 queen in bits
+    /// 12..14 and castling as king takes rook
+    pub fn to_polyglot(self) -> u16 {
+        let promotion = match self.promotion_piece() {
+            Some(Pieces::KNIGHT) => 1,
+            Some(Pieces::BISHOP) => 2,
+            Some(Pieces::ROOK) => 3,
+            Some(Pieces::QUEEN) => 4,
+            _ => 0,
+        };
+        (self.0 & 0xfff) | (promotion << 12)
+    }
+
+    pub fn from_polyglot(polyglot_move: u16, board: &Board) -> Option<Self> {
+        let promotion = match (polyglot_move >> 12) & 7 {
+            0 => None,
+            1 => Some(Pieces::KNIGHT),
+            2 => Some(Pieces::BISHOP),
+            3 => Some(Pieces::ROOK),
+            4 => Some(Pieces::QUEEN),
+            _ => return None,
+        };
+        let squares = Move(polyglot_move & 0xfff);
+        Self::from_board(
+            &squares.get_origin(),
+            &squares.get_destination(),
+            promotion,
+            board,
+        )
+    }
+
+    /// The kind a move gets from the generator, `None` without a piece of the side to move
+    /// on `origin`
+    fn from_board(
+        origin: &Square,
+        destination: &Square,
+        promotion: Option<usize>,
+        board: &Board,
+    ) -> Option<Self> {
+        let turn = board.state.turn;
+        let piece = board
+            .get_piece_on_square(origin)
+            .filter(|piece| piece.color() == turn)?
+            .piece();
+        let is_capture = board.colors[board.state.opponent].read_square(destination);
+
+        let kind = match (piece, promotion) {
+            (Pieces::PAWN, Some(promotion_piece)) if is_capture => {
+                MoveKind::PromotionCapture(promotion_piece)
+            }
+            (Pieces::PAWN, Some(promotion_piece)) => MoveKind::Promotion(promotion_piece),
+            (_, Some(_)) => return None,
+            (Pieces::PAWN, None) if origin.get_file() != destination.get_file() && !is_capture => {
+                MoveKind::EnPassant
+            }
+            (Pieces::PAWN, None) if origin.get_rank().abs_diff(destination.get_rank()) == 2 => {
+                MoveKind::DoublePush
+            }
+            (Pieces::KING, None) => {
+                let side = if destination.get_file() > origin.get_file() {
+                    SHORT
+                } else {
+                    LONG
+                };
+                let kind = if side == SHORT {
+                    MoveKind::ShortCastle
+                } else {
+                    MoveKind::LongCastle
+                };
+                let castling = board.state.castling;
+                let rook_square = castling.rook_square(turn, side);
+                if board.pieces[turn][Pieces::ROOK].read_square(destination) {
+                    return Some(Move::with_kind(destination, origin, kind));
+                }
+                // two square king move of standard chess notation
+                if castling.has_right(turn, side)
+                    && origin.get_file().abs_diff(destination.get_file()) == 2
+                    && origin.get_rank() == destination.get_rank()
+                {
+                    return Some(Move::with_kind(&rook_square, origin, kind));
+                }
+                if is_capture {
+                    MoveKind::Capture
+                } else {
+                    MoveKind::Quiet
+                }
+            }
+            _ if is_capture => MoveKind::Capture,
+            _ => MoveKind::Quiet,
+        };
+
+        Some(Move::with_kind(destination, origin, kind))
+    }
+
+    pub fn to_long_string(self) -> String {
+        self.to_uci_string(false)
+    }
+
+    /// Castling is written as king takes rook in Chess960 and as the two square
+    /// king move otherwise
+    pub fn to_uci_string(self, chess960: bool) -> String {
         let origin = self.get_origin();
-        let destination = self.get_destination();
+        let destination = if self.is_castling() && !chess960 {
+            self.castling_destinations().0
+        } else {
+            self.get_destination()
+        };
         let mut output = "".to_owned();
         output.push_str(&origin.to_string());
         output.push_str(&destination.to_string());
 
-        if self.special_move() != 1 {
-            return output;
+        if let Some(promotion_piece) = self.promotion_piece() {
+            output.push(promotion_to_char(promotion_piece));
         }
-
-        let promotion_piece = match self.get_promotion_piece() {
-            Pieces::QUEEN => "q",
-            Pieces::KNIGHT => "n",
-            Pieces::ROOK => "r",
-            Pieces::BISHOP => "b",
-            _ => panic!("Unexpected promotion piece {}", self.get_promotion_piece()),
-        };
-        output.push_str(promotion_piece);
         output
     }
 }
 
+fn char_to_promotion(promotion_char: char) -> usize {
+    match promotion_char {
+        'q' => Pieces::QUEEN,
+        'n' => Pieces::KNIGHT,
+        'r' => Pieces::ROOK,
+        'b' => Pieces::BISHOP,
+        _ => panic!("Unexpected promotion piece {}", promotion_char),
+    }
+}
+
+fn promotion_to_char(piece: usize) -> char {
+    match piece {
+        Pieces::QUEEN => 'q',
+        Pieces::KNIGHT => 'n',
+        Pieces::ROOK => 'r',
+        Pieces::BISHOP => 'b',
+        _ => panic!("Unexpected promotion piece {}", piece),
+    }
+}
+
+/// Moves are equal when they read the same in UCI, the kind follows from the position
 impl PartialEq for Move {
     fn eq(&self, other: &Self) -> bool {
-        (self.0 & 0x3FFF) == (other.0 & 0x3FFF)
+        (self.0 & 0xfff) == (other.0 & 0xfff) && self.promotion_piece() == other.promotion_piece()
     }
 }
 
 impl Eq for Move {}
 
+impl Hash for Move {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 & 0xfff).hash(state);
+        self.promotion_piece().hash(state);
+    }
+}
+
 impl Default for Move {
     fn default() -> Self {
         Self::new()
@@ -138,3 +392,148 @@ impl Display for Move {
         write!(f, "{}", self.to_long_string())
     }
 }
+
+#[cfg(test)]
+mod test_moves_utils {
+    use once_cell::sync::Lazy;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{moves::move_mask_gen::MoveGenMasks, utils::zobrist::ZobristHasher};
+
+    use super::*;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    #[test]
+    fn test_move_kind_encoding() {
+        let origin = Square::from_str("b7").unwrap();
+        let destination = Square::from_str("a8").unwrap();
+        let mut kinds = vec![
+            MoveKind::Quiet,
+            MoveKind::DoublePush,
+            MoveKind::Capture,
+            MoveKind::EnPassant,
+            MoveKind::ShortCastle,
+            MoveKind::LongCastle,
+        ];
+        for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+            kinds.push(MoveKind::Promotion(piece));
+            kinds.push(MoveKind::PromotionCapture(piece));
+        }
+
+        for kind in kinds {
+            let the_move = Move::with_kind(&destination, &origin, kind);
+            assert_eq!(the_move.kind(), kind);
+            assert_eq!(the_move.get_origin(), origin);
+            assert_eq!(the_move.get_destination(), destination);
+        }
+
+        let capture = Move::with_kind(&destination, &origin, MoveKind::PromotionCapture(0));
+        assert!(capture.is_capture() && capture.is_promotion() && capture.is_tactical());
+        let en_passant = Move::with_kind(&destination, &origin, MoveKind::EnPassant);
+        assert!(en_passant.is_capture() && !en_passant.is_promotion());
+        let quiet = Move::from_origin_and_destination(&destination, &origin);
+        assert!(!quiet.is_tactical());
+        assert_eq!(
+            Move::from_long_str("b7a8n").promotion_piece(),
+            Some(Pieces::KNIGHT)
+        );
+    }
+
+    #[test]
+    fn test_uci_and_polyglot_kinds() {
+        let board = Board::from_fen(
+            "r3k2r/p2pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq c6 0 2",
+            &HASHER,
+        )
+        .unwrap();
+        let kind_of = |uci: &str| Move::from_uci(uci, &board).unwrap().kind();
+
+        assert_eq!(kind_of("a2a3"), MoveKind::Quiet);
+        assert_eq!(kind_of("a2a4"), MoveKind::DoublePush);
+        assert_eq!(kind_of("e5f7"), MoveKind::Capture);
+        assert_eq!(kind_of("d5c6"), MoveKind::EnPassant);
+        assert_eq!(kind_of("e1g1"), MoveKind::ShortCastle);
+        assert_eq!(kind_of("e1h1"), MoveKind::ShortCastle);
+        assert_eq!(kind_of("e1c1"), MoveKind::LongCastle);
+        assert_eq!(
+            Move::from_uci("e1g1", &board).unwrap().to_uci_string(true),
+            "e1h1"
+        );
+        assert!(Move::from_uci("a7a6", &board).is_none());
+
+        // Polyglot: origin and destination as rank * 8 + file, castling as king takes rook
+        assert_eq!(
+            Move::from_uci("e2e4", &board).unwrap().to_polyglot(),
+            0x031c
+        );
+        assert_eq!(
+            Move::from_uci("e1g1", &board).unwrap().to_polyglot(),
+            0x0107
+        );
+        assert_eq!(
+            Move::from_polyglot(0x0107, &board).unwrap().kind(),
+            MoveKind::ShortCastle
+        );
+
+        let promotion = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &HASHER).unwrap();
+        let knight_capture = Move::from_uci("a7b8n", &promotion).unwrap();
+        assert_eq!(
+            knight_capture.kind(),
+            MoveKind::PromotionCapture(Pieces::KNIGHT)
+        );
+        assert_eq!(knight_capture.to_polyglot(), 0x1000 | (48 << 6) | 57);
+        assert_eq!(
+            Move::from_uci("a7a8q", &promotion).unwrap().kind(),
+            MoveKind::Promotion(Pieces::QUEEN)
+        );
+    }
+
+    #[test]
+    fn test_conversions_are_lossless() {
+        let mut rng = StdRng::seed_from_u64(0x4d0e);
+        let start_fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        ];
+
+        for start_fen in start_fens {
+            for _ in 0..4 {
+                let mut board = Board::from_fen(start_fen, &HASHER).unwrap();
+                for _ in 0..80 {
+                    let legal_moves = board.get_legal_moves(&MOVE_GEN_MASKS, &HASHER);
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+                    for legal_move in &legal_moves {
+                        let is_capture = board.colors[board.state.opponent]
+                            .read_square(&legal_move.get_destination())
+                            && !legal_move.is_castling();
+                        assert_eq!(
+                            legal_move.is_capture(),
+                            is_capture || legal_move.is_en_passant(),
+                            "{}",
+                            legal_move
+                        );
+
+                        let uci = legal_move.to_uci_string(true);
+                        assert_eq!(Move::from_uci(&uci, &board).unwrap().0, legal_move.0);
+                        let polyglot = legal_move.to_polyglot();
+                        assert_eq!(
+                            Move::from_polyglot(polyglot, &board).unwrap().0,
+                            legal_move.0
+                        );
+                    }
+
+                    let the_move = legal_moves[rng.random_range(0..legal_moves.len())];
+                    let san = board.move_to_san(&the_move, &MOVE_GEN_MASKS, &HASHER);
+                    let parsed = board.parse_san(&san, &MOVE_GEN_MASKS, &HASHER).unwrap();
+                    assert_eq!(parsed.0, the_move.0, "{}", san);
+                    board.make_move(&the_move, &HASHER);
+                }
+            }
+        }
+    }
+}
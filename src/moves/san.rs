@@ -0,0 +1,402 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{
+    board::Board,
+    moves::{move_mask_gen::MoveGenMasks, moves_utils::Move},
+    types::{piece::Pieces, square::Square},
+    utils::zobrist::ZobristHasher,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    InvalidSyntax(String),
+    NoMatchingMove(String),
+    AmbiguousMove(String),
+    MissingPromotion(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::InvalidSyntax(san) => write!(f, "Invalid SAN syntax: {}", san),
+            SanError::NoMatchingMove(san) => write!(f, "No legal move matches {}", san),
+            SanError::AmbiguousMove(san) => write!(f, "SAN move {} is ambiguous", san),
+            SanError::MissingPromotion(san) => {
+                write!(f, "SAN move {} is missing a promotion piece", san)
+            }
+        }
+    }
+}
+
+impl Error for SanError {}
+
+pub fn piece_to_san_char(piece: usize) -> char {
+    match piece {
+        Pieces::QUEEN => 'Q',
+        Pieces::ROOK => 'R',
+        Pieces::BISHOP => 'B',
+        Pieces::KNIGHT => 'N',
+        Pieces::PAWN => 'P',
+        Pieces::KING => 'K',
+        _ => panic!("Unexpected piece type {}", piece),
+    }
+}
+
+pub fn san_char_to_piece(piece_char: char) -> Option<usize> {
+    match piece_char {
+        'Q' => Some(Pieces::QUEEN),
+        'R' => Some(Pieces::ROOK),
+        'B' => Some(Pieces::BISHOP),
+        'N' => Some(Pieces::KNIGHT),
+        'K' => Some(Pieces::KING),
+        _ => None,
+    }
+}
+
+impl Board {
+    /// Standard Algebraic Notation of a legal move, including disambiguation and check/mate suffix
+    pub fn move_to_san(
+        &mut self,
+        the_move: &Move,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> String {
+        let origin = the_move.get_origin();
+        let destination = the_move.get_destination();
+        let mut san = String::new();
+
+        if the_move.is_castling() {
+            if the_move.is_short_castling() {
+                san.push_str("O-O");
+            } else {
+                san.push_str("O-O-O");
+            }
+        } else {
+            let piece = self
+                .get_piece_on_square(&origin)
+                .expect("No piece on move origin")
+                .piece();
+            let is_capture = the_move.is_capture();
+
+            if piece == Pieces::PAWN {
+                if is_capture {
+                    san.push((origin.get_file() + b'a') as char);
+                }
+            } else {
+                san.push(piece_to_san_char(piece));
+                san.push_str(&self.get_disambiguation(the_move, piece, move_gen_masks, hasher));
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&destination.to_string());
+
+            if let Some(promotion_piece) = the_move.promotion_piece() {
+                san.push('=');
+                san.push(piece_to_san_char(promotion_piece));
+            }
+        }
+
+        let unmake_move_helper = self.make_move(the_move, hasher);
+        if self.is_check(move_gen_masks) {
+            if self.get_legal_moves(move_gen_masks, hasher).is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+        self.unmake_move(unmake_move_helper, hasher);
+
+        san
+    }
+
+    fn get_disambiguation(
+        &mut self,
+        the_move: &Move,
+        piece: usize,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> String {
+        let origin = the_move.get_origin();
+        let destination = the_move.get_destination();
+
+        let other_origins: Vec<Square> = self
+            .get_legal_moves(move_gen_masks, hasher)
+            .into_iter()
+            .filter(|legal_move| {
+                legal_move.get_destination() == destination
+                    && legal_move.get_origin() != origin
+                    && self.pieces[self.state.turn][piece].read_square(&legal_move.get_origin())
+            })
+            .map(|legal_move| legal_move.get_origin())
+            .collect();
+
+        if other_origins.is_empty() {
+            return String::new();
+        }
+
+        let file = ((origin.get_file() + b'a') as char).to_string();
+        let rank = ((origin.get_rank() + b'1') as char).to_string();
+
+        if other_origins
+            .iter()
+            .all(|other| other.get_file() != origin.get_file())
+        {
+            file
+        } else if other_origins
+            .iter()
+            .all(|other| other.get_rank() != origin.get_rank())
+        {
+            rank
+        } else {
+            file + &rank
+        }
+    }
+
+    /// Resolves a move written in Standard Algebraic Notation against the legal moves
+    pub fn parse_san(
+        &mut self,
+        san: &str,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Result<Move, SanError> {
+        let cleaned = san
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .replace("e.p.", "");
+        let legal_moves = self.get_legal_moves(move_gen_masks, hasher);
+
+        if ["O-O", "0-0", "O-O-O", "0-0-0"].contains(&cleaned.as_str()) {
+            let short = cleaned.len() == 3;
+            return legal_moves
+                .into_iter()
+                .find(|legal_move| {
+                    legal_move.is_castling() && legal_move.is_short_castling() == short
+                })
+                .ok_or_else(|| SanError::NoMatchingMove(san.to_owned()));
+        }
+
+        let mut chars: Vec<char> = cleaned.chars().filter(|c| *c != 'x' && *c != '-').collect();
+        if chars.len() < 2 {
+            return Err(SanError::InvalidSyntax(san.to_owned()));
+        }
+
+        let piece = match san_char_to_piece(chars[0]) {
+            Some(piece) => {
+                chars.remove(0);
+                piece
+            }
+            None => Pieces::PAWN,
+        };
+
+        // promotion is written as e8=Q, some sources omit the '='
+        let mut promotion: Option<usize> = None;
+        if let Some(last) = chars.last() {
+            if let Some(promotion_piece) = san_char_to_piece(last.to_ascii_uppercase()) {
+                if piece == Pieces::PAWN && promotion_piece != Pieces::KING {
+                    promotion = Some(promotion_piece);
+                    chars.pop();
+                    if chars.last() == Some(&'=') {
+                        chars.pop();
+                    }
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(SanError::InvalidSyntax(san.to_owned()));
+        }
+        let destination_str: String = chars[chars.len() - 2..].iter().collect();
+        if !is_square_str(&destination_str) {
+            return Err(SanError::InvalidSyntax(san.to_owned()));
+        }
+        let destination = Square::from_str(&destination_str).unwrap();
+
+        let mut from_file: Option<u8> = None;
+        let mut from_rank: Option<u8> = None;
+        for disambiguation_char in &chars[..chars.len() - 2] {
+            match disambiguation_char {
+                'a'..='h' => from_file = Some(*disambiguation_char as u8 - b'a'),
+                '1'..='8' => from_rank = Some(*disambiguation_char as u8 - b'1'),
+                _ => return Err(SanError::InvalidSyntax(san.to_owned())),
+            }
+        }
+
+        let candidates: Vec<Move> = legal_moves
+            .into_iter()
+            .filter(|legal_move| {
+                let origin = legal_move.get_origin();
+                legal_move.get_destination() == destination
+                    && !legal_move.is_castling()
+                    && self.pieces[self.state.turn][piece].read_square(&origin)
+                    && from_file.is_none_or(|file| origin.get_file() == file)
+                    && from_rank.is_none_or(|rank| origin.get_rank() == rank)
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(SanError::NoMatchingMove(san.to_owned()));
+        }
+
+        if candidates.iter().any(|candidate| candidate.is_promotion()) {
+            let Some(promotion_piece) = promotion else {
+                return Err(SanError::MissingPromotion(san.to_owned()));
+            };
+            return candidates
+                .into_iter()
+                .find(|candidate| candidate.promotion_piece() == Some(promotion_piece))
+                .ok_or_else(|| SanError::NoMatchingMove(san.to_owned()));
+        }
+
+        if promotion.is_some() {
+            return Err(SanError::NoMatchingMove(san.to_owned()));
+        }
+
+        if candidates.len() > 1 {
+            return Err(SanError::AmbiguousMove(san.to_owned()));
+        }
+
+        Ok(candidates[0])
+    }
+}
+
+fn is_square_str(input: &str) -> bool {
+    let mut chars = input.chars();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some('a'..='h'), Some('1'..='8'))
+    )
+}
+
+#[cfg(test)]
+mod test_san {
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    fn san_of(fen: &str, long_move: &str) -> String {
+        let mut board = Board::from_fen(fen, &HASHER).unwrap();
+        let the_move = board
+            .get_legal_moves(&MOVE_GEN_MASKS, &HASHER)
+            .into_iter()
+            .find(|legal_move| legal_move.to_long_string() == long_move)
+            .unwrap();
+        board.move_to_san(&the_move, &MOVE_GEN_MASKS, &HASHER)
+    }
+
+    #[test]
+    fn test_move_to_san_basic() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san_of(start, "e2e4"), "e4");
+        assert_eq!(san_of(start, "g1f3"), "Nf3");
+
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san_of(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san_of(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san_of(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san_of(kiwipete, "e5f7"), "Nxf7");
+        assert_eq!(san_of(kiwipete, "e2a6"), "Bxa6");
+    }
+
+    #[test]
+    fn test_move_to_san_disambiguation() {
+        let fen = "4k3/8/8/8/8/8/8/R4RK1 w - - 0 1";
+        assert_eq!(san_of(fen, "a1d1"), "Rad1");
+        assert_eq!(san_of(fen, "f1d1"), "Rfd1");
+
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san_of(fen, "a1a3"), "R1a3");
+        assert_eq!(san_of(fen, "a5a3"), "R5a3");
+
+        let fen = "4k3/8/8/8/8/1Q3Q2/8/1Q2K3 w - - 0 1";
+        assert_eq!(san_of(fen, "b3d3"), "Qb3d3");
+    }
+
+    #[test]
+    fn test_move_to_san_promotion_and_checks() {
+        let fen = "8/P6k/8/8/8/8/8/K7 w - - 0 1";
+        assert_eq!(san_of(fen, "a7a8q"), "a8=Q");
+        assert_eq!(san_of(fen, "a7a8n"), "a8=N");
+
+        let fen = "k7/8/1K6/8/8/8/8/7R w - - 0 1";
+        assert_eq!(san_of(fen, "h1h8"), "Rh8#");
+        assert_eq!(san_of(fen, "h1h7"), "Rh7");
+        assert_eq!(san_of(fen, "h1a1"), "Ra1+");
+    }
+
+    #[test]
+    fn test_move_to_san_en_passant() {
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(san_of(fen, "e5f6"), "exf6");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let mut board = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &HASHER,
+        )
+        .unwrap();
+
+        let parsed = board.parse_san("O-O", &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert_eq!(parsed.to_long_string(), "e1g1");
+        let parsed = board.parse_san("Nxf7", &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert_eq!(parsed.to_long_string(), "e5f7");
+        let parsed = board
+            .parse_san("Qxf6+!?", &MOVE_GEN_MASKS, &HASHER)
+            .unwrap();
+        assert_eq!(parsed.to_long_string(), "f3f6");
+
+        assert_eq!(
+            board.parse_san("Nb5", &MOVE_GEN_MASKS, &HASHER),
+            Ok(Move::from_long_str("c3b5"))
+        );
+        assert!(matches!(
+            board.parse_san("Ke3", &MOVE_GEN_MASKS, &HASHER),
+            Err(SanError::NoMatchingMove(_))
+        ));
+        assert!(matches!(
+            board.parse_san("Z9", &MOVE_GEN_MASKS, &HASHER),
+            Err(SanError::InvalidSyntax(_))
+        ));
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", &HASHER).unwrap();
+        assert!(matches!(
+            board.parse_san("Rd1", &MOVE_GEN_MASKS, &HASHER),
+            Err(SanError::AmbiguousMove(_))
+        ));
+
+        let mut board = Board::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1", &HASHER).unwrap();
+        assert!(matches!(
+            board.parse_san("a8", &MOVE_GEN_MASKS, &HASHER),
+            Err(SanError::MissingPromotion(_))
+        ));
+        let parsed = board.parse_san("a8=R", &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert_eq!(parsed.to_long_string(), "a7a8r");
+        let parsed = board.parse_san("a8N", &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert_eq!(parsed.to_long_string(), "a7a8n");
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ];
+
+        for fen in fens {
+            let mut board = Board::from_fen(fen, &HASHER).unwrap();
+            for legal_move in board.get_legal_moves(&MOVE_GEN_MASKS, &HASHER) {
+                let san = board.move_to_san(&legal_move, &MOVE_GEN_MASKS, &HASHER);
+                let parsed = board.parse_san(&san, &MOVE_GEN_MASKS, &HASHER).unwrap();
+                assert_eq!(parsed, legal_move, "{} in {}", san, fen);
+            }
+        }
+    }
+}
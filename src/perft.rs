@@ -1,7 +1,132 @@
-use crate::{board::Board, moves::move_mask_gen::MoveGenMasks, utils::zobrist::ZobristHasher};
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    board::{Board, FenError},
+    moves::{move_mask_gen::MoveGenMasks, moves_utils::Move},
+    utils::{
+        command::{default_threads, thread_pool, ArgError, Args},
+        zobrist::{ZobristHash, ZobristHasher},
+    },
+};
 
 #[cfg(test)]
-use {crate::moves::moves_utils::Move, std::fs};
+use std::fs;
+
+#[derive(Debug)]
+pub enum PerftError {
+    InvalidArgument(ArgError),
+    InvalidFen(FenError),
+}
+
+impl fmt::Display for PerftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PerftError::InvalidArgument(e) => write!(f, "{}", e),
+            PerftError::InvalidFen(e) => write!(f, "Invalid fen: {}", e),
+        }
+    }
+}
+
+impl Error for PerftError {}
+
+impl From<ArgError> for PerftError {
+    fn from(e: ArgError) -> Self {
+        PerftError::InvalidArgument(e)
+    }
+}
+
+/// Lockless perft transposition table, safe to share between threads.
+/// Every slot is a (key ^ data, data) pair so torn writes are detected on read.
+pub struct PerftCache {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftCache {
+    pub fn new(size_mb: usize) -> Self {
+        let n_entries = (size_mb * 1024 * 1024 / size_of::<[AtomicU64; 2]>()).max(1);
+        Self {
+            entries: (0..n_entries)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    fn index(&self, hash: ZobristHash) -> usize {
+        (hash.get_value() % self.entries.len() as u64) as usize
+    }
+
+    pub fn get(&self, hash: ZobristHash, depth: u8) -> Option<usize> {
+        let [key, data] = &self.entries[self.index(hash)];
+        let data = data.load(Ordering::Relaxed);
+        let key = key.load(Ordering::Relaxed) ^ data;
+
+        if key == hash.get_value() && (data & 0xFF) as u8 == depth {
+            Some((data >> 8) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&self, hash: ZobristHash, depth: u8, nodes: usize) {
+        let [key, data] = &self.entries[self.index(hash)];
+        let new_data = ((nodes as u64) << 8) | depth as u64;
+        key.store(hash.get_value() ^ new_data, Ordering::Relaxed);
+        data.store(new_data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PerftOptions {
+    /// 1 runs on the calling thread, more splits the root moves across a rayon pool
+    pub threads: usize,
+    /// 0 disables the cache
+    pub cache_size_mb: usize,
+    /// castling moves are printed as king takes rook
+    pub chess960: bool,
+}
+
+impl Default for PerftOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            cache_size_mb: 0,
+            chess960: false,
+        }
+    }
+}
+
+pub struct PerftResult {
+    pub divide: Vec<(Move, usize)>,
+    pub nodes: usize,
+    pub duration: Duration,
+    pub chess960: bool,
+}
+
+impl PerftResult {
+    pub fn nps(&self) -> u64 {
+        let micros = self.duration.as_micros().max(1);
+        (self.nodes as u128 * 1_000_000 / micros) as u64
+    }
+}
+
+impl fmt::Display for PerftResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (the_move, nodes) in &self.divide {
+            writeln!(f, "{}: {}", the_move.to_uci_string(self.chess960), nodes)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Nodes searched: {}", self.nodes)?;
+        writeln!(f, "Time: {} ms", self.duration.as_millis())?;
+        write!(f, "NPS: {}", self.nps())
+    }
+}
 
 pub fn play_game(
     board: &mut Board,
@@ -21,12 +146,155 @@ pub fn play_game(
     for legal_move in legal_moves {
         let unmake_move_helper = board.make_move(&legal_move, hasher);
         n_moves += play_game(board, move_gen_masks, hasher, depth + 1, max_depth);
-        board.unmake_move(unmake_move_helper);
+        board.unmake_move(unmake_move_helper, hasher);
     }
 
     n_moves
 }
 
+/// Counts leaf nodes `depth` plies below `board`, using `cache` for subtrees if given
+pub fn perft(
+    board: &mut Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+    depth: u8,
+    cache: Option<&PerftCache>,
+) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+
+    let legal_moves = board.get_legal_moves(move_gen_masks, hasher);
+    if depth == 1 {
+        return legal_moves.len();
+    }
+
+    if let Some(nodes) = cache.and_then(|cache| cache.get(board.zobrist, depth)) {
+        return nodes;
+    }
+
+    let mut n_moves = 0;
+    for legal_move in legal_moves {
+        let unmake_move_helper = board.make_move(&legal_move, hasher);
+        n_moves += perft(board, move_gen_masks, hasher, depth - 1, cache);
+        board.unmake_move(unmake_move_helper, hasher);
+    }
+
+    if let Some(cache) = cache {
+        cache.insert(board.zobrist, depth, n_moves);
+    }
+
+    n_moves
+}
+
+/// Perft with per root move counts
+pub fn perft_divide(
+    board: &Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+    depth: u8,
+    options: &PerftOptions,
+) -> Result<PerftResult, PerftError> {
+    let start = Instant::now();
+    let cache = (options.cache_size_mb > 0).then(|| PerftCache::new(options.cache_size_mb));
+    let mut root_board = board.clone();
+    let root_moves = root_board.get_legal_moves(move_gen_masks, hasher);
+
+    let count_root_move = |the_move: Move| {
+        let mut board = board.clone();
+        board.make_move(&the_move, hasher);
+        let nodes = perft(
+            &mut board,
+            move_gen_masks,
+            hasher,
+            depth.saturating_sub(1),
+            cache.as_ref(),
+        );
+        (the_move, nodes)
+    };
+
+    let mut divide: Vec<(Move, usize)> = if depth == 0 {
+        Vec::new()
+    } else if options.threads > 1 {
+        thread_pool(options.threads)?
+            .install(|| root_moves.into_par_iter().map(count_root_move).collect())
+    } else {
+        root_moves.into_iter().map(count_root_move).collect()
+    };
+    divide.sort_by_key(|(the_move, _)| the_move.to_uci_string(options.chess960));
+
+    let nodes = if depth == 0 {
+        1
+    } else {
+        divide.iter().map(|(_, nodes)| nodes).sum()
+    };
+
+    Ok(PerftResult {
+        divide,
+        nodes,
+        duration: start.elapsed(),
+        chess960: options.chess960,
+    })
+}
+
+/// `perft <depth> [fen <fen>] [moves <move>...] [threads N] [hash MB] [chess960]`
+pub fn perft_command(args: &[String]) -> Result<(), PerftError> {
+    let mut args = Args::new(
+        args,
+        "perft <depth> [fen <fen>] [moves <move>...] [threads N] [hash MB] [chess960]",
+    );
+
+    let depth = args.parse()?;
+    let mut fen: Option<String> = None;
+    let mut moves: Vec<&str> = Vec::new();
+    let mut options = PerftOptions {
+        threads: default_threads(),
+        cache_size_mb: 16,
+        chess960: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg {
+            "fen" => fen = Some(args.value()?.to_owned()),
+            "moves" => {
+                while let Some(move_str) = args.next_if(|arg| !is_perft_option(arg)) {
+                    moves.push(move_str);
+                }
+            }
+            "threads" => options.threads = args.parse()?,
+            "hash" => options.cache_size_mb = args.parse()?,
+            "chess960" => options.chess960 = true,
+            other => return Err(ArgError::Invalid(other.to_owned()).into()),
+        }
+    }
+
+    let move_gen_masks = MoveGenMasks::load();
+    let hasher = ZobristHasher::load();
+    let mut board = match fen {
+        Some(fen) => Board::from_fen(&fen, &hasher).map_err(PerftError::InvalidFen)?,
+        None => Board::new(&hasher),
+    };
+    for move_str in moves {
+        let invalid_move = || ArgError::Invalid(move_str.to_owned());
+        let parsed = Move::from_uci(move_str, &board).ok_or_else(invalid_move)?;
+        let the_move = board
+            .get_legal_moves(&move_gen_masks, &hasher)
+            .into_iter()
+            .find(|legal_move| *legal_move == parsed)
+            .ok_or_else(invalid_move)?;
+        board.make_move(&the_move, &hasher);
+    }
+
+    let result = perft_divide(&board, &move_gen_masks, &hasher, depth, &options)?;
+    println!("{}", result);
+
+    Ok(())
+}
+
+fn is_perft_option(arg: &str) -> bool {
+    matches!(arg, "fen" | "moves" | "threads" | "hash" | "chess960")
+}
+
 #[cfg(test)]
 fn save_test_output(moves: Vec<Vec<Move>>) {
     let move_strings: Vec<String> = moves
@@ -50,6 +318,94 @@ mod test_perft {
     static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
     static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
 
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::from_fen(KIWIPETE, &HASHER).unwrap();
+
+        let result = perft_divide(
+            &board,
+            &MOVE_GEN_MASKS,
+            &HASHER,
+            3,
+            &PerftOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(result.divide.len(), 48);
+        assert_eq!(result.nodes, 97862);
+        let e2a6 = result
+            .divide
+            .iter()
+            .find(|(the_move, _)| the_move.to_long_string() == "e2a6")
+            .unwrap();
+        assert_eq!(e2a6.1, 1907);
+    }
+
+    #[test]
+    fn test_perft_threads_and_cache() {
+        let board = Board::from_fen(KIWIPETE, &HASHER).unwrap();
+        let options = [
+            PerftOptions::default(),
+            PerftOptions {
+                threads: 4,
+                cache_size_mb: 0,
+                ..PerftOptions::default()
+            },
+            PerftOptions {
+                threads: 1,
+                cache_size_mb: 1,
+                ..PerftOptions::default()
+            },
+            PerftOptions {
+                threads: 4,
+                cache_size_mb: 1,
+                ..PerftOptions::default()
+            },
+        ];
+
+        for options in options {
+            let result = perft_divide(&board, &MOVE_GEN_MASKS, &HASHER, 4, &options).unwrap();
+            assert_eq!(result.nodes, 4085603);
+        }
+    }
+
+    #[test]
+    fn test_perft_castling_notation() {
+        let board = Board::from_fen(KIWIPETE, &HASHER).unwrap();
+        let divide = |chess960| {
+            let options = PerftOptions {
+                chess960,
+                ..PerftOptions::default()
+            };
+            perft_divide(&board, &MOVE_GEN_MASKS, &HASHER, 1, &options)
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(divide(false).contains("e1g1: 1\n") && !divide(false).contains("e1h1"));
+        assert!(divide(true).contains("e1h1: 1\n") && !divide(true).contains("e1g1"));
+
+        for castling in ["e1g1", "e1h1"] {
+            let args =
+                ["1", "fen", KIWIPETE, "moves", castling, "threads", "1"].map(|arg| arg.to_owned());
+            assert!(perft_command(&args).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_perft_cache() {
+        let cache = PerftCache::new(1);
+        let hash = ZobristHash::new(0x1234_5678_9abc_def0);
+
+        assert_eq!(cache.get(hash, 3), None);
+        cache.insert(hash, 3, 97862);
+        assert_eq!(cache.get(hash, 3), Some(97862));
+        assert_eq!(cache.get(hash, 4), None);
+        assert_eq!(cache.get(ZobristHash::new(0x1234), 3), None);
+    }
+
     #[test]
     fn test_position_1_default() {
         let mut board = Board::new(&HASHER);
@@ -144,4 +500,61 @@ mod test_perft {
 
         assert_eq!(n_moves, 164075551)
     }
+
+    #[test]
+    fn test_chess960_positions() {
+        let positions = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                5,
+                8146062,
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                5,
+                16253601,
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                5,
+                6417013,
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                5,
+                9183776,
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                5,
+                34030312,
+            ),
+            (
+                "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+                5,
+                24851983,
+            ),
+            (
+                "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9",
+                5,
+                21093346,
+            ),
+            (
+                "qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9",
+                5,
+                13203304,
+            ),
+            (
+                "qn1rbbkr/ppp2p1p/1n1pp1p1/8/3P4/P6P/1PP1PPPK/QNNRBB1R w hd - 2 9",
+                4,
+                679699,
+            ),
+        ];
+
+        for (fen, max_depth, expected) in positions {
+            let mut board = Board::from_fen(fen, &HASHER).unwrap();
+            let n_moves = play_game(&mut board, &MOVE_GEN_MASKS, &HASHER, 1, max_depth);
+            assert_eq!(n_moves, expected, "{}", fen);
+        }
+    }
 }
@@ -0,0 +1,751 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::{
+    board::Board,
+    moves::{move_mask_gen::MoveGenMasks, moves_utils::Move, san::SanError},
+    types::piece::Color,
+    utils::zobrist::ZobristHasher,
+};
+
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+const MAX_LINE_LENGTH: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_pgn_str(input: &str) -> Option<Self> {
+        match input {
+            "1-0" => Some(Self::WhiteWins),
+            "0-1" => Some(Self::BlackWins),
+            "1/2-1/2" => Some(Self::Draw),
+            "*" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn as_pgn_str(&self) -> &'static str {
+        match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unknown => "*",
+        }
+    }
+
+    /// Score from white's point of view: 1 win, 0.5 draw, 0 loss
+    pub fn white_score(&self) -> Option<f64> {
+        match self {
+            Self::WhiteWins => Some(1.0),
+            Self::BlackWins => Some(0.0),
+            Self::Draw => Some(0.5),
+            Self::Unknown => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_pgn_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(io::Error),
+    InvalidTag(String),
+    InvalidFen(String),
+    InvalidMove { ply: usize, source: SanError },
+    IllegalMove { ply: usize, the_move: Move },
+    UnexpectedToken(String),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Io(e) => write!(f, "Error reading PGN: {}", e),
+            PgnError::InvalidTag(tag) => write!(f, "Invalid PGN tag: {}", tag),
+            PgnError::InvalidFen(fen) => write!(f, "Invalid FEN tag: {}", fen),
+            PgnError::InvalidMove { ply, source } => {
+                write!(f, "Invalid move at ply {}: {}", ply, source)
+            }
+            PgnError::IllegalMove { ply, the_move } => {
+                write!(f, "Illegal move {} at ply {}", the_move, ply)
+            }
+            PgnError::UnexpectedToken(token) => write!(f, "Unexpected PGN token: {}", token),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+impl From<io::Error> for PgnError {
+    fn from(e: io::Error) -> Self {
+        PgnError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PgnMove {
+    pub the_move: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    /// raw movetext of variations following this move, without the enclosing parentheses
+    pub variations: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// comments written before the first move
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    /// Builds a game from moves played from the `start` position
+    pub fn from_moves(
+        start: &Board,
+        moves: &[Move],
+        result: GameResult,
+        move_gen_masks: &MoveGenMasks,
+        hasher: &ZobristHasher,
+    ) -> Result<Self, PgnError> {
+        let mut board = start.clone();
+        let mut tags: Vec<(String, String)> = Vec::new();
+        let fen = start.get_fen();
+        if fen != STARTING_FEN {
+            tags.push(("SetUp".to_owned(), "1".to_owned()));
+            tags.push(("FEN".to_owned(), fen));
+        }
+
+        let mut pgn_moves: Vec<PgnMove> = Vec::with_capacity(moves.len());
+        for (ply, the_move) in moves.iter().enumerate() {
+            // moves parsed from long algebraic notation lack their kind and write castling
+            // as the two square king move
+            let illegal = || PgnError::IllegalMove {
+                ply: ply + 1,
+                the_move: *the_move,
+            };
+            let resolved = if the_move.is_castling() {
+                *the_move
+            } else {
+                Move::from_uci(&the_move.to_long_string(), &board).ok_or_else(illegal)?
+            };
+            let legal_move = board
+                .get_legal_moves(move_gen_masks, hasher)
+                .into_iter()
+                .find(|legal_move| *legal_move == resolved)
+                .ok_or_else(illegal)?;
+            let san = board.move_to_san(&legal_move, move_gen_masks, hasher);
+            board.make_move(&legal_move, hasher);
+            pgn_moves.push(PgnMove {
+                the_move: legal_move,
+                san,
+                ..Default::default()
+            });
+        }
+
+        let mut game = Self {
+            tags,
+            comments: Vec::new(),
+            moves: pgn_moves,
+            result,
+        };
+        game.set_tag("Result", result.as_pgn_str());
+        Ok(game)
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some(tag) => tag.1 = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    pub fn start_fen(&self) -> &str {
+        self.get_tag("FEN").unwrap_or(STARTING_FEN)
+    }
+
+    pub fn start_board(&self, hasher: &ZobristHasher) -> Result<Board, PgnError> {
+        Board::from_fen(self.start_fen(), hasher)
+            .map_err(|_| PgnError::InvalidFen(self.start_fen().to_owned()))
+    }
+
+    /// Board after all moves of the game were played, they can be taken back with `undo`
+    pub fn replay(&self, hasher: &ZobristHasher) -> Result<Board, PgnError> {
+        let mut board = self.start_board(hasher)?;
+        for pgn_move in self.moves.iter() {
+            board.play(&pgn_move.the_move, hasher);
+        }
+        Ok(board)
+    }
+
+    pub fn to_pgn_string(&self, hasher: &ZobristHasher) -> Result<String, PgnError> {
+        let mut output = String::new();
+
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = if name == "Result" {
+                self.result.as_pgn_str()
+            } else {
+                self.get_tag(name).unwrap_or(default)
+            };
+            output.push_str(&format_tag(name, value));
+        }
+        for (name, value) in self.tags.iter() {
+            if SEVEN_TAG_ROSTER
+                .iter()
+                .any(|(roster_name, _)| roster_name == name)
+            {
+                continue;
+            }
+            output.push_str(&format_tag(name, value));
+        }
+        output.push('\n');
+
+        let board = self.start_board(hasher)?;
+        let mut tokens: Vec<String> = self
+            .comments
+            .iter()
+            .map(|comment| format!("{{{}}}", comment))
+            .collect();
+        let mut full_move = board.state.full_moves;
+        let mut turn = board.state.turn;
+        let mut needs_move_number = true;
+
+        for pgn_move in self.moves.iter() {
+            if turn == Color::WHITE {
+                tokens.push(format!("{}.", full_move));
+            } else if needs_move_number {
+                tokens.push(format!("{}...", full_move));
+            }
+            tokens.push(pgn_move.san.clone());
+            needs_move_number = false;
+
+            for nag in pgn_move.nags.iter() {
+                tokens.push(format!("${}", nag));
+            }
+            for comment in pgn_move.comments.iter() {
+                tokens.push(format!("{{{}}}", comment));
+                needs_move_number = true;
+            }
+            for variation in pgn_move.variations.iter() {
+                tokens.push(format!("({})", variation));
+                needs_move_number = true;
+            }
+
+            if turn == Color::BLACK {
+                full_move += 1;
+            }
+            turn ^= 1;
+        }
+        tokens.push(self.result.as_pgn_str().to_owned());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                output.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                output.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            output.push_str(&token);
+        }
+        output.push_str("\n\n");
+
+        Ok(output)
+    }
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    TagStart,
+    TagEnd,
+    Str(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    Variation(String),
+    Result(GameResult),
+}
+
+/// Streaming PGN reader that yields one game at a time
+pub struct PgnReader<'a, R: BufRead> {
+    reader: R,
+    line: Vec<char>,
+    position: usize,
+    peeked: Option<Token>,
+    move_gen_masks: &'a MoveGenMasks,
+    hasher: &'a ZobristHasher,
+}
+
+impl<'a, R: BufRead> PgnReader<'a, R> {
+    pub fn new(reader: R, move_gen_masks: &'a MoveGenMasks, hasher: &'a ZobristHasher) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+            position: 0,
+            peeked: None,
+            move_gen_masks,
+            hasher,
+        }
+    }
+
+    fn fill_line(&mut self) -> Result<bool, PgnError> {
+        while self.position >= self.line.len() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+            // lines starting with % are escaped
+            if line.starts_with('%') {
+                continue;
+            }
+            self.line = line.chars().collect();
+            self.position = 0;
+        }
+        Ok(true)
+    }
+
+    fn peek_char(&mut self) -> Result<Option<char>, PgnError> {
+        if !self.fill_line()? {
+            return Ok(None);
+        }
+        Ok(Some(self.line[self.position]))
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, PgnError> {
+        let next = self.peek_char()?;
+        self.position += 1;
+        Ok(next)
+    }
+
+    fn skip_rest_of_line(&mut self) {
+        self.position = self.line.len();
+    }
+
+    fn read_until(&mut self, end: char) -> Result<String, PgnError> {
+        let mut output = String::new();
+        while let Some(next) = self.next_char()? {
+            if next == end {
+                return Ok(output);
+            }
+            output.push(next);
+        }
+        Err(PgnError::UnexpectedToken(format!("{}...", output)))
+    }
+
+    fn read_variation(&mut self) -> Result<String, PgnError> {
+        let mut output = String::new();
+        let mut depth = 1;
+        while let Some(next) = self.next_char()? {
+            match next {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(output.split_whitespace().collect::<Vec<&str>>().join(" "));
+                    }
+                }
+                '{' => {
+                    let comment = self.read_until('}')?;
+                    output.push('{');
+                    output.push_str(&comment);
+                    output.push('}');
+                    continue;
+                }
+                _ => (),
+            }
+            output.push(next);
+        }
+        Err(PgnError::UnexpectedToken(format!("({}", output)))
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token>, PgnError> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+
+        loop {
+            let Some(next) = self.next_char()? else {
+                return Ok(None);
+            };
+
+            let token = match next {
+                c if c.is_whitespace() || c == '.' => continue,
+                ';' => {
+                    self.skip_rest_of_line();
+                    continue;
+                }
+                '[' => Token::TagStart,
+                ']' => Token::TagEnd,
+                '"' => {
+                    let mut value = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('\\') => {
+                                if let Some(escaped) = self.next_char()? {
+                                    value.push(escaped);
+                                }
+                            }
+                            Some('"') | None => break,
+                            Some(c) => value.push(c),
+                        }
+                    }
+                    Token::Str(value)
+                }
+                '{' => Token::Comment(self.read_until('}')?.trim().to_owned()),
+                '(' => Token::Variation(self.read_variation()?),
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek_char()? {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(c);
+                        self.position += 1;
+                    }
+                    match digits.parse::<u8>() {
+                        Ok(nag) => Token::Nag(nag),
+                        Err(_) => return Err(PgnError::UnexpectedToken(format!("${}", digits))),
+                    }
+                }
+                '!' | '?' => {
+                    let mut suffix = next.to_string();
+                    while let Some(c) = self.peek_char()? {
+                        if c != '!' && c != '?' {
+                            break;
+                        }
+                        suffix.push(c);
+                        self.position += 1;
+                    }
+                    match suffix_to_nag(&suffix) {
+                        Some(nag) => Token::Nag(nag),
+                        None => return Err(PgnError::UnexpectedToken(suffix)),
+                    }
+                }
+                '*' => Token::Result(GameResult::Unknown),
+                c if c.is_ascii_alphanumeric() => {
+                    let mut symbol = c.to_string();
+                    while let Some(c) = self.peek_char()? {
+                        if !(c.is_ascii_alphanumeric() || "_+#=:-/".contains(c)) {
+                            break;
+                        }
+                        symbol.push(c);
+                        self.position += 1;
+                    }
+                    match GameResult::from_pgn_str(&symbol) {
+                        Some(result) => Token::Result(result),
+                        None => Token::Symbol(symbol),
+                    }
+                }
+                c => return Err(PgnError::UnexpectedToken(c.to_string())),
+            };
+            return Ok(Some(token));
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&Token>, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn read_tags(&mut self) -> Result<Vec<(String, String)>, PgnError> {
+        let mut tags = Vec::new();
+        while let Some(Token::TagStart) = self.peek_token()? {
+            self.read_token()?;
+            let name = match self.read_token()? {
+                Some(Token::Symbol(name)) => name,
+                other => return Err(PgnError::InvalidTag(format!("{:?}", other))),
+            };
+            let value = match self.read_token()? {
+                Some(Token::Str(value)) => value,
+                other => return Err(PgnError::InvalidTag(format!("{} {:?}", name, other))),
+            };
+            if self.read_token()? != Some(Token::TagEnd) {
+                return Err(PgnError::InvalidTag(name));
+            }
+            tags.push((name, value));
+        }
+        Ok(tags)
+    }
+
+    /// Reads the next game from the stream, `None` at the end of input
+    pub fn read_game(&mut self) -> Option<Result<PgnGame, PgnError>> {
+        match self.peek_token() {
+            Ok(None) => None,
+            Ok(Some(_)) => Some(self.parse_game()),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn parse_game(&mut self) -> Result<PgnGame, PgnError> {
+        let tags = self.read_tags()?;
+        let mut game = PgnGame {
+            tags,
+            comments: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        };
+        let mut board = game.start_board(self.hasher)?;
+
+        loop {
+            // a new tag section without a termination marker starts the next game
+            if let Some(Token::TagStart) | None = self.peek_token()? {
+                break;
+            }
+
+            match self.read_token()?.unwrap() {
+                Token::Result(result) => {
+                    game.result = result;
+                    break;
+                }
+                Token::Symbol(symbol) => {
+                    if symbol.chars().all(|c| c.is_ascii_digit()) {
+                        // move number
+                        continue;
+                    }
+                    let the_move = board
+                        .parse_san(&symbol, self.move_gen_masks, self.hasher)
+                        .map_err(|source| PgnError::InvalidMove {
+                            ply: game.moves.len() + 1,
+                            source,
+                        })?;
+                    let san = board.move_to_san(&the_move, self.move_gen_masks, self.hasher);
+                    board.make_move(&the_move, self.hasher);
+                    game.moves.push(PgnMove {
+                        the_move,
+                        san,
+                        ..Default::default()
+                    });
+                }
+                Token::Nag(nag) => match game.moves.last_mut() {
+                    Some(last_move) => last_move.nags.push(nag),
+                    None => return Err(PgnError::UnexpectedToken(format!("${}", nag))),
+                },
+                Token::Comment(comment) => match game.moves.last_mut() {
+                    Some(last_move) => last_move.comments.push(comment),
+                    None => game.comments.push(comment),
+                },
+                Token::Variation(variation) => match game.moves.last_mut() {
+                    Some(last_move) => last_move.variations.push(variation),
+                    None => return Err(PgnError::UnexpectedToken(format!("({})", variation))),
+                },
+                token => return Err(PgnError::UnexpectedToken(format!("{:?}", token))),
+            }
+        }
+
+        Ok(game)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<'_, R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game()
+    }
+}
+
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_pgn {
+    use std::io::Cursor;
+
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    const PGN: &str = r#"[Event "Casual Game"]
+[Site "Berlin GER"]
+[Date "1852.??.??"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Jean Dufresne"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.b4 Bxb4 5.c3 Ba5 6.d4 exd4 7.O-O
+d3 8.Qb3 Qf6 9.e5 Qg6 10.Re1 Nge7 11.Ba3 b5 12.Qxb5 Rb8 13.Qa4
+Bb6 14.Nbd2 Bb7 15.Ne4 Qf5 16.Bxd3 Qh5 17.Nf6+ gxf6 18.exf6
+Rg8 19.Rad1 Qxf3 20.Rxe7+ Nxe7 21.Qxd7+ Kxd7 22.Bf5+ Ke8
+23.Bd7+ Kf8 24.Bxe7# 1-0
+
+% escaped line that should be ignored
+[Event "Annotated"]
+[Result "*"]
+
+{Opening comment} 1. d4 $1 d5 (1... Nf6 2. c4 {Indian} (2. Nf3)) 2. c4!? ; rest of line
+e6?! {A comment
+over two lines} *
+
+[Event "Setup"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"]
+[Result "1/2-1/2"]
+
+40... Kd7 41. e4 1/2-1/2
+"#;
+
+    #[test]
+    fn test_read_games() {
+        let reader = PgnReader::new(Cursor::new(PGN), &MOVE_GEN_MASKS, &HASHER);
+        let games: Vec<PgnGame> = reader.map(|game| game.unwrap()).collect();
+        assert_eq!(games.len(), 3);
+
+        let immortal = &games[0];
+        assert_eq!(immortal.get_tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(immortal.result, GameResult::WhiteWins);
+        assert_eq!(immortal.moves.len(), 47);
+        assert_eq!(immortal.moves[12].san, "O-O");
+        assert_eq!(immortal.moves[46].san, "Bxe7#");
+        let final_board = immortal.replay(&HASHER).unwrap();
+        assert_eq!(
+            final_board.get_fen(),
+            "1r3kr1/pbpBBp1p/1b3P2/8/8/2P2q2/P4PPP/3R2K1 b - - 0 24"
+        );
+
+        let annotated = &games[1];
+        assert_eq!(annotated.result, GameResult::Unknown);
+        assert_eq!(annotated.comments, vec!["Opening comment".to_owned()]);
+        assert_eq!(annotated.moves.len(), 4);
+        assert_eq!(annotated.moves[0].nags, vec![1]);
+        assert_eq!(
+            annotated.moves[1].variations,
+            vec!["1... Nf6 2. c4 {Indian} (2. Nf3)".to_owned()]
+        );
+        assert_eq!(annotated.moves[2].nags, vec![5]);
+        assert_eq!(annotated.moves[3].nags, vec![6]);
+        assert_eq!(
+            annotated.moves[3].comments,
+            vec!["A comment\nover two lines".to_owned()]
+        );
+
+        let setup = &games[2];
+        assert_eq!(setup.result, GameResult::Draw);
+        assert_eq!(setup.moves.len(), 2);
+        assert_eq!(
+            setup.replay(&HASHER).unwrap().get_fen(),
+            "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 41"
+        );
+    }
+
+    #[test]
+    fn test_invalid_move() {
+        let pgn = "[Event \"?\"]\n\n1. e4 e5 2. Ke3 *\n";
+        let mut reader = PgnReader::new(Cursor::new(pgn), &MOVE_GEN_MASKS, &HASHER);
+        let error = reader.next().unwrap().unwrap_err();
+        assert!(matches!(error, PgnError::InvalidMove { ply: 3, .. }));
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let hasher = &*HASHER;
+        let start = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40", hasher).unwrap();
+        let moves = [Move::from_long_str("e8d7"), Move::from_long_str("e2e4")];
+        let mut game =
+            PgnGame::from_moves(&start, &moves, GameResult::Draw, &MOVE_GEN_MASKS, hasher).unwrap();
+        game.set_tag("White", "rustier-chess");
+        game.moves[0].comments.push("only move".to_owned());
+
+        let pgn = game.to_pgn_string(hasher).unwrap();
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"rustier-chess\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n\
+             [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]\n\n\
+             40... Kd7 {only move} 41. e4 1/2-1/2\n\n"
+        );
+
+        let mut reader = PgnReader::new(Cursor::new(pgn), &MOVE_GEN_MASKS, hasher);
+        let read_game = reader.next().unwrap().unwrap();
+        assert_eq!(read_game.result, GameResult::Draw);
+        assert_eq!(read_game.moves.len(), 2);
+        assert_eq!(read_game.moves[0].the_move, moves[0]);
+        assert_eq!(read_game.moves[1].the_move, moves[1]);
+        assert_eq!(read_game.get_tag("White"), Some("rustier-chess"));
+    }
+
+    #[test]
+    fn test_write_from_start_position() {
+        let board = Board::new(&HASHER);
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "g8f6", "e1g1"]
+            .into_iter()
+            .map(Move::from_long_str)
+            .collect();
+        let game = PgnGame::from_moves(
+            &board,
+            &moves,
+            GameResult::Unknown,
+            &MOVE_GEN_MASKS,
+            &HASHER,
+        )
+        .unwrap();
+        let pgn = game.to_pgn_string(&HASHER).unwrap();
+
+        assert!(!pgn.contains("FEN"));
+        assert!(pgn.ends_with("\n1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 4. O-O *\n\n"));
+        assert!(game.moves[6].the_move.is_castling());
+
+        let illegal = PgnGame::from_moves(
+            &board,
+            &[Move::from_long_str("e2e5")],
+            GameResult::Unknown,
+            &MOVE_GEN_MASKS,
+            &HASHER,
+        );
+        assert!(matches!(illegal, Err(PgnError::IllegalMove { ply: 1, .. })));
+    }
+}
@@ -0,0 +1,526 @@
+// https://www.chessprogramming.org/Texel%27s_Tuning_Method
+
+use std::{
+    error::Error,
+    f64::consts::LN_10,
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    board::{Board, FenError, FenOptions},
+    bots::{
+        king_safety::evaluate_king_safety,
+        mobility::evaluate_mobility,
+        pawns::{evaluate_pawns, PawnHashTable},
+        pesto::{
+            pesto_features, pesto_table_index, pesto_value_index, pesto_weights, PESTO_WEIGHT_COUNT,
+        },
+    },
+    moves::move_mask_gen::MoveGenMasks,
+    types::piece::Color,
+    utils::{
+        command::{default_threads, thread_pool, ArgError, Args},
+        zobrist::ZobristHasher,
+    },
+};
+
+#[derive(Debug)]
+pub enum TuneError {
+    Io(io::Error),
+    InvalidLine(String),
+    InvalidFen(FenError),
+    InvalidArgument(ArgError),
+}
+
+impl fmt::Display for TuneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TuneError::Io(e) => write!(f, "Error reading positions: {}", e),
+            TuneError::InvalidLine(line) => write!(f, "Invalid line: {}", line),
+            TuneError::InvalidFen(e) => write!(f, "Invalid fen: {}", e),
+            TuneError::InvalidArgument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for TuneError {}
+
+impl From<io::Error> for TuneError {
+    fn from(e: io::Error) -> Self {
+        TuneError::Io(e)
+    }
+}
+
+impl From<ArgError> for TuneError {
+    fn from(e: ArgError) -> Self {
+        TuneError::InvalidArgument(e)
+    }
+}
+
+/// What a position is labelled with, from white's point of view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Label {
+    /// 1.0 win, 0.5 draw, 0.0 loss
+    Result(f64),
+    /// centipawns
+    Score(i32),
+}
+
+impl Label {
+    pub fn target(&self, k: f64) -> f64 {
+        match self {
+            Label::Result(result) => *result,
+            Label::Score(score) => sigmoid(*score as f64, k),
+        }
+    }
+}
+
+/// Splits a line into its fen and label. Accepts `[1.0]`, `"1-0";`, `c9 "1/2-1/2";`
+/// style results and plain centipawn scores after the fen, or `FEN;score;result`.
+pub fn parse_line(line: &str) -> Result<(String, Label), TuneError> {
+    let invalid = || TuneError::InvalidLine(line.to_owned());
+
+    // `FEN;score;result` from `datagen export`
+    if let [fen, _, result] = line.split(';').collect::<Vec<&str>>()[..] {
+        return match result.trim().parse::<f64>() {
+            Ok(result) if (0.0..=1.0).contains(&result) => {
+                Ok((fen.trim().to_owned(), Label::Result(result)))
+            }
+            _ => Err(invalid()),
+        };
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 5 {
+        return Err(invalid());
+    }
+
+    // the move counters are optional
+    let mut fen_length = 4;
+    while fen_length < 6
+        && fen_length + 1 < fields.len()
+        && fields[fen_length].parse::<u32>().is_ok()
+    {
+        fen_length += 1;
+    }
+
+    let label = fields[fen_length..]
+        .iter()
+        .filter(|field| **field != "c9")
+        .map(|field| field.trim_matches(|c| matches!(c, '[' | ']' | '"' | ';' | ',')))
+        .find(|field| !field.is_empty())
+        .ok_or_else(invalid)?;
+
+    let label = match label {
+        "1-0" => Label::Result(1.0),
+        "0-1" => Label::Result(0.0),
+        "1/2-1/2" | "1/2" => Label::Result(0.5),
+        _ if label.contains('.') => match label.parse::<f64>() {
+            Ok(result) if (0.0..=1.0).contains(&result) => Label::Result(result),
+            _ => return Err(invalid()),
+        },
+        _ => Label::Score(label.parse().map_err(|_| invalid())?),
+    };
+
+    Ok((fields[..fen_length].join(" "), label))
+}
+
+/// Expected result from white's point of view
+pub fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + (-k * eval * LN_10 / 400.0).exp())
+}
+
+/// A quiet position reduced to the features of the tuned weights
+pub struct TuningEntry {
+    pub features: Vec<(usize, f64)>,
+    /// the evaluation terms that aren't tuned, white's point of view
+    pub offset: f64,
+    pub target: f64,
+}
+
+impl TuningEntry {
+    pub fn evaluate(&self, weights: &[f64]) -> f64 {
+        self.offset
+            + self
+                .features
+                .iter()
+                .map(|(index, coefficient)| weights[*index] * coefficient)
+                .sum::<f64>()
+    }
+}
+
+/// Resolves captures with PeSTO only and returns the position at the end of
+/// the principal variation
+fn quiescence(
+    board: &mut Board,
+    mut alpha: i32,
+    beta: i32,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> (i32, Board) {
+    let stand_pat = board.pesto.score(board.state.turn);
+    let mut best = (stand_pat, board.clone());
+    if stand_pat >= beta {
+        return best;
+    }
+    alpha = alpha.max(stand_pat);
+
+    let capture_moves = board
+        .get_legal_moves(move_gen_masks, hasher)
+        .into_iter()
+        .filter(|legal_move| legal_move.is_capture());
+
+    for capture in capture_moves {
+        let unmake_move_helper = board.make_move(&capture, hasher);
+        let (score, leaf) = quiescence(board, -beta, -alpha, move_gen_masks, hasher);
+        board.unmake_move(unmake_move_helper, hasher);
+
+        if -score > best.0 {
+            best = (-score, leaf);
+        }
+        if -score >= beta {
+            break;
+        }
+        alpha = alpha.max(-score);
+    }
+
+    best
+}
+
+/// None for positions that are already decided
+pub fn quiet_position(
+    board: &Board,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Option<Board> {
+    let mut board = board.clone();
+    if board.get_legal_moves(move_gen_masks, hasher).is_empty() {
+        return None;
+    }
+    let (_, leaf) = quiescence(&mut board, -i32::MAX, i32::MAX, move_gen_masks, hasher);
+    Some(leaf)
+}
+
+pub fn tuning_entry(
+    board: &Board,
+    target: f64,
+    pawn_table: &mut PawnHashTable,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Option<TuningEntry> {
+    let board = quiet_position(board, move_gen_masks, hasher)?;
+    let offset = evaluate_pawns(&board, pawn_table, move_gen_masks)
+        + evaluate_king_safety(&board, move_gen_masks)
+        + evaluate_mobility(&board, move_gen_masks);
+    let offset = if board.state.turn == Color::WHITE {
+        offset
+    } else {
+        -offset
+    };
+
+    Some(TuningEntry {
+        features: pesto_features(&board),
+        offset: offset as f64,
+        target,
+    })
+}
+
+pub fn load_entries(
+    path: &Path,
+    k: f64,
+    move_gen_masks: &MoveGenMasks,
+    hasher: &ZobristHasher,
+) -> Result<Vec<TuningEntry>, TuneError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut positions = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (fen, label) = parse_line(&line)?;
+        let board = Board::from_fen_with_options(&fen, hasher, &FenOptions::lenient())
+            .map_err(TuneError::InvalidFen)?;
+        positions.push((board, label.target(k)));
+    }
+
+    Ok(positions
+        .par_iter()
+        .map_init(PawnHashTable::new, |pawn_table, (board, target)| {
+            tuning_entry(board, *target, pawn_table, move_gen_masks, hasher)
+        })
+        .flatten()
+        .collect())
+}
+
+/// Mean squared error between the targets and the predicted results
+pub fn mean_error(entries: &[TuningEntry], weights: &[f64], k: f64) -> f64 {
+    let total: f64 = entries
+        .par_iter()
+        .map(|entry| (entry.target - sigmoid(entry.evaluate(weights), k)).powi(2))
+        .sum();
+    total / entries.len().max(1) as f64
+}
+
+fn gradient(entries: &[TuningEntry], weights: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = entries
+        .par_iter()
+        .fold(
+            || vec![0.0; weights.len()],
+            |mut gradient, entry| {
+                let predicted = sigmoid(entry.evaluate(weights), k);
+                let derivative =
+                    (predicted - entry.target) * predicted * (1.0 - predicted) * k * LN_10 / 400.0;
+                for (index, coefficient) in &entry.features {
+                    gradient[*index] += derivative * coefficient;
+                }
+                gradient
+            },
+        )
+        .reduce(
+            || vec![0.0; weights.len()],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+
+    let scale = 2.0 / entries.len().max(1) as f64;
+    gradient.iter_mut().for_each(|g| *g *= scale);
+    gradient
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TuneOptions {
+    pub epochs: usize,
+    pub learning_rate: f64,
+    /// sigmoid scaling
+    pub k: f64,
+    pub threads: usize,
+}
+
+impl Default for TuneOptions {
+    fn default() -> Self {
+        Self {
+            epochs: 500,
+            learning_rate: 1.0,
+            k: 1.0,
+            threads: 1,
+        }
+    }
+}
+
+/// Full batch Adam, `report` gets the epoch and the error before it
+pub fn tune(
+    entries: &[TuningEntry],
+    weights: &[f64],
+    options: &TuneOptions,
+    mut report: impl FnMut(usize, f64),
+) -> Vec<f64> {
+    const BETA_1: f64 = 0.9;
+    const BETA_2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    let mut weights = weights.to_vec();
+    let mut momentum = vec![0.0; weights.len()];
+    let mut velocity = vec![0.0; weights.len()];
+
+    for epoch in 0..options.epochs {
+        report(epoch, mean_error(entries, &weights, options.k));
+
+        let gradient = gradient(entries, &weights, options.k);
+        let step = (epoch + 1) as i32;
+        for i in 0..weights.len() {
+            momentum[i] = BETA_1 * momentum[i] + (1.0 - BETA_1) * gradient[i];
+            velocity[i] = BETA_2 * velocity[i] + (1.0 - BETA_2) * gradient[i].powi(2);
+            let momentum_hat = momentum[i] / (1.0 - BETA_1.powi(step));
+            let velocity_hat = velocity[i] / (1.0 - BETA_2.powi(step));
+            weights[i] -= options.learning_rate * momentum_hat / (velocity_hat.sqrt() + EPSILON);
+        }
+    }
+
+    weights
+}
+
+/// The weights as the statics of `bots/pesto.rs`
+pub fn pesto_source(weights: &[i32]) -> String {
+    const TABLE_NAMES: [&str; 6] = ["QUEEN", "ROOK", "BISHOP", "KNIGHT", "PAWN", "KING"];
+    // the order they appear in pesto.rs
+    const TABLE_ORDER: [usize; 6] = [4, 3, 2, 1, 0, 5];
+    let phases = ["MG", "EG"];
+
+    let mut source = String::from("// Generated by the `tune` command\n\n");
+    for (phase, phase_name) in phases.iter().enumerate() {
+        let values: Vec<String> = (0..6)
+            .map(|piece| weights[pesto_value_index(phase, piece)].to_string())
+            .collect();
+        source += &format!(
+            "static PESTO_{}_VALUE: [i32; 6] = [{}];\n",
+            phase_name,
+            values.join(", ")
+        );
+    }
+
+    for piece in TABLE_ORDER {
+        for (phase, phase_name) in phases.iter().enumerate() {
+            source += &format!(
+                "\n#[rustfmt::skip]\nstatic PESTO_TABLE_{}_{}: [i32; 64] = [\n",
+                phase_name, TABLE_NAMES[piece]
+            );
+            for rank in 0..8 {
+                let row: Vec<String> = (0..8)
+                    .map(|file| {
+                        format!(
+                            "{:>4},",
+                            weights[pesto_table_index(phase, piece, rank * 8 + file)]
+                        )
+                    })
+                    .collect();
+                source += &format!("  {}\n", row.join(""));
+            }
+            source += "];\n";
+        }
+    }
+
+    source
+}
+
+/// `tune <file> [epochs N] [lr X] [k X] [threads N] [out <path>]`
+pub fn tune_command(args: &[String]) -> Result<(), TuneError> {
+    let mut args = Args::new(
+        args,
+        "tune <file> [epochs N] [lr X] [k X] [threads N] [out <path>]",
+    );
+
+    let path = args.value()?;
+    let mut options = TuneOptions {
+        threads: default_threads(),
+        ..TuneOptions::default()
+    };
+    let mut out: Option<&str> = None;
+
+    while let Some(arg) = args.next() {
+        match arg {
+            "epochs" => options.epochs = args.parse()?,
+            "lr" => options.learning_rate = args.parse()?,
+            "k" => options.k = args.parse()?,
+            "threads" => options.threads = args.parse()?,
+            "out" => out = Some(args.value()?),
+            other => return Err(ArgError::Invalid(other.to_owned()).into()),
+        }
+    }
+
+    let pool = thread_pool(options.threads)?;
+
+    let move_gen_masks = MoveGenMasks::load();
+    let hasher = ZobristHasher::load();
+    let entries =
+        pool.install(|| load_entries(Path::new(path), options.k, &move_gen_masks, &hasher))?;
+    println!("{} quiet positions", entries.len());
+
+    let initial: Vec<f64> = pesto_weights().into_iter().map(|w| w as f64).collect();
+    let tuned = pool.install(|| {
+        tune(&entries, &initial, &options, |epoch, error| {
+            if epoch % 10 == 0 {
+                println!("epoch {} error {:.6}", epoch, error);
+            }
+        })
+    });
+    println!(
+        "final error {:.6}",
+        pool.install(|| mean_error(&entries, &tuned, options.k))
+    );
+
+    let tuned: Vec<i32> = tuned.into_iter().map(|w| w.round() as i32).collect();
+    debug_assert_eq!(tuned.len(), PESTO_WEIGHT_COUNT);
+    let source = pesto_source(&tuned);
+    match out {
+        Some(out) => fs::write(out, source)?,
+        None => print!("{}", source),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_tuner {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static MOVE_GEN_MASKS: Lazy<MoveGenMasks> = Lazy::new(MoveGenMasks::load);
+    static HASHER: Lazy<ZobristHasher> = Lazy::new(ZobristHasher::load);
+
+    #[test]
+    fn test_parse_line() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
+        let cases = [
+            (format!("{} 0 1 [1.0]", start), Label::Result(1.0)),
+            (format!("{} [0.5]", start), Label::Result(0.5)),
+            (format!("{} c9 \"0-1\";", start), Label::Result(0.0)),
+            (format!("{} 0 1 \"1/2-1/2\"", start), Label::Result(0.5)),
+            (format!("{} 0 1 35", start), Label::Score(35)),
+            (format!("{} -120", start), Label::Score(-120)),
+            (format!("{} 0 1;-45;0.5", start), Label::Result(0.5)),
+        ];
+        for (line, expected) in cases {
+            let (fen, label) = parse_line(&line).unwrap();
+            assert_eq!(label, expected, "{}", line);
+            assert!(fen.starts_with(start));
+        }
+
+        assert!(parse_line(start).is_err());
+        assert!(parse_line(&format!("{} [1.5]", start)).is_err());
+        assert!(parse_line(&format!("{} won", start)).is_err());
+    }
+
+    #[test]
+    fn test_quiet_position() {
+        // the queen on d5 hangs
+        let board = Board::from_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1", &HASHER).unwrap();
+        let quiet = quiet_position(&board, &MOVE_GEN_MASKS, &HASHER).unwrap();
+        assert!(quiet.pieces[Color::BLACK][0].is_empty());
+
+        let mated = Board::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", &HASHER).unwrap();
+        assert!(quiet_position(&mated, &MOVE_GEN_MASKS, &HASHER).is_none());
+    }
+
+    #[test]
+    fn test_tune_reduces_error() {
+        let positions = [
+            ("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1", 1.0),
+            ("4k3/ppp5/8/8/8/8/8/4K3 w - - 0 1", 0.0),
+            ("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", 0.5),
+            ("4k3/8/8/8/8/8/1N6/4K3 b - - 0 1", 0.5),
+        ];
+        let mut pawn_table = PawnHashTable::new();
+        let entries: Vec<TuningEntry> = positions
+            .iter()
+            .filter_map(|(fen, target)| {
+                let board = Board::from_fen(fen, &HASHER).unwrap();
+                tuning_entry(&board, *target, &mut pawn_table, &MOVE_GEN_MASKS, &HASHER)
+            })
+            .collect();
+        assert_eq!(entries.len(), positions.len());
+
+        let initial: Vec<f64> = pesto_weights().into_iter().map(|w| w as f64).collect();
+        let options = TuneOptions {
+            epochs: 50,
+            learning_rate: 5.0,
+            ..TuneOptions::default()
+        };
+        let tuned = tune(&entries, &initial, &options, |_, _| {});
+        assert!(mean_error(&entries, &tuned, 1.0) < mean_error(&entries, &initial, 1.0));
+    }
+
+    #[test]
+    fn test_pesto_source() {
+        let source = pesto_source(&pesto_weights());
+        assert!(source.contains("static PESTO_MG_VALUE: [i32; 6] = [1025, 477, 365, 337, 82, 0];"));
+        assert!(source.contains("static PESTO_TABLE_EG_KING: [i32; 64] = [\n   -74, -35,"));
+        assert_eq!(source.matches("#[rustfmt::skip]").count(), 12);
+    }
+}
@@ -24,14 +24,27 @@ impl Pieces {
     pub const KING: usize = 5;
 }
 
+/// u8 fields keep the board mailbox small, boards get cloned a lot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Piece {
-    pub piece: usize,
-    pub color: usize,
+    piece: u8,
+    color: u8,
 }
 
 impl Piece {
     pub fn new(piece: usize, color: usize) -> Self {
-        Self { piece, color }
+        Self {
+            piece: piece as u8,
+            color: color as u8,
+        }
+    }
+
+    pub fn piece(&self) -> usize {
+        self.piece as usize
+    }
+
+    pub fn color(&self) -> usize {
+        self.color as usize
     }
 }
 
@@ -1,39 +1,72 @@
-use crate::types::{bitboard::BitBoard, piece::Color, square::Square};
+use crate::types::{piece::Color, square::Square};
 
-pub const WHITE_LONG_ROOK_STARTING_MASK: BitBoard = BitBoard(0b1);
-pub const WHITE_SHORT_ROOK_STARTING_MASK: BitBoard = BitBoard(0x80);
-pub const BLACK_LONG_ROOK_STARTING_MASK: BitBoard = BitBoard(0x100000000000000);
-pub const BLACK_SHORT_ROOK_STARTING_MASK: BitBoard = BitBoard(0x8000000000000000);
+pub const SHORT: usize = 0;
+pub const LONG: usize = 1;
 
+/// rights:
 /// bit 0:      white short
 /// bit 1:      white long
 /// bit 2:      black short
 /// bit 3:      black long
 /// bits 4-7:   unused
+///
+/// rook_files: file of the castling rook per [color][side], h and a files in standard chess
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct Castling(u8);
+pub struct Castling {
+    rights: u8,
+    rook_files: [[u8; 2]; 2],
+}
 
 impl Default for Castling {
     fn default() -> Self {
-        Self(0b1111)
+        Self {
+            rights: 0b1111,
+            rook_files: [[7, 0], [7, 0]],
+        }
     }
 }
 
 impl Castling {
     pub fn new() -> Self {
-        Self(0)
+        Self {
+            rights: 0,
+            rook_files: [[7, 0], [7, 0]],
+        }
     }
 
     pub fn read_bit(&self, bit: u8) -> bool {
-        self.0 & (1 << bit) != 0
+        self.rights & (1 << bit) != 0
     }
 
     pub fn set_zero(&mut self, bit: u8) {
-        self.0 &= !(1 << bit);
+        self.rights &= !(1 << bit);
     }
 
     pub fn set_one(&mut self, bit: u8) {
-        self.0 |= 1 << bit;
+        self.rights |= 1 << bit;
+    }
+
+    pub fn has_right(&self, color: usize, side: usize) -> bool {
+        self.read_bit((color * 2 + side) as u8)
+    }
+
+    /// Grants the right to castle with the rook on `rook_file`
+    pub fn set_right(&mut self, color: usize, side: usize, rook_file: u8) {
+        self.set_one((color * 2 + side) as u8);
+        self.rook_files[color][side] = rook_file;
+    }
+
+    pub fn remove_right(&mut self, color: usize, side: usize) {
+        self.set_zero((color * 2 + side) as u8);
+    }
+
+    pub fn rook_file(&self, color: usize, side: usize) -> u8 {
+        self.rook_files[color][side]
+    }
+
+    pub fn rook_square(&self, color: usize, side: usize) -> Square {
+        let rank = if color == Color::WHITE { 0 } else { 7 };
+        Square::new(rank * 8 + self.rook_file(color, side))
     }
 
     pub fn set_white_short(&mut self) {
@@ -86,22 +119,22 @@ impl Castling {
 
     pub fn remove_color_castling(&mut self, color: usize) {
         if color == Color::WHITE {
-            self.0 &= !0b11;
+            self.rights &= !0b11;
         } else {
-            self.0 &= !0b1100;
+            self.rights &= !0b1100;
         }
     }
 
     pub fn can_color_castle(&self, color: usize) -> bool {
         if color == Color::WHITE {
-            self.0 & 0b11 != 0
+            self.rights & 0b11 != 0
         } else {
-            self.0 & 0b1100 != 0
+            self.rights & 0b1100 != 0
         }
     }
 
     pub fn can_someone_castle(&self) -> bool {
-        self.0 != 0
+        self.rights != 0
     }
 
     /// return (short, long) tuple
@@ -168,6 +201,6 @@ impl State {
     }
 
     pub fn remove_castling_rights(&mut self, color: usize, side: usize) {
-        self.castling.set_zero((color | (side << 1)) as u8);
+        self.castling.remove_right(color, side);
     }
 }
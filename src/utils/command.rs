@@ -0,0 +1,96 @@
+use std::{error::Error, fmt, iter::Peekable, slice, str::FromStr, thread};
+
+/// Errors shared by the command line subcommands, wrapped by their own error types
+#[derive(Debug)]
+pub enum ArgError {
+    /// missing argument, holds the command's usage line
+    Usage(&'static str),
+    Invalid(String),
+    ThreadPool(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::Usage(usage) => write!(f, "usage: {}", usage),
+            ArgError::Invalid(arg) => write!(f, "Invalid argument: {}", arg),
+            ArgError::ThreadPool(e) => write!(f, "Could not build thread pool: {}", e),
+        }
+    }
+}
+
+impl Error for ArgError {}
+
+/// Arguments of a subcommand, `value` and `parse` read the operand of an option
+pub struct Args<'a> {
+    args: Peekable<slice::Iter<'a, String>>,
+    usage: &'static str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(args: &'a [String], usage: &'static str) -> Self {
+        Self {
+            args: args.iter().peekable(),
+            usage,
+        }
+    }
+
+    pub fn next_if(&mut self, func: impl FnOnce(&str) -> bool) -> Option<&'a str> {
+        self.args.next_if(|arg| func(arg)).map(String::as_str)
+    }
+
+    /// The next argument, which has to be there
+    pub fn value(&mut self) -> Result<&'a str, ArgError> {
+        self.next().ok_or(ArgError::Usage(self.usage))
+    }
+
+    pub fn parse<T: FromStr>(&mut self) -> Result<T, ArgError> {
+        let value = self.value()?;
+        value
+            .parse::<T>()
+            .map_err(|_| ArgError::Invalid(value.to_owned()))
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.args.next().map(String::as_str)
+    }
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+pub fn thread_pool(threads: usize) -> Result<rayon::ThreadPool, ArgError> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| ArgError::ThreadPool(e.to_string()))
+}
+
+#[cfg(test)]
+mod test_command {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let args = ["7", "threads", "x", "out"].map(|arg| arg.to_owned());
+        let mut args = Args::new(&args, "test <depth> [threads N] [out <path>]");
+
+        assert_eq!(args.parse::<u8>().unwrap(), 7);
+        assert_eq!(args.next_if(|arg| arg == "out"), None);
+        assert_eq!(args.next(), Some("threads"));
+        assert!(matches!(args.parse::<usize>(), Err(ArgError::Invalid(arg)) if arg == "x"));
+        assert_eq!(args.next(), Some("out"));
+        assert!(matches!(args.value(), Err(ArgError::Usage(_))));
+        assert_eq!(
+            args.value().unwrap_err().to_string(),
+            "usage: test <depth> [threads N] [out <path>]"
+        );
+    }
+}
@@ -1,2 +1,3 @@
+pub mod command;
 mod polyglot_array;
 pub mod zobrist;
@@ -8,6 +8,7 @@ use crate::{
     types::{
         piece::{Color, Pieces},
         square::Square,
+        state::Castling,
     },
 };
 
@@ -90,6 +91,55 @@ impl ZobristHasher {
         zobrist_hash
     }
 
+    /// pawns only, `Board::pawn_key` from scratch
+    pub fn hash_pawns(&self, board: &Board) -> ZobristHash {
+        let mut zobrist_hash = ZobristHash::new(0_u64);
+
+        for color in [Color::WHITE, Color::BLACK] {
+            for square in board.pieces[color][Pieces::PAWN].get_ones() {
+                zobrist_hash ^= self.hash_piece_at_square(&Pieces::PAWN, &color, &square)
+            }
+        }
+
+        zobrist_hash
+    }
+
+    /// non-pawn pieces of one colour, king included
+    pub fn hash_non_pawns(&self, board: &Board, color: usize) -> ZobristHash {
+        let mut zobrist_hash = ZobristHash::new(0_u64);
+
+        for (piece, piece_board) in board.pieces[color].iter().enumerate() {
+            if piece == Pieces::PAWN {
+                continue;
+            }
+            for square in piece_board.get_ones() {
+                zobrist_hash ^= self.hash_piece_at_square(&piece, &color, &square)
+            }
+        }
+
+        zobrist_hash
+    }
+
+    /// Key of the `index`th piece of a kind, the piece square keys of the first squares are reused
+    pub fn hash_material_count(&self, piece: usize, color: usize, index: u8) -> ZobristHash {
+        self.hash_piece_at_square(&piece, &color, &Square::new(index))
+    }
+
+    /// piece counts only, positions with the same material share the key
+    pub fn hash_material(&self, board: &Board) -> ZobristHash {
+        let mut zobrist_hash = ZobristHash::new(0_u64);
+
+        for (color, pieces) in board.pieces.iter().enumerate() {
+            for (piece, piece_board) in pieces.iter().enumerate() {
+                for index in 0..piece_board.0.count_ones() as u8 {
+                    zobrist_hash ^= self.hash_material_count(piece, color, index)
+                }
+            }
+        }
+
+        zobrist_hash
+    }
+
     pub fn hash_castling_white_short(&self) -> ZobristHash {
         self.array[768]
     }
@@ -112,25 +162,30 @@ impl ZobristHasher {
     }
 
     pub fn hash_castling(&self, board: &Board) -> ZobristHash {
+        self.hash_castling_rights(&board.state.castling)
+    }
+
+    pub fn hash_castling_rights(&self, castling: &Castling) -> ZobristHash {
         let mut zobrist_hash = ZobristHash::new(0);
 
-        if board.state.castling.white_short() {
+        if castling.white_short() {
             zobrist_hash ^= self.array[768];
         }
-        if board.state.castling.white_long() {
+        if castling.white_long() {
             zobrist_hash ^= self.array[769];
         }
-        if board.state.castling.black_short() {
+        if castling.black_short() {
             zobrist_hash ^= self.array[770];
         }
-        if board.state.castling.black_long() {
+        if castling.black_long() {
             zobrist_hash ^= self.array[771];
         }
 
         zobrist_hash
     }
 
-    /// Zobrist hashes en passant only if there is a pawn around that can possibly use it
+    /// Zobrist hashes en passant only if a pawn of the side to move stands next to the pawn
+    /// that just double pushed, whether or not the capture is legal (Polyglot spec)
     pub fn hash_en_passant(&self, board: &Board, turn: usize) -> ZobristHash {
         if let Some(square) = board.state.en_passant {
             let mask = if turn == Color::WHITE {
@@ -138,22 +193,21 @@ impl ZobristHasher {
             } else {
                 board.pieces[Color::BLACK][Pieces::PAWN].shift_down(1)
             };
+            // shifting across the a / h file boundary wraps onto the neighbouring rank
             let file = square.get_file();
-            if file == 0 {
-                mask.shift_right(1).read_square(&square);
-                return self.array[772 + file as usize];
-            }
-            if file == 7 {
-                mask.shift_left(1).read_square(&square);
+            let neighbours = match file {
+                0 => mask.shift_left(1),
+                7 => mask.shift_right(1),
+                _ => mask.shift_left(1) | mask.shift_right(1),
+            };
+            if neighbours.read_square(&square) {
                 return self.array[772 + file as usize];
             }
-            if (mask.shift_left(1) | mask.shift_right(1)).read_square(&square) {
-                return self.array[772 + square.get_file() as usize];
-            }
         }
         ZobristHash::new(0)
     }
 
+    /// Polyglot xors the turn key when white is to move
     pub fn hash_turn(&self, board: &Board) -> ZobristHash {
         if board.state.turn == Color::WHITE {
             self.turn_hash()
@@ -189,7 +243,7 @@ mod test_zobrist {
 
     use once_cell::sync::Lazy;
 
-    use crate::moves::move_mask_gen::MoveGenMasks;
+    use crate::moves::{move_mask_gen::MoveGenMasks, moves_utils::Move};
 
     use super::*;
 
@@ -329,6 +383,113 @@ mod test_zobrist {
         )
     }
 
+    #[test]
+    fn test_polyglot_keys_incremental() {
+        let move_gen_masks = MoveGenMasks::load();
+        let sequences: [(&[&str], u64); 9] = [
+            (&[], 0x463b96181691fc9c),
+            (&["e2e4"], 0x823c9b50fd114196),
+            (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+            (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+            (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+            (
+                &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+                0x652a607ca3f242c1,
+            ),
+            (
+                &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+                0x00fdd303c946bdd9,
+            ),
+            (
+                &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+                0x3c8123ea7b067637,
+            ),
+            (
+                &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"],
+                0x5c3f9b829b279560,
+            ),
+        ];
+
+        for (moves, key) in sequences {
+            let mut board = Board::new(&HASHER);
+            for move_str in moves {
+                board
+                    .check_and_make_move(&Move::from_long_str(move_str), &move_gen_masks, &HASHER)
+                    .unwrap();
+            }
+            assert_eq!(board.zobrist, ZobristHash::new(key), "{:?}", moves);
+            assert_eq!(HASHER.hash_everyting(&board), ZobristHash::new(key));
+        }
+    }
+
+    #[test]
+    fn test_zobrist_en_passant_edge_files() {
+        let hash_of = |fen: &str| HASHER.hash_everyting(&Board::from_fen(fen, &HASHER).unwrap());
+
+        // the h3 and a5 pawns only touch the en passant square by wrapping around the board
+        assert_eq!(
+            hash_of("rnbqkbnr/ppppppp1/8/8/P7/7p/1PPPPPPP/RNBQKBNR b KQkq a3 0 3"),
+            hash_of("rnbqkbnr/ppppppp1/8/8/P7/7p/1PPPPPPP/RNBQKBNR b KQkq - 0 3")
+        );
+        assert_eq!(
+            hash_of("rnbqkbnr/1ppppppp/8/p7/7P/8/PPPPPPP1/RNBQKBNR b KQkq h3 0 2"),
+            hash_of("rnbqkbnr/1ppppppp/8/p7/7P/8/PPPPPPP1/RNBQKBNR b KQkq - 0 2")
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/p1pppppp/8/8/Pp6/8/1PPPPPPP/RNBQKBNR b KQkq a3 0 3"),
+            hash_of("rnbqkbnr/p1pppppp/8/8/Pp6/8/1PPPPPPP/RNBQKBNR b KQkq - 0 3")
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/pppppp1p/8/8/6pP/8/PPPPPPP1/RNBQKBNR b KQkq h3 0 3"),
+            hash_of("rnbqkbnr/pppppp1p/8/8/6pP/8/PPPPPPP1/RNBQKBNR b KQkq - 0 3")
+        );
+    }
+
+    #[test]
+    fn test_pawn_material_and_non_pawn_keys() {
+        let move_gen_masks = MoveGenMasks::load();
+        let mut board = Board::new(&HASHER);
+        let start = board.clone();
+
+        // knights out and back, only the non-pawn keys move along
+        board
+            .check_and_make_move(&Move::from_long_str("g1f3"), &move_gen_masks, &HASHER)
+            .unwrap();
+        assert_eq!(board.pawn_key, start.pawn_key);
+        assert_eq!(board.material_key, start.material_key);
+        assert_ne!(
+            board.non_pawn_keys[Color::WHITE],
+            start.non_pawn_keys[Color::WHITE]
+        );
+        assert_eq!(
+            board.non_pawn_keys[Color::BLACK],
+            start.non_pawn_keys[Color::BLACK]
+        );
+
+        for move_str in ["e7e5", "f3e5"] {
+            board
+                .check_and_make_move(&Move::from_long_str(move_str), &move_gen_masks, &HASHER)
+                .unwrap();
+        }
+        assert_ne!(board.pawn_key, start.pawn_key);
+        assert_ne!(board.material_key, start.material_key);
+
+        let same_material = Board::from_fen(
+            "rnbqkbnr/pppp1ppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 2",
+            &HASHER,
+        )
+        .unwrap();
+        assert_eq!(board.material_key, same_material.material_key);
+        assert_eq!(board.material_key, HASHER.hash_material(&board));
+        assert_eq!(board.pawn_key, HASHER.hash_pawns(&board));
+        for color in [Color::WHITE, Color::BLACK] {
+            assert_eq!(
+                board.non_pawn_keys[color],
+                HASHER.hash_non_pawns(&board, color)
+            );
+        }
+    }
+
     #[test]
     fn test_zobrist_hash_everything_9() {
         let board = Board::from_fen(
@@ -392,9 +553,9 @@ mod test_zobrist {
             for second_legal_move in board.get_legal_moves(&move_gen_masks, &HASHER) {
                 let umh2 = board.make_move(&second_legal_move, &HASHER);
                 assert_eq!(board.zobrist, HASHER.hash_everyting(&board));
-                board.unmake_move(umh2);
+                board.unmake_move(umh2, &HASHER);
             }
-            board.unmake_move(umh);
+            board.unmake_move(umh, &HASHER);
             assert_eq!(board.zobrist, HASHER.hash_everyting(&board));
         }
     }